@@ -0,0 +1,1398 @@
+//! Batch capture from manifest files.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::sync::Semaphore;
+use url::Url;
+
+use crate::error::{Error, Result};
+use crate::output::{CaptureMetadata, OutputTemplate};
+use crate::traits::ScreenshotClient;
+use crate::types::{ImageFormat, ScreenshotOptions, ScreenshotRequest, ScreenshotResponse};
+
+const DEFAULT_CONCURRENCY: usize = 4;
+const DEFAULT_MAX_RETRIES: u32 = 2;
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Fields of a manifest item other than the screenshot options.
+const ITEM_FIELDS: &[&str] = &["id", "url", "output"];
+
+/// Screenshot option fields and how CSV cells are parsed for them.
+const OPTION_FIELDS: &[(&str, CellKind)] = &[
+    ("format", CellKind::String),
+    ("quality", CellKind::Number),
+    ("width", CellKind::Number),
+    ("height", CellKind::Number),
+    ("full_page", CellKind::Bool),
+    ("wait_until", CellKind::String),
+    ("wait_for_selector", CellKind::String),
+    ("wait_for_timeout", CellKind::Number),
+    ("device_scale_factor", CellKind::Number),
+    ("store", CellKind::Bool),
+    ("block_ads", CellKind::Bool),
+];
+
+#[derive(Clone, Copy)]
+enum CellKind {
+    String,
+    Number,
+    Bool,
+}
+
+/// A list of screenshots to capture.
+///
+/// Manifests can be written in TOML, JSON or CSV. TOML and JSON manifests
+/// share the same structure: an optional `output_dir`, `output_template`
+/// and `metadata` setting, `defaults` applied to every item, and the `items`
+/// themselves. Each item has a `url`, an optional `output` path and `id`,
+/// and any [`ScreenshotOptions`] field to override the defaults:
+///
+/// ```toml
+/// output_dir = "screenshots"
+/// output_template = "{host}/{path}"
+/// metadata = true
+///
+/// [defaults]
+/// format = "jpeg"
+/// quality = 80
+/// width = 1280
+///
+/// [[items]]
+/// url = "https://example.com"
+/// output = "home.jpg"
+///
+/// [[items]]
+/// url = "https://example.com/pricing"
+/// full_page = true
+/// ```
+///
+/// Items without an `output` are named by the [`OutputTemplate`], or by
+/// their position (e.g. `0002.jpeg`) without one. Templates are rendered
+/// before capture, so `{width}` and `{height}` are the viewport size, dates
+/// and times are those of the start of the run, and `{hash}` is not
+/// available. Checkpoints track these items by URL rather than path, so a
+/// resumed run skips them even if the date changed. With `metadata`, a JSON [`CaptureMetadata`] file is written
+/// next to each image, as by a [`CaptureWriter`](crate::CaptureWriter).
+///
+/// A CSV manifest is a header row followed by one item per row, with the
+/// same column names. Empty cells leave the option unset.
+///
+/// ```csv
+/// url,output,width,full_page
+/// https://example.com,home.png,1280,
+/// https://example.com/pricing,pricing.png,,true
+/// ```
+///
+/// Relative paths are resolved against the current directory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Directory that item outputs are written to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Template for the output paths of items without an `output`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_template: Option<OutputTemplate>,
+
+    /// Whether to write a JSON metadata file next to each image.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub metadata: bool,
+
+    /// Options applied to every item unless the item overrides them.
+    #[serde(default)]
+    pub defaults: ScreenshotOptions,
+
+    /// Screenshots to capture.
+    #[serde(default)]
+    pub items: Vec<ManifestItem>,
+}
+
+/// One screenshot in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestItem {
+    /// Identifier used to track progress. Defaults to the output path, or
+    /// the URL for stored screenshots and outputs named by the manifest's
+    /// `output_template`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// URL to capture.
+    pub url: Url,
+
+    /// File to write the screenshot to, relative to the manifest's
+    /// `output_dir`. Defaults to a path from the manifest's
+    /// `output_template`, or to the item's position, e.g. `0001.png`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+
+    /// Options overriding the manifest defaults.
+    #[serde(flatten)]
+    pub options: ScreenshotOptions,
+}
+
+impl ManifestItem {
+    /// Create an item capturing `url` with the manifest defaults.
+    pub fn new(url: Url) -> Self {
+        Self {
+            id: None,
+            url,
+            output: None,
+            options: ScreenshotOptions::default(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Create a manifest from a list of items.
+    pub fn new(items: impl IntoIterator<Item = ManifestItem>) -> Self {
+        Self {
+            items: items.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Read a manifest file, choosing the format from its extension
+    /// (`.toml`, `.json` or `.csv`).
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+
+        let manifest = match extension.as_deref() {
+            Some("toml") => Self::from_toml(&contents),
+            Some("json") => Self::from_json(&contents),
+            Some("csv") => Self::from_csv(&contents),
+            _ => {
+                return Err(Error::Manifest(format!(
+                    "unsupported manifest format for {}; expected .toml, .json or .csv",
+                    path.display()
+                )))
+            }
+        };
+        manifest.map_err(|e| match e {
+            Error::Manifest(message) => Error::Manifest(format!("{}: {}", path.display(), message)),
+            e => e,
+        })
+    }
+
+    /// Parse a TOML manifest.
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        let value: Value = toml::from_str(contents).map_err(|e| Error::Manifest(e.to_string()))?;
+        Self::from_value(value)
+    }
+
+    /// Parse a JSON manifest.
+    pub fn from_json(contents: &str) -> Result<Self> {
+        let value: Value =
+            serde_json::from_str(contents).map_err(|e| Error::Manifest(e.to_string()))?;
+        Self::from_value(value)
+    }
+
+    /// Parse a CSV manifest with a header row.
+    pub fn from_csv(contents: &str) -> Result<Self> {
+        let mut reader = csv::Reader::from_reader(contents.as_bytes());
+        let headers = reader
+            .headers()
+            .map_err(|e| Error::Manifest(e.to_string()))?
+            .clone();
+
+        let mut items = Vec::new();
+        for (row, record) in reader.records().enumerate() {
+            let record = record.map_err(|e| Error::Manifest(e.to_string()))?;
+            let line = row + 2;
+
+            let mut item = Map::new();
+            for (header, cell) in headers.iter().zip(record.iter()) {
+                let (header, cell) = (header.trim(), cell.trim());
+                if cell.is_empty() {
+                    continue;
+                }
+                let value = parse_cell(header, cell)
+                    .map_err(|message| Error::Manifest(format!("line {}: {}", line, message)))?;
+                item.insert(header.to_string(), value);
+            }
+            items.push(Value::Object(item));
+        }
+
+        let mut manifest = Map::new();
+        manifest.insert("items".to_string(), Value::Array(items));
+        Self::from_value(Value::Object(manifest))
+    }
+
+    fn from_value(value: Value) -> Result<Self> {
+        check_fields(&value)?;
+        serde_json::from_value(value).map_err(|e| Error::Manifest(e.to_string()))
+    }
+}
+
+/// Reject misspelled fields, which serde would otherwise silently ignore.
+fn check_fields(manifest: &Value) -> Result<()> {
+    let Some(manifest) = manifest.as_object() else {
+        return Err(Error::Manifest("expected a table of settings".into()));
+    };
+    let is_option = |key: &str| ScreenshotOptions::FIELDS.contains(&key);
+
+    for key in manifest.keys() {
+        if ![
+            "output_dir",
+            "output_template",
+            "metadata",
+            "defaults",
+            "items",
+        ]
+        .contains(&key.as_str())
+        {
+            return Err(Error::Manifest(format!("unknown field `{}`", key)));
+        }
+    }
+    if let Some(defaults) = manifest.get("defaults").and_then(Value::as_object) {
+        if let Some(key) = defaults.keys().find(|key| !is_option(key)) {
+            return Err(Error::Manifest(format!(
+                "unknown field `{}` in defaults",
+                key
+            )));
+        }
+    }
+    if let Some(items) = manifest.get("items").and_then(Value::as_array) {
+        for (index, item) in items.iter().enumerate() {
+            let Some(item) = item.as_object() else {
+                continue;
+            };
+            if let Some(key) = item
+                .keys()
+                .find(|key| !is_option(key) && !ITEM_FIELDS.contains(&key.as_str()))
+            {
+                return Err(Error::Manifest(format!(
+                    "unknown field `{}` in item {}",
+                    key,
+                    index + 1
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_cell(header: &str, cell: &str) -> std::result::Result<Value, String> {
+    let kind = OPTION_FIELDS
+        .iter()
+        .find(|(name, _)| *name == header)
+        .map(|(_, kind)| *kind)
+        .unwrap_or(CellKind::String);
+
+    match kind {
+        CellKind::String => Ok(Value::String(cell.to_string())),
+        CellKind::Number => cell
+            .parse::<serde_json::Number>()
+            .map(Value::Number)
+            .map_err(|_| format!("invalid number `{}` for `{}`", cell, header)),
+        CellKind::Bool => match cell.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "1" => Ok(Value::Bool(true)),
+            "false" | "no" | "0" => Ok(Value::Bool(false)),
+            _ => Err(format!("invalid boolean `{}` for `{}`", cell, header)),
+        },
+    }
+}
+
+/// Runs the items of a [`Manifest`] with bounded concurrency.
+///
+/// Each item is captured with the manifest defaults merged with its own
+/// options, and written to its output path. Failed captures are retried
+/// with exponential backoff when the error is transient (network errors,
+/// `429` and `5xx` responses); other errors fail the item immediately.
+/// A failed item never stops the rest of the batch.
+///
+/// With a checkpoint file, every completed item is appended to the file as
+/// it finishes. Running the same manifest again skips the items already in
+/// the checkpoint, so an interrupted run resumes where it stopped.
+///
+/// Concurrency bounds the number of captures in flight for this batch; use
+/// a [`RateLimiter`](crate::RateLimiter) on the client to match plan limits.
+///
+/// # Example
+///
+/// ```no_run
+/// use pxshot::{Batch, Manifest, Pxshot};
+///
+/// #[tokio::main]
+/// async fn main() -> pxshot::Result<()> {
+///     let client = Pxshot::new("px_your_api_key");
+///
+///     let report = Batch::builder(Manifest::from_path("screenshots.toml")?)
+///         .concurrency(8)
+///         .checkpoint("screenshots.progress")
+///         .build()?
+///         .run(&client)
+///         .await?;
+///
+///     println!("{}", report);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Batch {
+    manifest: Manifest,
+    concurrency: usize,
+    checkpoint: Option<PathBuf>,
+    max_retries: u32,
+    retry_delay: Duration,
+    per_host_concurrency: Option<usize>,
+}
+
+/// A manifest item resolved against the manifest defaults.
+struct Job {
+    index: usize,
+    key: String,
+    request: ScreenshotRequest,
+    output: Option<PathBuf>,
+}
+
+impl Batch {
+    /// Create a new builder for a batch.
+    pub fn builder(manifest: Manifest) -> BatchBuilder {
+        BatchBuilder {
+            manifest,
+            concurrency: DEFAULT_CONCURRENCY,
+            checkpoint: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            per_host_concurrency: None,
+        }
+    }
+
+    /// The manifest run by this batch.
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Capture every item of the manifest.
+    ///
+    /// Returns an error only if the manifest or checkpoint is unusable;
+    /// failed items are reported in the [`BatchReport`].
+    pub async fn run<C>(&self, client: &C) -> Result<BatchReport>
+    where
+        C: ScreenshotClient + ?Sized,
+    {
+        let started = Instant::now();
+        let jobs = self.jobs(client, Utc::now())?;
+
+        let mut completed = match &self.checkpoint {
+            Some(path) => read_checkpoint(path)?,
+            None => HashMap::new(),
+        };
+        let checkpoint = match &self.checkpoint {
+            Some(path) => Some(Mutex::new(open_checkpoint(path)?)),
+            None => None,
+        };
+
+        let mut items = Vec::with_capacity(jobs.len());
+        let mut pending = Vec::new();
+        for job in jobs {
+            match completed.remove(&job.key) {
+                Some(mut item) => {
+                    item.index = job.index;
+                    item.status = BatchItemStatus::Skipped;
+                    items.push(item);
+                }
+                None => pending.push(job),
+            }
+        }
+
+        // One semaphore per host, so a batch spanning many sites can run
+        // more captures in total than it runs against any one of them.
+        let mut hosts: HashMap<String, Arc<Semaphore>> = HashMap::new();
+        if let Some(limit) = self.per_host_concurrency {
+            for job in &pending {
+                hosts
+                    .entry(host_key(&job.request.url))
+                    .or_insert_with(|| Arc::new(Semaphore::new(limit)));
+            }
+        }
+
+        // Jobs wait for their host before taking one of the batch's slots,
+        // so those queued behind a busy host don't hold slots that jobs for
+        // other hosts could use. That needs every job to be polled at once.
+        let slots = Semaphore::new(self.concurrency);
+        let polled = if hosts.is_empty() {
+            self.concurrency
+        } else {
+            pending.len().max(1)
+        };
+        let captured: Vec<BatchItemReport> = stream::iter(pending)
+            .map(|job| {
+                let host = hosts.get(&host_key(&job.request.url)).cloned();
+                let (slots, checkpoint) = (&slots, checkpoint.as_ref());
+                async move {
+                    let _host = match &host {
+                        Some(host) => {
+                            Some(host.acquire().await.expect("semaphore is never closed"))
+                        }
+                        None => None,
+                    };
+                    let _slot = slots.acquire().await.expect("semaphore is never closed");
+                    self.run_job(client, job, checkpoint).await
+                }
+            })
+            .buffer_unordered(polled)
+            .collect()
+            .await;
+        items.extend(captured);
+        items.sort_by_key(|item| item.index);
+
+        Ok(BatchReport {
+            items,
+            elapsed: started.elapsed(),
+        })
+    }
+
+    /// Resolve the items, naming outputs for a run started at `started_at`.
+    fn jobs<C>(&self, client: &C, started_at: DateTime<Utc>) -> Result<Vec<Job>>
+    where
+        C: ScreenshotClient + ?Sized,
+    {
+        let output_dir = self.manifest.output_dir.as_deref().unwrap_or(Path::new(""));
+        let template = self.manifest.output_template.as_ref();
+        if template.is_some_and(OutputTemplate::needs_image) {
+            return Err(Error::Manifest(
+                "`{hash}` is not available in output templates of manifests, which are \
+                 rendered before capture"
+                    .into(),
+            ));
+        }
+        let mut keys = HashSet::new();
+        let mut templated = HashSet::new();
+        let mut jobs = Vec::with_capacity(self.manifest.items.len());
+
+        for (index, item) in self.manifest.items.iter().enumerate() {
+            // Name outputs after the request the client actually sends,
+            // including any client defaults.
+            let options = self.manifest.defaults.merge(&item.options);
+            let request = client.effective_request(&options.to_request(item.url.clone()));
+            let output = if request.store == Some(true) {
+                None
+            } else {
+                let file = match (&item.output, template) {
+                    (Some(output), _) => output.clone(),
+                    (None, Some(template)) => {
+                        let file = template.render(&request, &[], started_at);
+                        if !templated.insert(file.clone()) {
+                            return Err(Error::Manifest(format!(
+                                "item {} is named `{}` by the output template, like an \
+                                 earlier item; add a placeholder that tells them apart",
+                                index + 1,
+                                file.display()
+                            )));
+                        }
+                        file
+                    }
+                    (None, None) => {
+                        let format = request.format.unwrap_or_default();
+                        PathBuf::from(format!("{:04}.{}", index + 1, format.as_str()))
+                    }
+                };
+                Some(output_dir.join(file))
+            };
+
+            // Templates may include the time of the run, so outputs named by
+            // them can't identify items across runs.
+            let key = match (&item.id, &output) {
+                (Some(id), _) => id.clone(),
+                (None, Some(output)) if item.output.is_some() || template.is_none() => {
+                    output.display().to_string()
+                }
+                (None, _) => item.url.to_string(),
+            };
+            if !keys.insert(key.clone()) {
+                return Err(Error::Manifest(format!(
+                    "item {} duplicates `{}`; give items distinct outputs or ids",
+                    index + 1,
+                    key
+                )));
+            }
+
+            jobs.push(Job {
+                index,
+                key,
+                request,
+                output,
+            });
+        }
+        Ok(jobs)
+    }
+
+    async fn run_job<C>(
+        &self,
+        client: &C,
+        job: Job,
+        checkpoint: Option<&Mutex<File>>,
+    ) -> BatchItemReport
+    where
+        C: ScreenshotClient + ?Sized,
+    {
+        let started = Instant::now();
+        let mut report = BatchItemReport {
+            index: job.index,
+            key: job.key,
+            url: job.request.url.clone(),
+            format: job.request.format.unwrap_or_default(),
+            output: job.output.clone(),
+            stored_url: None,
+            width: None,
+            height: None,
+            bytes: None,
+            attempts: 0,
+            duration: Duration::ZERO,
+            status: BatchItemStatus::Succeeded,
+            error: None,
+        };
+
+        let result = loop {
+            report.attempts += 1;
+            match client.screenshot(job.request.clone()).await {
+                Err(e) if is_transient(&e) && report.attempts <= self.max_retries => {
+                    let backoff = self
+                        .retry_delay
+                        .saturating_mul(2u32.saturating_pow(report.attempts - 1));
+                    tokio::time::sleep(backoff).await;
+                }
+                result => break result,
+            }
+        };
+
+        let result = match result {
+            Ok(response) => {
+                let metadata = self.manifest.metadata.then_some(&job.request);
+                save(&mut report, response, metadata).await
+            }
+            Err(e) => Err(e),
+        };
+        report.duration = started.elapsed();
+
+        match result {
+            Ok(()) => {
+                if let Some(checkpoint) = checkpoint {
+                    if let Err(e) = append_checkpoint(checkpoint, &report) {
+                        report.status = BatchItemStatus::Failed;
+                        report.error = Some(format!("failed to update checkpoint: {}", e));
+                    }
+                }
+            }
+            Err(e) => {
+                report.status = BatchItemStatus::Failed;
+                report.error = Some(e.to_string());
+            }
+        }
+        report
+    }
+}
+
+/// Write a captured screenshot to the item's output, along with its
+/// metadata if the `request` it was captured for is given.
+async fn save(
+    report: &mut BatchItemReport,
+    response: ScreenshotResponse,
+    metadata: Option<&ScreenshotRequest>,
+) -> Result<()> {
+    report.bytes = Some(response.size_bytes());
+
+    match response {
+        ScreenshotResponse::Bytes(bytes) => {
+            let Some(output) = &report.output else {
+                return Ok(());
+            };
+            if let Some(parent) = output
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+            {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            write_file(output, &bytes).await?;
+
+            if let Some(request) = metadata {
+                let mut metadata = CaptureMetadata::new(output, request, &bytes, Utc::now(), None);
+                metadata.attempts = Some(report.attempts);
+                let path = CaptureMetadata::sidecar_path(output);
+                write_file(&path, &metadata.to_json()?).await?;
+            }
+        }
+        ScreenshotResponse::Stored(stored) => {
+            report.stored_url = Some(stored.url);
+            report.width = Some(stored.width);
+            report.height = Some(stored.height);
+        }
+    }
+    Ok(())
+}
+
+/// Write to a temporary file first so an interrupted run never leaves a
+/// truncated file at `path`.
+async fn write_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut partial = path.to_path_buf().into_os_string();
+    partial.push(".partial");
+    tokio::fs::write(&partial, contents).await?;
+    tokio::fs::rename(&partial, path).await?;
+    Ok(())
+}
+
+/// The host and port of `url`, which captures are limited per.
+fn host_key(url: &Url) -> String {
+    format!(
+        "{}:{}",
+        url.host_str().unwrap_or_default(),
+        url.port_or_known_default().unwrap_or_default()
+    )
+}
+
+/// Whether a failed capture is worth retrying.
+fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::Api { status, .. } => *status == 429 || *status >= 500,
+        Error::Request(e) => e.is_timeout() || e.is_connect() || e.is_request(),
+        _ => false,
+    }
+}
+
+fn read_checkpoint(path: &Path) -> Result<HashMap<String, BatchItemReport>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut completed = HashMap::new();
+    for line in BufReader::new(file).lines() {
+        // A line cut short by an interrupted write is ignored; that item is
+        // simply captured again.
+        if let Ok(item) = serde_json::from_str::<BatchItemReport>(&line?) {
+            completed.insert(item.key.clone(), item);
+        }
+    }
+    Ok(completed)
+}
+
+fn open_checkpoint(path: &Path) -> Result<File> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+    // Start on a fresh line if the previous run was cut off mid-write.
+    let len = file.metadata()?.len();
+    if len > 0 && fs::read(path)?.last() != Some(&b'\n') {
+        (&file).write_all(b"\n")?;
+    }
+    Ok(file)
+}
+
+fn append_checkpoint(checkpoint: &Mutex<File>, report: &BatchItemReport) -> Result<()> {
+    let mut line = serde_json::to_vec(report)
+        .map_err(|e| Error::Manifest(format!("failed to serialize checkpoint: {}", e)))?;
+    line.push(b'\n');
+
+    let mut file = checkpoint.lock().unwrap_or_else(|e| e.into_inner());
+    file.write_all(&line)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// `path` with `extension`, numbered if another page already maps to it.
+#[cfg(any(feature = "sitemap", feature = "crawl"))]
+pub(crate) fn unique_path(taken: &mut HashSet<PathBuf>, path: PathBuf, extension: &str) -> PathBuf {
+    let name = path.to_string_lossy().into_owned();
+    let mut candidate = PathBuf::from(format!("{}.{}", name, extension));
+    let mut n = 2;
+    while !taken.insert(candidate.clone()) {
+        candidate = PathBuf::from(format!("{}-{}.{}", name, n, extension));
+        n += 1;
+    }
+    candidate
+}
+
+/// Match `text` against a pattern where `*` matches any run of characters
+/// and `?` any single character.
+#[cfg(any(feature = "sitemap", feature = "crawl"))]
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` seen, and the text position it matched up to.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Builder for [`Batch`].
+#[derive(Debug)]
+pub struct BatchBuilder {
+    manifest: Manifest,
+    concurrency: usize,
+    checkpoint: Option<PathBuf>,
+    max_retries: u32,
+    retry_delay: Duration,
+    per_host_concurrency: Option<usize>,
+}
+
+impl BatchBuilder {
+    /// Maximum number of captures in flight at once (default: 4).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Maximum number of captures in flight against any one host (default:
+    /// no limit besides [`concurrency`](Self::concurrency)).
+    ///
+    /// Items waiting for a busy host don't hold back items for other hosts.
+    pub fn per_host_concurrency(mut self, concurrency: usize) -> Self {
+        self.per_host_concurrency = Some(concurrency);
+        self
+    }
+
+    /// Record progress in `path` and skip items it lists as completed.
+    pub fn checkpoint(mut self, path: impl Into<PathBuf>) -> Self {
+        self.checkpoint = Some(path.into());
+        self
+    }
+
+    /// Maximum number of retries per item for transient errors (default: 2).
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    /// Delay before the first retry, doubled for each further retry
+    /// (default: 1 second).
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Build the batch.
+    pub fn build(self) -> Result<Batch> {
+        if self.concurrency == 0 {
+            return Err(Error::Config("concurrency must be at least 1".into()));
+        }
+        if self.per_host_concurrency == Some(0) {
+            return Err(Error::Config(
+                "per-host concurrency must be at least 1".into(),
+            ));
+        }
+
+        Ok(Batch {
+            manifest: self.manifest,
+            concurrency: self.concurrency,
+            checkpoint: self.checkpoint,
+            max_retries: self.max_retries,
+            retry_delay: self.retry_delay,
+            per_host_concurrency: self.per_host_concurrency,
+        })
+    }
+}
+
+/// Outcome of one manifest item.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchItemStatus {
+    /// The screenshot was captured in this run.
+    Succeeded,
+    /// The screenshot was captured in a previous run, according to the
+    /// checkpoint.
+    Skipped,
+    /// The screenshot could not be captured or saved.
+    Failed,
+}
+
+/// Result of capturing one manifest item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchItemReport {
+    /// Position of the item in the manifest.
+    pub index: usize,
+
+    /// Identifier used to track progress.
+    pub key: String,
+
+    /// URL captured.
+    pub url: Url,
+
+    /// Image format requested.
+    pub format: ImageFormat,
+
+    /// File the screenshot was written to (`None` for stored screenshots).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+
+    /// URL of the stored screenshot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stored_url: Option<String>,
+
+    /// Width of the stored screenshot in pixels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+
+    /// Height of the stored screenshot in pixels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+
+    /// Size of the screenshot in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+
+    /// Number of capture attempts made.
+    pub attempts: u32,
+
+    /// Time taken, including retries.
+    #[serde(rename = "duration_ms", with = "duration_ms")]
+    pub duration: Duration,
+
+    /// Outcome of the item.
+    pub status: BatchItemStatus,
+
+    /// Error message of the last attempt, if the item failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Summary of a batch run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchReport {
+    /// Every manifest item, in manifest order.
+    pub items: Vec<BatchItemReport>,
+
+    /// Total time taken by the run.
+    #[serde(rename = "elapsed_ms", with = "duration_ms")]
+    pub elapsed: Duration,
+}
+
+impl BatchReport {
+    /// Number of items captured in this run.
+    pub fn succeeded(&self) -> usize {
+        self.count(BatchItemStatus::Succeeded)
+    }
+
+    /// Number of items skipped because a previous run completed them.
+    pub fn skipped(&self) -> usize {
+        self.count(BatchItemStatus::Skipped)
+    }
+
+    /// Number of items that failed.
+    pub fn failed(&self) -> usize {
+        self.count(BatchItemStatus::Failed)
+    }
+
+    /// Whether every item has been captured, in this run or a previous one.
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+
+    /// Items that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &BatchItemReport> {
+        self.items
+            .iter()
+            .filter(|item| item.status == BatchItemStatus::Failed)
+    }
+
+    /// Write the report to `path` as JSON.
+    pub fn write_json(&self, path: impl AsRef<Path>) -> Result<()> {
+        let contents = serde_json::to_vec_pretty(self)
+            .map_err(|e| Error::Manifest(format!("failed to serialize report: {}", e)))?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    fn count(&self, status: BatchItemStatus) -> usize {
+        self.items
+            .iter()
+            .filter(|item| item.status == status)
+            .count()
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} captured, {} skipped, {} failed in {:.1}s",
+            self.succeeded(),
+            self.skipped(),
+            self.failed(),
+            self.elapsed.as_secs_f64()
+        )?;
+        for item in self.failures() {
+            write!(
+                f,
+                "\n  {} ({}): {}",
+                item.key,
+                item.url,
+                item.error.as_deref().unwrap_or("unknown error")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+mod duration_ms {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        duration: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis().try_into().unwrap_or(u64::MAX))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::testing::MockClient;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    /// An empty directory under the system temp directory for one test.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("pxshot-batch-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn keys(manifest: &Manifest, started_at: DateTime<Utc>) -> Result<Vec<String>> {
+        let batch = Batch::builder(manifest.clone()).build()?;
+        let jobs = batch.jobs(&MockClient::new(), started_at)?;
+        Ok(jobs.into_iter().map(|job| job.key).collect())
+    }
+
+    #[test]
+    fn templated_outputs_keep_keys_across_runs() {
+        let manifest = Manifest::from_toml(
+            r#"
+            output_template = "{date}/{path_slug}"
+
+            [[items]]
+            url = "https://example.com/a"
+
+            [[items]]
+            url = "https://example.com/b"
+            output = "b.png"
+
+            [[items]]
+            id = "c"
+            url = "https://example.com/c"
+            "#,
+        )
+        .unwrap();
+        let monday = Utc.with_ymd_and_hms(2024, 5, 6, 9, 0, 0).unwrap();
+        let tuesday = Utc.with_ymd_and_hms(2024, 5, 7, 9, 0, 0).unwrap();
+
+        let expected = ["https://example.com/a", "b.png", "c"];
+        assert_eq!(keys(&manifest, monday).unwrap(), expected);
+        assert_eq!(keys(&manifest, tuesday).unwrap(), expected);
+
+        let jobs = Batch::builder(manifest)
+            .build()
+            .unwrap()
+            .jobs(&MockClient::new(), tuesday)
+            .unwrap();
+        assert_eq!(jobs[0].output, Some(PathBuf::from("2024-05-07/a.png")));
+    }
+
+    #[test]
+    fn templated_outputs_must_differ() {
+        let manifest = Manifest::from_toml(
+            r#"
+            output_template = "{host}"
+
+            [[items]]
+            url = "https://example.com/a"
+
+            [[items]]
+            url = "https://example.com/b"
+            "#,
+        )
+        .unwrap();
+        let error = keys(&manifest, Utc::now()).unwrap_err().to_string();
+        assert!(
+            error.contains("item 2 is named `example.com.png`"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn parses_toml_manifest() {
+        let manifest = Manifest::from_toml(
+            r#"
+            output_dir = "shots"
+            metadata = true
+
+            [defaults]
+            format = "jpeg"
+            quality = 80
+
+            [[items]]
+            url = "https://example.com"
+            output = "home.jpg"
+
+            [[items]]
+            id = "pricing"
+            url = "https://example.com/pricing"
+            full_page = true
+            width = 1440
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.output_dir, Some(PathBuf::from("shots")));
+        assert!(manifest.metadata);
+        assert_eq!(manifest.defaults.format, Some(ImageFormat::Jpeg));
+        assert_eq!(manifest.defaults.quality, Some(80));
+        assert_eq!(manifest.items.len(), 2);
+        assert_eq!(manifest.items[0].output, Some(PathBuf::from("home.jpg")));
+        assert_eq!(manifest.items[1].id.as_deref(), Some("pricing"));
+        assert_eq!(manifest.items[1].options.full_page, Some(true));
+        assert_eq!(manifest.items[1].options.width, Some(1440));
+    }
+
+    #[test]
+    fn parses_json_manifest() {
+        let manifest = Manifest::from_json(
+            r#"{
+                "defaults": {"width": 800},
+                "items": [
+                    {"url": "https://example.com/a", "store": true},
+                    {"url": "https://example.com/b", "format": "webp"}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.defaults.width, Some(800));
+        assert_eq!(manifest.items[0].url, url("https://example.com/a"));
+        assert_eq!(manifest.items[0].options.store, Some(true));
+        assert_eq!(manifest.items[1].options.format, Some(ImageFormat::Webp));
+    }
+
+    #[test]
+    fn parses_csv_manifest() {
+        let manifest = Manifest::from_csv(
+            "url, output ,width,full_page,quality\n\
+             https://example.com,home.png,1280,,\n\
+             https://example.com/pricing,pricing.png,, yes ,90\n",
+        )
+        .unwrap();
+
+        let [home, pricing] = &manifest.items[..] else {
+            panic!("expected two items, got {:?}", manifest.items);
+        };
+        assert_eq!(home.output, Some(PathBuf::from("home.png")));
+        assert_eq!(home.options.width, Some(1280));
+        assert_eq!(
+            home.options.full_page, None,
+            "empty cells leave options unset"
+        );
+        assert_eq!(pricing.options.full_page, Some(true));
+        assert_eq!(pricing.options.quality, Some(90));
+    }
+
+    #[test]
+    fn csv_cells_are_validated() {
+        let error = Manifest::from_csv("url,width\nhttps://example.com,wide\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("line 2: invalid number `wide` for `width`"),
+            "{}",
+            error
+        );
+
+        let error = Manifest::from_csv("url,store\nhttps://example.com,maybe\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("invalid boolean `maybe`"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let cases = [
+            ("output_directory = \"x\"", "unknown field `output_directory`"),
+            ("[defaults]\nfullpage = true", "unknown field `fullpage` in defaults"),
+            (
+                "[[items]]\nurl = \"https://example.com\"\n[[items]]\nurl = \"https://example.com/b\"\nfile = \"b.png\"",
+                "unknown field `file` in item 2",
+            ),
+        ];
+        for (toml, expected) in cases {
+            let error = Manifest::from_toml(toml).unwrap_err().to_string();
+            assert!(error.contains(expected), "{:?}: {}", toml, error);
+        }
+
+        let error = Manifest::from_csv("url,colour\nhttps://example.com,red\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("unknown field `colour` in item 1"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_urls() {
+        assert!(Manifest::from_json(r#"{"items": [{"url": "not a url"}]}"#).is_err());
+        assert!(Manifest::from_json(r#"{"items": [{"output": "a.png"}]}"#).is_err());
+    }
+
+    #[test]
+    fn from_path_requires_a_known_extension() {
+        let dir = scratch_dir("extension");
+        let path = dir.join("manifest.yaml");
+        fs::write(&path, "items: []").unwrap();
+
+        let error = Manifest::from_path(&path).unwrap_err().to_string();
+        assert!(error.contains("unsupported manifest format"), "{}", error);
+
+        let path = dir.join("manifest.JSON");
+        fs::write(
+            &path,
+            r#"{"items": [{"url": "https://example.com", "bogus": 1}]}"#,
+        )
+        .unwrap();
+        let error = Manifest::from_path(&path).unwrap_err().to_string();
+        assert!(
+            error.contains("manifest.JSON: unknown field `bogus`"),
+            "{}",
+            error
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn default_outputs_are_numbered_by_position() {
+        let manifest = Manifest::from_toml(
+            r#"
+            output_dir = "shots"
+            [defaults]
+            format = "jpeg"
+            [[items]]
+            url = "https://example.com/a"
+            [[items]]
+            url = "https://example.com/b"
+            format = "png"
+            [[items]]
+            url = "https://example.com/c"
+            store = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            keys(&manifest, Utc::now()).unwrap(),
+            [
+                Path::new("shots").join("0001.jpeg").display().to_string(),
+                Path::new("shots").join("0002.png").display().to_string(),
+                "https://example.com/c".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_keys_are_rejected() {
+        let cases = [
+            // Same explicit output.
+            r#"
+            [[items]]
+            url = "https://example.com/a"
+            output = "a.png"
+            [[items]]
+            url = "https://example.com/b"
+            output = "a.png"
+            "#,
+            // Same id.
+            r#"
+            [[items]]
+            id = "home"
+            url = "https://example.com/a"
+            [[items]]
+            id = "home"
+            url = "https://example.com/b"
+            "#,
+            // Stored screenshots of the same URL.
+            r#"
+            [defaults]
+            store = true
+            [[items]]
+            url = "https://example.com/a"
+            [[items]]
+            url = "https://example.com/a"
+            "#,
+        ];
+        for toml in cases {
+            let manifest = Manifest::from_toml(toml).unwrap();
+            let error = keys(&manifest, Utc::now()).unwrap_err().to_string();
+            assert!(error.contains("item 2 duplicates"), "{}: {}", toml, error);
+        }
+    }
+
+    #[tokio::test]
+    async fn checkpoint_resumes_where_the_last_run_stopped() {
+        let dir = scratch_dir("checkpoint");
+        let checkpoint = dir.join("progress.jsonl");
+        let mut manifest = Manifest::new(
+            ["a", "b", "c"]
+                .map(|page| ManifestItem::new(url(&format!("https://example.com/{}", page)))),
+        );
+        manifest.output_dir = Some(dir.clone());
+        let batch = Batch::builder(manifest)
+            .concurrency(1)
+            .checkpoint(&checkpoint)
+            .build()
+            .unwrap();
+
+        let client = MockClient::new();
+        client.push_screenshot(ScreenshotResponse::Bytes(b"a".to_vec()));
+        client.push_screenshot_error(Error::Api {
+            status: 400,
+            message: "bad request".into(),
+        });
+        client.push_screenshot(ScreenshotResponse::Bytes(b"c".to_vec()));
+        let report = batch.run(&client).await.unwrap();
+        assert_eq!(
+            (report.succeeded(), report.skipped(), report.failed()),
+            (2, 0, 1),
+            "{}",
+            report
+        );
+        assert_eq!(report.items[1].attempts, 1, "client errors are not retried");
+        assert_eq!(fs::read(dir.join("0001.png")).unwrap(), b"a");
+
+        let client = MockClient::new();
+        client.respond_with(|_| Ok(ScreenshotResponse::Bytes(b"b".to_vec())));
+        let report = batch.run(&client).await.unwrap();
+        assert_eq!(
+            (report.succeeded(), report.skipped(), report.failed()),
+            (1, 2, 0),
+            "{}",
+            report
+        );
+        assert!(report.is_success());
+        let requested: Vec<_> = client
+            .requests()
+            .into_iter()
+            .map(|request| request.url)
+            .collect();
+        assert_eq!(requested, [url("https://example.com/b")]);
+        assert_eq!(
+            report
+                .items
+                .iter()
+                .map(|item| item.index)
+                .collect::<Vec<_>>(),
+            [0, 1, 2],
+            "skipped items keep their manifest position"
+        );
+        assert_eq!(fs::read(dir.join("0002.png")).unwrap(), b"b");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn checkpoint_ignores_truncated_lines() {
+        let dir = scratch_dir("truncated");
+        let checkpoint = dir.join("progress.jsonl");
+        fs::write(&checkpoint, r#"{"index":0,"key":"#).unwrap();
+
+        let mut manifest = Manifest::new([ManifestItem::new(url("https://example.com/a"))]);
+        manifest.output_dir = Some(dir.clone());
+        let batch = Batch::builder(manifest)
+            .checkpoint(&checkpoint)
+            .build()
+            .unwrap();
+        let client = MockClient::new();
+        client.respond_with(|_| Ok(ScreenshotResponse::Bytes(b"a".to_vec())));
+
+        let report = batch.run(&client).await.unwrap();
+        assert_eq!(report.succeeded(), 1, "{}", report);
+        let contents = fs::read_to_string(&checkpoint).unwrap();
+        assert_eq!(
+            contents.lines().count(),
+            2,
+            "the new entry starts on a fresh line"
+        );
+        assert_eq!(read_checkpoint(&checkpoint).unwrap().len(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn transient_errors_are_retried() {
+        let dir = scratch_dir("retry");
+        let mut manifest = Manifest::new([ManifestItem::new(url("https://example.com/a"))]);
+        manifest.output_dir = Some(dir.clone());
+        let batch = Batch::builder(manifest)
+            .retry_delay(Duration::ZERO)
+            .build()
+            .unwrap();
+
+        let client = MockClient::new();
+        client.push_screenshot_error(Error::Api {
+            status: 503,
+            message: "unavailable".into(),
+        });
+        client.push_screenshot(ScreenshotResponse::Bytes(b"a".to_vec()));
+        let report = batch.run(&client).await.unwrap();
+        assert!(report.is_success(), "{}", report);
+        assert_eq!(report.items[0].attempts, 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,488 @@
+//! Command-line interface for the Pxshot screenshot API.
+
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use pxshot::{
+    CaptureWriter, Error, ImageFormat, Pxshot, ScreenshotRequest, ScreenshotResponse, WaitUntil,
+};
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0   success
+  2   invalid command-line arguments
+  65  the API rejected the request (4xx)
+  69  the API or network is unavailable (5xx, connection errors)
+  70  internal error
+  74  failed to read or write a file
+  75  rate limited or over budget; try again later
+  76  unexpected response from the API
+  77  the API key is missing or not authorized
+  78  invalid configuration";
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "pxshot",
+    version,
+    about = "Capture screenshots with the Pxshot API"
+)]
+#[command(after_help = EXIT_CODES)]
+struct Cli {
+    /// API key.
+    #[arg(long, env = "PXSHOT_API_KEY", hide_env_values = true, global = true)]
+    api_key: Option<String>,
+
+    /// API base URL.
+    #[arg(long, env = "PXSHOT_BASE_URL", global = true)]
+    base_url: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Capture a screenshot.
+    Capture(CaptureArgs),
+
+    /// Show API usage for the current billing period.
+    Usage {
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        output: OutputFormat,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+struct CaptureArgs {
+    /// URL to capture.
+    url: String,
+
+    /// File to write the image to, or `-` for stdout.
+    ///
+    /// Defaults to stdout when it is not a terminal.
+    #[arg(short, long, conflicts_with = "store")]
+    output: Option<PathBuf>,
+
+    /// Save the image to a path from a template, e.g.
+    /// `{host}/{date}/{path_slug}.{ext}`.
+    #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["output", "store"])]
+    output_template: Option<String>,
+
+    /// Write a JSON metadata file next to the image saved with
+    /// --output-template.
+    #[arg(long, requires = "output_template")]
+    metadata: bool,
+
+    /// Image format.
+    #[arg(short, long, value_enum)]
+    format: Option<FormatArg>,
+
+    /// Image quality (1-100, only for JPEG/WebP).
+    #[arg(short, long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: Option<u8>,
+
+    /// Viewport width in pixels.
+    #[arg(short = 'W', long)]
+    width: Option<u32>,
+
+    /// Viewport height in pixels.
+    #[arg(short = 'H', long)]
+    height: Option<u32>,
+
+    /// Capture the full scrollable page; `--full-page=false` captures only
+    /// the viewport.
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    full_page: Option<bool>,
+
+    /// When to consider navigation succeeded.
+    #[arg(long, value_enum)]
+    wait_until: Option<WaitUntilArg>,
+
+    /// Wait for a CSS selector before capturing.
+    #[arg(long, value_name = "SELECTOR")]
+    wait_for_selector: Option<String>,
+
+    /// Additional wait time in milliseconds after page load.
+    #[arg(long, value_name = "MS")]
+    wait_for_timeout: Option<u32>,
+
+    /// Device scale factor (1-3).
+    #[arg(long, value_name = "FACTOR")]
+    device_scale_factor: Option<f32>,
+
+    /// Store the screenshot and print its URL instead of downloading it.
+    #[arg(long)]
+    store: bool,
+
+    /// Block ads and trackers; `--block-ads=false` allows them.
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    block_ads: Option<bool>,
+
+    /// Output format for stored screenshot details.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, requires = "store")]
+    info: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum FormatArg {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl From<FormatArg> for ImageFormat {
+    fn from(format: FormatArg) -> Self {
+        match format {
+            FormatArg::Png => ImageFormat::Png,
+            FormatArg::Jpeg => ImageFormat::Jpeg,
+            FormatArg::Webp => ImageFormat::Webp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum WaitUntilArg {
+    Load,
+    DomContentLoaded,
+    NetworkIdle,
+}
+
+impl From<WaitUntilArg> for WaitUntil {
+    fn from(wait_until: WaitUntilArg) -> Self {
+        match wait_until {
+            WaitUntilArg::Load => WaitUntil::Load,
+            WaitUntilArg::DomContentLoaded => WaitUntil::DomContentLoaded,
+            WaitUntilArg::NetworkIdle => WaitUntil::NetworkIdle,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut cli = Cli::parse();
+
+    let Some(api_key) = cli.api_key.take().filter(|key| !key.is_empty()) else {
+        eprintln!("error: no API key; set PXSHOT_API_KEY or pass --api-key");
+        return ExitCode::from(77);
+    };
+
+    match run(cli, api_key).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+async fn run(cli: Cli, api_key: String) -> pxshot::Result<()> {
+    let mut builder = Pxshot::builder(api_key);
+    if let Some(base_url) = cli.base_url {
+        builder = builder.base_url(base_url);
+    }
+    let client = builder.build()?;
+
+    match cli.command {
+        Command::Capture(args) => capture(&client, args).await,
+        Command::Usage { output } => usage(&client, output).await,
+    }
+}
+
+async fn capture(client: &Pxshot, args: CaptureArgs) -> pxshot::Result<()> {
+    let mut request = ScreenshotRequest::builder().url(args.url);
+    if let Some(format) = args.format {
+        request = request.format(format.into());
+    }
+    if let Some(quality) = args.quality {
+        request = request.quality(quality);
+    }
+    if let Some(width) = args.width {
+        request = request.width(width);
+    }
+    if let Some(height) = args.height {
+        request = request.height(height);
+    }
+    if let Some(full_page) = args.full_page {
+        request = request.full_page(full_page);
+    }
+    if let Some(wait_until) = args.wait_until {
+        request = request.wait_until(wait_until.into());
+    }
+    if let Some(selector) = args.wait_for_selector {
+        request = request.wait_for_selector(selector);
+    }
+    if let Some(timeout) = args.wait_for_timeout {
+        request = request.wait_for_timeout(timeout);
+    }
+    if let Some(factor) = args.device_scale_factor {
+        request = request.device_scale_factor(factor);
+    }
+    if args.store {
+        request = request.store(true);
+    }
+    if let Some(block_ads) = args.block_ads {
+        request = request.block_ads(block_ads);
+    }
+
+    if let Some(template) = args.output_template {
+        let writer = CaptureWriter::builder(template)
+            .sidecars(args.metadata)
+            .build()?;
+        let saved = writer.capture(client, request.build()?).await?;
+        eprintln!(
+            "Saved {} ({} bytes)",
+            saved.path.display(),
+            saved.metadata.size_bytes
+        );
+        return Ok(());
+    }
+
+    // Refuse to write image data to a terminal before spending a capture.
+    let to_stdout = match &args.output {
+        Some(path) => path.as_os_str() == "-",
+        None if args.store => false,
+        None if io::stdout().is_terminal() => {
+            return Err(Error::Config(
+                "refusing to write image data to a terminal; use --output".to_string(),
+            ))
+        }
+        None => true,
+    };
+
+    match client.screenshot(request.build()?).await? {
+        ScreenshotResponse::Bytes(bytes) => {
+            if to_stdout {
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(&bytes)?;
+                stdout.flush()?;
+            } else if let Some(path) = &args.output {
+                std::fs::write(path, &bytes)?;
+                eprintln!("Saved {} ({} bytes)", path.display(), bytes.len());
+            }
+        }
+        ScreenshotResponse::Stored(stored) => match args.info {
+            OutputFormat::Json => print_json(&serde_json::json!({
+                "url": stored.url,
+                "expires_at": stored.expires_at,
+                "width": stored.width,
+                "height": stored.height,
+                "size_bytes": stored.size_bytes,
+            }))?,
+            OutputFormat::Table => print_table(&[
+                ("URL", stored.url),
+                ("Expires", stored.expires_at.to_rfc3339()),
+                ("Dimensions", format!("{}x{}", stored.width, stored.height)),
+                ("Size", format_bytes(stored.size_bytes)),
+            ]),
+        },
+    }
+
+    Ok(())
+}
+
+async fn usage(client: &Pxshot, output: OutputFormat) -> pxshot::Result<()> {
+    let usage = client.usage().await?;
+
+    match output {
+        OutputFormat::Json => print_json(&usage)?,
+        OutputFormat::Table => {
+            let projection = usage.project();
+            print_table(&[
+                (
+                    "Period",
+                    format!(
+                        "{} to {}",
+                        usage.period_start.date_naive(),
+                        usage.period_end.date_naive()
+                    ),
+                ),
+                ("Screenshots", usage.screenshots.to_string()),
+                ("Bytes", format_bytes(usage.bytes)),
+                (
+                    "Projected",
+                    format!("{} screenshots", projection.screenshots),
+                ),
+            ]);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_json(value: &impl serde::Serialize) -> pxshot::Result<()> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| Error::Parse(format!("failed to serialize output: {}", e)))?;
+    println!("{}", json);
+    Ok(())
+}
+
+fn print_table(rows: &[(&str, String)]) {
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    for (label, value) in rows {
+        println!("{:<width$}  {}", label, value, width = width);
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    format!("{:.1} {} ({} bytes)", value, unit, bytes)
+}
+
+/// Map an SDK error to a `sysexits.h`-style exit code.
+fn exit_code(error: &Error) -> u8 {
+    match error {
+        Error::Api { status, .. } => match status {
+            401 | 403 => 77,
+            429 => 75,
+            400..=499 => 65,
+            _ => 69,
+        },
+        Error::BudgetExceeded { .. } => 75,
+        Error::Request(e) if e.is_builder() => 78,
+        Error::Request(_) => 69,
+        Error::Parse(_) => 76,
+        Error::Io(_) => 74,
+        Error::MissingField(_) | Error::InvalidField { .. } | Error::PolicyViolation { .. } => 65,
+        Error::Config(_) => 78,
+        _ => 70,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::CommandFactory;
+    use pxshot::BudgetResource;
+
+    fn capture_args(args: &[&str]) -> CaptureArgs {
+        let cli = Cli::try_parse_from(["pxshot", "capture"].iter().chain(args)).unwrap();
+        match cli.command {
+            Command::Capture(args) => args,
+            command => panic!("expected capture, got {:?}", command),
+        }
+    }
+
+    fn api(status: u16) -> Error {
+        Error::Api {
+            status,
+            message: "error".into(),
+        }
+    }
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn boolean_flags_can_be_turned_off() {
+        let args = capture_args(&["https://example.com"]);
+        assert_eq!((args.full_page, args.block_ads), (None, None));
+
+        let args = capture_args(&["--full-page", "--block-ads", "https://example.com"]);
+        assert_eq!((args.full_page, args.block_ads), (Some(true), Some(true)));
+        assert_eq!(args.url, "https://example.com");
+
+        let args = capture_args(&[
+            "https://example.com",
+            "--full-page=false",
+            "--block-ads=false",
+        ]);
+        assert_eq!((args.full_page, args.block_ads), (Some(false), Some(false)));
+
+        assert!(Cli::try_parse_from([
+            "pxshot",
+            "capture",
+            "https://example.com",
+            "--full-page=maybe"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn output_options_conflict() {
+        let parse = |args: &[&str]| {
+            Cli::try_parse_from(
+                ["pxshot", "capture", "https://example.com"]
+                    .iter()
+                    .chain(args),
+            )
+        };
+        assert!(parse(&["--output", "a.png", "--store"]).is_err());
+        assert!(parse(&["--output-template", "{host}.{ext}", "--output", "a.png"]).is_err());
+        assert!(parse(&["--metadata"]).is_err());
+        assert!(parse(&["--output-template", "{host}.{ext}", "--metadata"]).is_ok());
+        assert!(parse(&["--quality", "0"]).is_err());
+    }
+
+    #[test]
+    fn api_errors_map_to_exit_codes() {
+        assert_eq!(exit_code(&api(401)), 77);
+        assert_eq!(exit_code(&api(403)), 77);
+        assert_eq!(exit_code(&api(429)), 75);
+        assert_eq!(exit_code(&api(400)), 65);
+        assert_eq!(exit_code(&api(404)), 65);
+        assert_eq!(exit_code(&api(500)), 69);
+        assert_eq!(exit_code(&api(503)), 69);
+    }
+
+    #[test]
+    fn other_errors_map_to_exit_codes() {
+        let budget = Error::BudgetExceeded {
+            resource: BudgetResource::Screenshots,
+            used: 10,
+            limit: 10,
+        };
+        assert_eq!(exit_code(&budget), 75);
+        assert_eq!(exit_code(&Error::Parse("bad json".into())), 76);
+        assert_eq!(exit_code(&Error::Io(io::Error::other("disk full"))), 74);
+        assert_eq!(exit_code(&Error::MissingField("url")), 65);
+        assert_eq!(
+            exit_code(&Error::PolicyViolation {
+                url: "http://169.254.169.254/".into(),
+                reason: "metadata address".into(),
+            }),
+            65
+        );
+        assert_eq!(exit_code(&Error::Config("bad".into())), 78);
+        assert_eq!(exit_code(&Error::Image("bad".into())), 70);
+
+        let builder = reqwest::Client::new().get("not a url").build().unwrap_err();
+        assert_eq!(exit_code(&Error::Request(builder)), 78);
+    }
+}
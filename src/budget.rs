@@ -0,0 +1,567 @@
+//! Per-period quota guard built on usage statistics.
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+use crate::error::{Error, Result};
+use crate::types::Usage;
+
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+/// A resource limited by a [`BudgetGuard`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BudgetResource {
+    /// Number of screenshots taken this period.
+    Screenshots,
+    /// Bytes of screenshots taken this period.
+    Bytes,
+}
+
+impl fmt::Display for BudgetResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Screenshots => f.write_str("screenshots"),
+            Self::Bytes => f.write_str("bytes"),
+        }
+    }
+}
+
+/// Notification that usage crossed a configured threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetAlert {
+    /// Resource that crossed the threshold.
+    pub resource: BudgetResource,
+    /// Threshold that was crossed, as a fraction of the budget (e.g. `0.8`).
+    pub threshold: f64,
+    /// Amount used this period.
+    pub used: u64,
+    /// Budget for the period.
+    pub limit: u64,
+}
+
+/// Snapshot of the usage tracked by a [`BudgetGuard`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetStatus {
+    /// Screenshots taken this period, including captures in flight.
+    pub screenshots: u64,
+    /// Bytes of screenshots taken this period.
+    pub bytes: u64,
+    /// Screenshot budget, if configured.
+    pub max_screenshots: Option<u64>,
+    /// Byte budget, if configured.
+    pub max_bytes: Option<u64>,
+    /// End of the current billing period, once known.
+    pub period_end: Option<DateTime<Utc>>,
+}
+
+type AlertCallback = Arc<dyn Fn(&BudgetAlert) + Send + Sync>;
+
+/// Guard that enforces a per-period screenshot or byte budget.
+///
+/// The guard is seeded from [`Pxshot::usage`](crate::Pxshot::usage) before the
+/// first capture, refreshed periodically, and tracks captures locally in
+/// between. Once a budget is reached, new captures fail with
+/// [`Error::BudgetExceeded`] without calling the API.
+///
+/// Cloning a guard is cheap and every clone shares the same counters.
+///
+/// # Example
+///
+/// ```
+/// use pxshot::{BudgetGuard, Pxshot};
+///
+/// # fn main() -> pxshot::Result<()> {
+/// let budget = BudgetGuard::builder()
+///     .max_screenshots(10_000)
+///     .alert_at(0.8)
+///     .alert_at(0.95)
+///     .on_alert(|alert| {
+///         eprintln!(
+///             "{:.0}% of the {} budget used",
+///             alert.threshold * 100.0,
+///             alert.resource
+///         );
+///     })
+///     .build()?;
+///
+/// let client = Pxshot::builder("px_your_api_key").budget(budget).build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BudgetGuard {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    max_screenshots: Option<u64>,
+    max_bytes: Option<u64>,
+    thresholds: Vec<f64>,
+    callbacks: Vec<AlertCallback>,
+    refresh_interval: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    screenshots: u64,
+    bytes: u64,
+    pending: u64,
+    period_start: Option<DateTime<Utc>>,
+    period_end: Option<DateTime<Utc>>,
+    refreshed_at: Option<Instant>,
+    fired: Vec<(BudgetResource, usize)>,
+}
+
+impl fmt::Debug for BudgetGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BudgetGuard")
+            .field("max_screenshots", &self.inner.max_screenshots)
+            .field("max_bytes", &self.inner.max_bytes)
+            .field("thresholds", &self.inner.thresholds)
+            .field("refresh_interval", &self.inner.refresh_interval)
+            .field("state", &*self.lock())
+            .finish()
+    }
+}
+
+impl BudgetGuard {
+    /// Create a new builder for a budget guard.
+    pub fn builder() -> BudgetGuardBuilder {
+        BudgetGuardBuilder::default()
+    }
+
+    /// Current usage as tracked by the guard.
+    pub fn status(&self) -> BudgetStatus {
+        let state = self.lock();
+        BudgetStatus {
+            screenshots: state.screenshots + state.pending,
+            bytes: state.bytes,
+            max_screenshots: self.inner.max_screenshots,
+            max_bytes: self.inner.max_bytes,
+            period_end: state.period_end,
+        }
+    }
+
+    /// Whether the guard should be (re)seeded from the usage endpoint.
+    pub(crate) fn needs_refresh(&self) -> bool {
+        let state = self.lock();
+        match state.refreshed_at {
+            None => true,
+            Some(at) => {
+                at.elapsed() >= self.inner.refresh_interval
+                    || state.period_end.is_some_and(|end| Utc::now() >= end)
+            }
+        }
+    }
+
+    /// Whether the guard has been seeded at least once.
+    pub(crate) fn is_seeded(&self) -> bool {
+        self.lock().refreshed_at.is_some()
+    }
+
+    /// Seed the local counters from the usage endpoint.
+    pub(crate) fn refresh(&self, usage: &Usage) {
+        let alerts = {
+            let mut state = self.lock();
+            if state.period_start != Some(usage.period_start) {
+                state.fired.clear();
+            }
+            state.screenshots = usage.screenshots;
+            state.bytes = usage.bytes;
+            state.period_start = Some(usage.period_start);
+            state.period_end = Some(usage.period_end);
+            state.refreshed_at = Some(Instant::now());
+            self.pending_alerts(&mut state)
+        };
+        self.fire(&alerts);
+    }
+
+    /// Postpone the next refresh after a failed attempt.
+    pub(crate) fn refresh_failed(&self) {
+        self.lock().refreshed_at = Some(Instant::now());
+    }
+
+    /// Reserve one capture, failing if a budget has been reached.
+    pub(crate) fn reserve(&self) -> Result<()> {
+        let mut state = self.lock();
+
+        let screenshots = state.screenshots + state.pending;
+        if let Some(limit) = self.inner.max_screenshots {
+            if screenshots >= limit {
+                return Err(Error::BudgetExceeded {
+                    resource: BudgetResource::Screenshots,
+                    used: screenshots,
+                    limit,
+                });
+            }
+        }
+        if let Some(limit) = self.inner.max_bytes {
+            if state.bytes >= limit {
+                return Err(Error::BudgetExceeded {
+                    resource: BudgetResource::Bytes,
+                    used: state.bytes,
+                    limit,
+                });
+            }
+        }
+
+        state.pending += 1;
+        Ok(())
+    }
+
+    /// Record a completed capture of `bytes` bytes.
+    pub(crate) fn commit(&self, bytes: u64) {
+        let alerts = {
+            let mut state = self.lock();
+            state.pending = state.pending.saturating_sub(1);
+            state.screenshots += 1;
+            state.bytes += bytes;
+            self.pending_alerts(&mut state)
+        };
+        self.fire(&alerts);
+    }
+
+    /// Release a reservation for a capture that failed.
+    pub(crate) fn cancel(&self) {
+        let mut state = self.lock();
+        state.pending = state.pending.saturating_sub(1);
+    }
+
+    fn pending_alerts(&self, state: &mut State) -> Vec<BudgetAlert> {
+        let mut alerts = Vec::new();
+        let usage = [
+            (
+                BudgetResource::Screenshots,
+                state.screenshots,
+                self.inner.max_screenshots,
+            ),
+            (BudgetResource::Bytes, state.bytes, self.inner.max_bytes),
+        ];
+
+        for (resource, used, limit) in usage {
+            let Some(limit) = limit else { continue };
+            let ratio = used as f64 / limit as f64;
+            for (index, &threshold) in self.inner.thresholds.iter().enumerate() {
+                if ratio >= threshold && !state.fired.contains(&(resource, index)) {
+                    state.fired.push((resource, index));
+                    alerts.push(BudgetAlert {
+                        resource,
+                        threshold,
+                        used,
+                        limit,
+                    });
+                }
+            }
+        }
+
+        alerts
+    }
+
+    fn fire(&self, alerts: &[BudgetAlert]) {
+        for alert in alerts {
+            for callback in &self.inner.callbacks {
+                callback(alert);
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Builder for [`BudgetGuard`].
+#[derive(Default)]
+pub struct BudgetGuardBuilder {
+    max_screenshots: Option<u64>,
+    max_bytes: Option<u64>,
+    thresholds: Vec<f64>,
+    callbacks: Vec<AlertCallback>,
+    refresh_interval: Option<Duration>,
+}
+
+impl fmt::Debug for BudgetGuardBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BudgetGuardBuilder")
+            .field("max_screenshots", &self.max_screenshots)
+            .field("max_bytes", &self.max_bytes)
+            .field("thresholds", &self.thresholds)
+            .field("callbacks", &self.callbacks.len())
+            .field("refresh_interval", &self.refresh_interval)
+            .finish()
+    }
+}
+
+impl BudgetGuardBuilder {
+    /// Maximum number of screenshots per billing period.
+    pub fn max_screenshots(mut self, screenshots: u64) -> Self {
+        self.max_screenshots = Some(screenshots);
+        self
+    }
+
+    /// Maximum bytes of screenshots per billing period.
+    pub fn max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Fire alerts when usage reaches this fraction of a budget (0.0-1.0).
+    ///
+    /// May be called several times; each threshold fires once per period.
+    pub fn alert_at(mut self, threshold: f64) -> Self {
+        self.thresholds.push(threshold);
+        self
+    }
+
+    /// Call `callback` whenever a threshold is crossed.
+    pub fn on_alert(mut self, callback: impl Fn(&BudgetAlert) + Send + Sync + 'static) -> Self {
+        self.callbacks.push(Arc::new(callback));
+        self
+    }
+
+    /// How often to re-seed the counters from the usage endpoint (default: 5 minutes).
+    pub fn refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval = Some(interval);
+        self
+    }
+
+    /// Build the budget guard.
+    pub fn build(mut self) -> Result<BudgetGuard> {
+        if self.max_screenshots.is_none() && self.max_bytes.is_none() {
+            return Err(Error::Config(
+                "budget requires max_screenshots or max_bytes".to_string(),
+            ));
+        }
+        if let Some(threshold) = self.thresholds.iter().find(|t| !(0.0..=1.0).contains(*t)) {
+            return Err(Error::Config(format!(
+                "budget alert threshold must be between 0 and 1, got {}",
+                threshold
+            )));
+        }
+        self.thresholds.sort_by(f64::total_cmp);
+        self.thresholds.dedup();
+
+        Ok(BudgetGuard {
+            inner: Arc::new(Inner {
+                max_screenshots: self.max_screenshots,
+                max_bytes: self.max_bytes,
+                thresholds: self.thresholds,
+                callbacks: self.callbacks,
+                refresh_interval: self.refresh_interval.unwrap_or(DEFAULT_REFRESH_INTERVAL),
+                state: Mutex::new(State::default()),
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    fn usage(screenshots: u64, bytes: u64, period: u32) -> Usage {
+        Usage {
+            screenshots,
+            bytes,
+            period_start: Utc.with_ymd_and_hms(2024, period, 1, 0, 0, 0).unwrap(),
+            period_end: Utc.with_ymd_and_hms(2024, period + 1, 1, 0, 0, 0).unwrap(),
+        }
+    }
+
+    fn screenshots(limit: u64) -> BudgetGuard {
+        BudgetGuard::builder()
+            .max_screenshots(limit)
+            .build()
+            .unwrap()
+    }
+
+    fn exceeded(result: Result<()>) -> Option<(BudgetResource, u64, u64)> {
+        match result {
+            Err(Error::BudgetExceeded {
+                resource,
+                used,
+                limit,
+            }) => Some((resource, used, limit)),
+            Ok(()) => None,
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn reserves_up_to_the_screenshot_limit() {
+        let guard = screenshots(10);
+        guard.refresh(&usage(8, 0, 1));
+
+        assert!(guard.reserve().is_ok());
+        assert!(
+            guard.reserve().is_ok(),
+            "the last screenshot of the budget is allowed"
+        );
+        assert_eq!(
+            exceeded(guard.reserve()),
+            Some((BudgetResource::Screenshots, 10, 10))
+        );
+        assert_eq!(
+            guard.status().screenshots,
+            10,
+            "pending captures are counted"
+        );
+    }
+
+    #[test]
+    fn cancelled_reservations_free_the_budget() {
+        let guard = screenshots(1);
+        guard.reserve().unwrap();
+        assert!(guard.reserve().is_err());
+
+        guard.cancel();
+        guard.reserve().unwrap();
+        guard.commit(100);
+        assert!(guard.reserve().is_err());
+
+        let status = guard.status();
+        assert_eq!((status.screenshots, status.bytes), (1, 100));
+    }
+
+    #[test]
+    fn byte_limit_blocks_once_reached() {
+        let guard = BudgetGuard::builder().max_bytes(1_000).build().unwrap();
+        guard.refresh(&usage(0, 999, 1));
+
+        guard.reserve().unwrap();
+        guard.commit(1);
+        assert_eq!(
+            exceeded(guard.reserve()),
+            Some((BudgetResource::Bytes, 1_000, 1_000))
+        );
+    }
+
+    #[test]
+    fn refresh_replaces_local_counts() {
+        let guard = screenshots(10);
+        guard.refresh(&usage(10, 0, 1));
+        assert!(guard.reserve().is_err());
+
+        guard.refresh(&usage(0, 0, 2));
+        assert!(
+            guard.reserve().is_ok(),
+            "a new period starts from the fresh usage"
+        );
+        assert_eq!(guard.status().period_end, Some(usage(0, 0, 2).period_end));
+    }
+
+    #[test]
+    fn concurrent_reservations_never_exceed_the_limit() {
+        let guard = screenshots(50);
+        let granted: usize = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    let guard = guard.clone();
+                    scope.spawn(move || (0..20).filter(|_| guard.reserve().is_ok()).count())
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .sum()
+        });
+
+        assert_eq!(granted, 50);
+        assert_eq!(guard.status().screenshots, 50);
+    }
+
+    #[test]
+    fn alerts_fire_once_per_threshold_and_period() {
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        let guard = BudgetGuard::builder()
+            .max_screenshots(10)
+            .alert_at(0.5)
+            .alert_at(0.8)
+            .alert_at(0.5)
+            .on_alert({
+                let alerts = alerts.clone();
+                move |alert| alerts.lock().unwrap().push((alert.threshold, alert.used))
+            })
+            .build()
+            .unwrap();
+
+        guard.refresh(&usage(4, 0, 1));
+        assert!(alerts.lock().unwrap().is_empty());
+
+        guard.reserve().unwrap();
+        guard.commit(0);
+        assert_eq!(
+            *alerts.lock().unwrap(),
+            [(0.5, 5)],
+            "the threshold is inclusive"
+        );
+
+        guard.refresh(&usage(9, 0, 1));
+        guard.refresh(&usage(9, 0, 1));
+        assert_eq!(*alerts.lock().unwrap(), [(0.5, 5), (0.8, 9)]);
+
+        guard.refresh(&usage(9, 0, 2));
+        assert_eq!(alerts.lock().unwrap().len(), 4, "a new period fires again");
+    }
+
+    #[test]
+    fn needs_refresh_until_seeded_and_after_the_period_ends() {
+        let guard = BudgetGuard::builder()
+            .max_screenshots(10)
+            .refresh_interval(Duration::from_secs(3600))
+            .build()
+            .unwrap();
+        assert!(guard.needs_refresh());
+        assert!(!guard.is_seeded());
+
+        let now = Utc::now();
+        guard.refresh(&Usage {
+            screenshots: 0,
+            bytes: 0,
+            period_start: now - chrono::Duration::days(1),
+            period_end: now + chrono::Duration::days(1),
+        });
+        assert!(guard.is_seeded());
+        assert!(!guard.needs_refresh());
+
+        guard.refresh(&usage(0, 0, 1));
+        assert!(guard.needs_refresh(), "the period ended in the past");
+    }
+
+    #[test]
+    fn failed_refresh_postpones_the_next_attempt() {
+        let guard = BudgetGuard::builder()
+            .max_screenshots(10)
+            .refresh_interval(Duration::from_secs(3600))
+            .build()
+            .unwrap();
+        guard.refresh_failed();
+        assert!(!guard.needs_refresh());
+    }
+
+    #[test]
+    fn build_validates_limits_and_thresholds() {
+        assert!(BudgetGuard::builder().build().is_err());
+        assert!(screenshots(1).reserve().is_ok());
+        assert!(BudgetGuard::builder()
+            .max_bytes(1)
+            .alert_at(1.5)
+            .build()
+            .is_err());
+        assert!(BudgetGuard::builder()
+            .max_bytes(1)
+            .alert_at(-0.1)
+            .build()
+            .is_err());
+        assert!(BudgetGuard::builder()
+            .max_bytes(1)
+            .alert_at(f64::NAN)
+            .build()
+            .is_err());
+    }
+}
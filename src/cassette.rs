@@ -0,0 +1,359 @@
+//! Record and replay API interactions for deterministic tests.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+const CASSETTE_VERSION: u32 = 1;
+
+/// Whether a [`Cassette`] records or replays interactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Send requests to the API and write each interaction to the cassette.
+    Record,
+    /// Serve responses from the cassette without any network access.
+    Replay,
+}
+
+/// A file of recorded API interactions.
+///
+/// In [`CassetteMode::Record`], every request sent by the client is written
+/// to the cassette file together with the response status, headers and body.
+/// The `Authorization` header is never written. The file is rewritten after
+/// each interaction, so it stays complete if the process stops early.
+///
+/// In [`CassetteMode::Replay`], requests are answered from the cassette.
+/// Requests are matched on method, path, query and the canonical JSON body,
+/// i.e. the [`ScreenshotRequest`](crate::ScreenshotRequest) with keys in a
+/// fixed order, so field order and base URL do not matter. Identical requests
+/// are answered with their recorded responses in order, and the last response
+/// repeats once they run out. A request with no recorded match fails with
+/// [`Error::Cassette`].
+///
+/// Cloning a cassette is cheap and every clone shares the same interactions.
+///
+/// # Example
+///
+/// ```no_run
+/// use pxshot::{Cassette, Pxshot, ScreenshotRequest};
+///
+/// #[tokio::main]
+/// async fn main() -> pxshot::Result<()> {
+///     // Records on the first run (requires an API key), replays afterwards.
+///     let cassette = Cassette::auto("tests/cassettes/homepage.json")?;
+///     let api_key = std::env::var("PXSHOT_API_KEY").unwrap_or_default();
+///     let client = Pxshot::builder(api_key).cassette(cassette).build()?;
+///
+///     let response = client
+///         .screenshot(
+///             ScreenshotRequest::builder()
+///                 .url("https://example.com")
+///                 .build()?,
+///         )
+///         .await?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Cassette {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    path: PathBuf,
+    mode: CassetteMode,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    interactions: Vec<Interaction>,
+    used: Vec<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CassetteFile {
+    version: u32,
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+/// A request as written to a cassette.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct RecordedRequest {
+    method: String,
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    query: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    encoding: BodyEncoding,
+    body: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum BodyEncoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+impl Cassette {
+    /// Start recording to `path`, replacing any existing cassette.
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self::new(path.into(), CassetteMode::Record, Vec::new())
+    }
+
+    /// Replay the cassette at `path`.
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let contents = fs::read(&path)?;
+        let file: CassetteFile = serde_json::from_slice(&contents).map_err(|e| {
+            Error::Cassette(format!("invalid cassette {}: {}", path.display(), e))
+        })?;
+        if file.version != CASSETTE_VERSION {
+            return Err(Error::Cassette(format!(
+                "unsupported cassette version {} in {}",
+                file.version,
+                path.display()
+            )));
+        }
+        Ok(Self::new(path, CassetteMode::Replay, file.interactions))
+    }
+
+    /// Replay the cassette at `path` if it exists, otherwise record it.
+    pub fn auto(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if path.exists() {
+            Self::replay(path)
+        } else {
+            Ok(Self::record(path))
+        }
+    }
+
+    /// Path of the cassette file.
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    /// Whether the cassette records or replays.
+    pub fn mode(&self) -> CassetteMode {
+        self.inner.mode
+    }
+
+    /// Number of interactions in the cassette.
+    pub fn len(&self) -> usize {
+        self.lock().interactions.len()
+    }
+
+    /// Whether the cassette has no interactions.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn new(path: PathBuf, mode: CassetteMode, interactions: Vec<Interaction>) -> Self {
+        let used = vec![false; interactions.len()];
+        Self {
+            inner: Arc::new(Inner {
+                path,
+                mode,
+                state: Mutex::new(State { interactions, used }),
+            }),
+        }
+    }
+
+    /// Find the recorded response for `request`.
+    pub(crate) fn replay_response(&self, request: &RecordedRequest) -> Result<http::Response<Vec<u8>>> {
+        let mut state = self.lock();
+
+        let matches: Vec<usize> = state
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, interaction)| interaction.request.matches(request))
+            .map(|(index, _)| index)
+            .collect();
+
+        let index = matches
+            .iter()
+            .copied()
+            .find(|&index| !state.used[index])
+            .or_else(|| matches.last().copied())
+            .ok_or_else(|| {
+                Error::Cassette(format!(
+                    "no recorded interaction for {} {} in {}",
+                    request.method,
+                    request.path,
+                    self.inner.path.display()
+                ))
+            })?;
+
+        state.used[index] = true;
+        state.interactions[index].response.to_http()
+    }
+
+    /// Write an interaction to the cassette and return the response to use.
+    pub(crate) fn record_response(
+        &self,
+        request: RecordedRequest,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: Vec<u8>,
+    ) -> Result<http::Response<Vec<u8>>> {
+        let response = RecordedResponse::new(status, headers, &body);
+
+        let mut state = self.lock();
+        state.interactions.push(Interaction { request, response });
+        state.used.push(true);
+
+        let file = CassetteFile {
+            version: CASSETTE_VERSION,
+            interactions: state.interactions.clone(),
+        };
+        let contents = serde_json::to_vec_pretty(&file)
+            .map_err(|e| Error::Cassette(format!("failed to serialize cassette: {}", e)))?;
+        if let Some(parent) = self.inner.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.inner.path, contents)?;
+
+        let mut response = http::Response::new(body);
+        *response.status_mut() = status;
+        *response.headers_mut() = headers.clone();
+        Ok(response)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for Cassette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cassette")
+            .field("path", &self.inner.path)
+            .field("mode", &self.inner.mode)
+            .field("interactions", &self.len())
+            .finish()
+    }
+}
+
+impl RecordedRequest {
+    pub(crate) fn new(
+        method: &Method,
+        url: &Url,
+        headers: &HeaderMap,
+        body: Option<&[u8]>,
+    ) -> Self {
+        let headers = headers
+            .iter()
+            .filter(|(name, value)| *name != AUTHORIZATION && !value.is_sensitive())
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+
+        // Parsing into a `Value` sorts object keys, giving a canonical form.
+        let body = body.filter(|body| !body.is_empty()).map(|body| {
+            serde_json::from_slice(body)
+                .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(body).into()))
+        });
+
+        Self {
+            method: method.to_string(),
+            path: url.path().to_string(),
+            query: url.query().map(str::to_string),
+            headers,
+            body,
+        }
+    }
+
+    fn matches(&self, other: &Self) -> bool {
+        self.method == other.method
+            && self.path == other.path
+            && self.query == other.query
+            && self.body == other.body
+    }
+}
+
+impl RecordedResponse {
+    fn new(status: StatusCode, headers: &HeaderMap, body: &[u8]) -> Self {
+        let mut recorded = BTreeMap::new();
+        for (name, value) in headers {
+            if let Ok(value) = value.to_str() {
+                recorded
+                    .entry(name.to_string())
+                    .and_modify(|existing: &mut String| {
+                        existing.push_str(", ");
+                        existing.push_str(value);
+                    })
+                    .or_insert_with(|| value.to_string());
+            }
+        }
+
+        let is_text = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/json") || value.starts_with("text/"));
+
+        let (encoding, body) = match std::str::from_utf8(body) {
+            Ok(text) if is_text => (BodyEncoding::Utf8, text.to_string()),
+            _ => (BodyEncoding::Base64, BASE64.encode(body)),
+        };
+
+        Self {
+            status: status.as_u16(),
+            headers: recorded,
+            encoding,
+            body,
+        }
+    }
+
+    fn to_http(&self) -> Result<http::Response<Vec<u8>>> {
+        let body = match self.encoding {
+            BodyEncoding::Utf8 => self.body.clone().into_bytes(),
+            BodyEncoding::Base64 => BASE64
+                .decode(&self.body)
+                .map_err(|e| Error::Cassette(format!("invalid base64 body: {}", e)))?,
+        };
+
+        let mut response = http::Response::new(body);
+        *response.status_mut() = StatusCode::from_u16(self.status)
+            .map_err(|_| Error::Cassette(format!("invalid status code {}", self.status)))?;
+        for (name, value) in &self.headers {
+            let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) else {
+                continue;
+            };
+            response.headers_mut().append(name, value);
+        }
+        Ok(response)
+    }
+}
@@ -1,12 +1,27 @@
 //! Pxshot API client.
 
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use reqwest::{
     header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE},
     Client, StatusCode,
 };
+use serde::de::DeserializeOwned;
 
+use crate::budget::BudgetGuard;
+use crate::cassette::{Cassette, CassetteMode, RecordedRequest};
 use crate::error::{Error, Result};
-use crate::types::{ApiError, ScreenshotRequest, ScreenshotResponse, StoredScreenshot, Usage};
+use crate::hooks::{Hook, Hooks, ResponseMeta};
+use crate::policy::UrlPolicy;
+use crate::rate_limit::{RateLimitPermit, RateLimiter};
+#[cfg(feature = "tracing")]
+use crate::telemetry::TraceContext;
+use crate::telemetry::{self, Span};
+use crate::types::{
+    ApiError, ScreenshotOptions, ScreenshotRequest, ScreenshotResponse, StoredScreenshot, Usage,
+};
+use crate::usage::{UsageHistory, UsageRange};
 
 const DEFAULT_BASE_URL: &str = "https://api.pxshot.com";
 
@@ -35,7 +50,72 @@ const DEFAULT_BASE_URL: &str = "https://api.pxshot.com";
 #[derive(Debug, Clone)]
 pub struct Pxshot {
     client: Client,
+    options: ClientOptions,
+}
+
+/// Settings shared by the async and blocking clients.
+#[derive(Debug, Clone)]
+struct ClientOptions {
     base_url: String,
+    rate_limiter: Option<RateLimiter>,
+    budget: Option<BudgetGuard>,
+    hooks: Hooks,
+    cassette: Option<Cassette>,
+    defaults: ScreenshotOptions,
+    url_policy: Option<UrlPolicy>,
+    #[cfg(feature = "tracing")]
+    propagate_trace_context: bool,
+}
+
+impl ClientOptions {
+    /// Fill the fields the request leaves unset from the client defaults.
+    fn apply_defaults(&self, request: &ScreenshotRequest) -> ScreenshotRequest {
+        self.defaults
+            .merge(&request.options())
+            .to_request(request.url.clone())
+    }
+
+    fn after_response(
+        &self,
+        request: ScreenshotRequest,
+        exchange: Exchange,
+        latency: Duration,
+        result: &Result<ScreenshotResponse>,
+    ) {
+        if self.hooks.is_empty() {
+            return;
+        }
+
+        let meta = ResponseMeta {
+            request,
+            status: exchange.status,
+            headers: exchange.headers,
+            attempts: exchange.attempts,
+            latency,
+        };
+        self.hooks.after_response(&meta, result);
+    }
+}
+
+/// What happened on the wire while serving one SDK call.
+#[derive(Debug, Default)]
+struct Exchange {
+    status: Option<u16>,
+    headers: HeaderMap,
+    attempts: u32,
+}
+
+impl Exchange {
+    fn record(&mut self, response: &reqwest::Response) {
+        self.status = Some(response.status().as_u16());
+        self.headers = response.headers().clone();
+    }
+
+    #[cfg(feature = "blocking")]
+    fn record_blocking(&mut self, response: &reqwest::blocking::Response) {
+        self.status = Some(response.status().as_u16());
+        self.headers = response.headers().clone();
+    }
 }
 
 impl Pxshot {
@@ -56,22 +136,28 @@ impl Pxshot {
     ///
     /// This is primarily useful for testing or self-hosted instances.
     pub fn with_base_url(api_key: impl AsRef<str>, base_url: impl Into<String>) -> Self {
-        let mut headers = HeaderMap::new();
-        let auth_value = format!("Bearer {}", api_key.as_ref());
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(&auth_value).expect("invalid API key"),
-        );
-
-        let client = Client::builder()
-            .default_headers(headers)
+        Self::builder(api_key)
+            .base_url(base_url)
             .build()
-            .expect("failed to build HTTP client");
+            .expect("failed to build Pxshot client")
+    }
 
-        Self {
-            client,
-            base_url: base_url.into().trim_end_matches('/').to_string(),
-        }
+    /// Create a new builder for a Pxshot client.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pxshot::{Pxshot, RateLimiter};
+    ///
+    /// # fn main() -> pxshot::Result<()> {
+    /// let client = Pxshot::builder("px_your_api_key")
+    ///     .rate_limiter(RateLimiter::builder().per_second(2).build()?)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder(api_key: impl AsRef<str>) -> PxshotBuilder {
+        PxshotBuilder::new(api_key)
     }
 
     /// Capture a screenshot.
@@ -122,18 +208,127 @@ impl Pxshot {
     /// }
     /// ```
     pub async fn screenshot(&self, request: ScreenshotRequest) -> Result<ScreenshotResponse> {
+        let mut request = self.options.apply_defaults(&request);
+        let mut headers = HeaderMap::new();
+        self.options
+            .hooks
+            .before_request(&mut request, &mut headers);
+
+        let span = Span::screenshot(&request);
+        let started = Instant::now();
+        let mut exchange = Exchange::default();
+
+        let result = span
+            .instrument(self.guarded_capture(&request, &headers, &span, &mut exchange))
+            .await;
+
+        if let Ok(response) = &result {
+            span.record_bytes(response.size_bytes());
+        }
+        span.record_result(&result, started.elapsed());
+        telemetry::screenshot_completed(&request, exchange.status, started.elapsed(), &result);
+        self.options
+            .after_response(request, exchange, started.elapsed(), &result);
+        result
+    }
+
+    /// Default options applied to every screenshot request.
+    ///
+    /// Set with [`PxshotBuilder::defaults`].
+    pub fn defaults(&self) -> &ScreenshotOptions {
+        &self.options.defaults
+    }
+
+    /// The request that [`screenshot`](Self::screenshot) sends for `request`,
+    /// with the client defaults applied.
+    ///
+    /// Changes made by [`Hook::before_request`] are not included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pxshot::{ImageFormat, Pxshot, ScreenshotOptions, ScreenshotRequest};
+    ///
+    /// # fn main() -> pxshot::Result<()> {
+    /// let client = Pxshot::builder("px_your_api_key")
+    ///     .defaults(ScreenshotOptions {
+    ///         format: Some(ImageFormat::Webp),
+    ///         width: Some(1280),
+    ///         ..Default::default()
+    ///     })
+    ///     .build()?;
+    ///
+    /// let request = ScreenshotRequest::builder()
+    ///     .url("https://example.com")
+    ///     .width(390)
+    ///     .build()?;
+    ///
+    /// let effective = client.effective_request(&request);
+    /// assert_eq!(effective.format, Some(ImageFormat::Webp));
+    /// assert_eq!(effective.width, Some(390));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn effective_request(&self, request: &ScreenshotRequest) -> ScreenshotRequest {
+        self.options.apply_defaults(request)
+    }
+
+    async fn guarded_capture(
+        &self,
+        request: &ScreenshotRequest,
+        headers: &HeaderMap,
+        span: &Span,
+        exchange: &mut Exchange,
+    ) -> Result<ScreenshotResponse> {
+        request.validate()?;
+        if let Some(policy) = &self.options.url_policy {
+            policy.check(&request.url).await?;
+        }
+
+        let Some(budget) = &self.options.budget else {
+            return self.capture(request, headers, span, exchange).await;
+        };
+
+        if budget.needs_refresh() {
+            match self.usage().await {
+                Ok(usage) => budget.refresh(&usage),
+                Err(e) if !budget.is_seeded() => return Err(e),
+                Err(_) => budget.refresh_failed(),
+            }
+        }
+
+        budget.reserve()?;
+        let result = self.capture(request, headers, span, exchange).await;
+        match &result {
+            Ok(response) => budget.commit(response.size_bytes()),
+            Err(_) => budget.cancel(),
+        }
+        result
+    }
+
+    async fn capture(
+        &self,
+        request: &ScreenshotRequest,
+        headers: &HeaderMap,
+        span: &Span,
+        exchange: &mut Exchange,
+    ) -> Result<ScreenshotResponse> {
         let store = request.store.unwrap_or(false);
-        let url = format!("{}/v1/screenshot", self.base_url);
-
-        let response = self
-            .client
-            .post(&url)
-            .header(CONTENT_TYPE, "application/json")
-            .json(&request)
-            .send()
+        let url = format!("{}/v1/screenshot", self.options.base_url);
+
+        let (response, _permit) = self
+            .send(span, exchange, || {
+                self.client
+                    .post(&url)
+                    .headers(headers.clone())
+                    .header(CONTENT_TYPE, "application/json")
+                    .json(request)
+            })
             .await?;
 
         let status = response.status();
+        span.record_status(status.as_u16());
+        exchange.record(&response);
 
         if !status.is_success() {
             return Err(self.parse_error(status, response).await);
@@ -169,20 +364,170 @@ impl Pxshot {
     /// }
     /// ```
     pub async fn usage(&self) -> Result<Usage> {
-        let url = format!("{}/v1/usage", self.base_url);
+        let url = format!("{}/v1/usage", self.options.base_url);
 
-        let response = self.client.get(&url).send().await?;
+        self.get_json(Span::usage("usage"), "usage", || self.client.get(&url))
+            .await
+    }
 
-        let status = response.status();
+    /// Get usage history with per-format, per-delivery and per-key breakdowns.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use pxshot::{Pxshot, UsageRange};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> pxshot::Result<()> {
+    ///     let client = Pxshot::new("px_your_api_key");
+    ///
+    ///     let history = client.usage_history(UsageRange::last_days(30)).await?;
+    ///     for point in &history.series {
+    ///         println!("{}: {} screenshots", point.start.date_naive(), point.screenshots);
+    ///     }
+    ///
+    ///     let usage = client.usage().await?;
+    ///     let projection = history.project(&usage);
+    ///     println!("Projected screenshots this period: {}", projection.screenshots);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn usage_history(&self, range: UsageRange) -> Result<UsageHistory> {
+        let url = format!("{}/v1/usage/history", self.options.base_url);
 
-        if !status.is_success() {
-            return Err(self.parse_error(status, response).await);
+        self.get_json(Span::usage("usage_history"), "usage history", || {
+            self.client.get(&url).query(&range)
+        })
+        .await
+    }
+
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        span: Span,
+        what: &str,
+        request: impl Fn() -> reqwest::RequestBuilder,
+    ) -> Result<T> {
+        let started = Instant::now();
+
+        let result = span
+            .instrument(async {
+                let (response, _permit) =
+                    self.send(&span, &mut Exchange::default(), request).await?;
+
+                let status = response.status();
+                span.record_status(status.as_u16());
+
+                if !status.is_success() {
+                    return Err(self.parse_error(status, response).await);
+                }
+
+                response
+                    .json()
+                    .await
+                    .map_err(|e| Error::Parse(format!("failed to parse {} response: {}", what, e)))
+            })
+            .await;
+
+        span.record_result(&result, started.elapsed());
+        result
+    }
+
+    /// Send a request, pacing it through the rate limiter if one is configured.
+    ///
+    /// The returned permit must be held until the response body has been read.
+    async fn send(
+        &self,
+        span: &Span,
+        exchange: &mut Exchange,
+        request: impl Fn() -> reqwest::RequestBuilder,
+    ) -> Result<(reqwest::Response, Option<RateLimitPermit>)> {
+        #[cfg(feature = "tracing")]
+        let trace = self.options.propagate_trace_context.then(|| {
+            let trace = TraceContext::new();
+            span.record_trace_id(trace.trace_id());
+            trace
+        });
+        #[cfg(not(feature = "tracing"))]
+        let _ = span;
+
+        let send_attempt = |attempt: u32| {
+            #[allow(unused_mut)]
+            let mut builder = request();
+            #[cfg(feature = "tracing")]
+            if let Some(trace) = &trace {
+                builder = builder.header("traceparent", trace.traceparent());
+            }
+
+            let attempt_span = Span::attempt(attempt);
+            async move {
+                let started = Instant::now();
+                let result = attempt_span.instrument(self.execute(builder)).await;
+                if let Ok(response) = &result {
+                    attempt_span.record_status(response.status().as_u16());
+                }
+                attempt_span.record_latency(started.elapsed());
+                result
+            }
+        };
+
+        let Some(limiter) = &self.options.rate_limiter else {
+            exchange.attempts = 1;
+            return Ok((send_attempt(1).await?, None));
+        };
+
+        let mut attempt = 1;
+        loop {
+            let queued = Instant::now();
+            let permit = limiter.acquire().await;
+            telemetry::rate_limit_wait(queued.elapsed());
+            exchange.attempts = attempt;
+            let response = send_attempt(attempt).await?;
+
+            if response.status() == StatusCode::TOO_MANY_REQUESTS
+                && attempt <= limiter.max_retries()
+            {
+                let delay = limiter.throttled(response.headers(), attempt);
+                telemetry::retry(attempt, delay);
+                attempt += 1;
+                continue;
+            }
+
+            limiter.observe(response.headers());
+            return Ok((response, Some(permit)));
         }
+    }
 
-        response
-            .json()
-            .await
-            .map_err(|e| Error::Parse(format!("failed to parse usage response: {}", e)))
+    /// Send one HTTP request, or answer it from the cassette if one is configured.
+    async fn execute(&self, builder: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let Some(cassette) = &self.options.cassette else {
+            return Ok(builder.send().await?);
+        };
+
+        let request = builder.build()?;
+        let recorded = RecordedRequest::new(
+            request.method(),
+            request.url(),
+            request.headers(),
+            request.body().and_then(|body| body.as_bytes()),
+        );
+
+        match cassette.mode() {
+            CassetteMode::Replay => {
+                let response = cassette.replay_response(&recorded)?;
+                telemetry::cache_hit();
+                Ok(response.into())
+            }
+            CassetteMode::Record => {
+                let response = self.client.execute(request).await?;
+                let status = response.status();
+                let headers = response.headers().clone();
+                let body = response.bytes().await?.to_vec();
+                Ok(cassette
+                    .record_response(recorded, status, &headers, body)?
+                    .into())
+            }
+        }
     }
 
     async fn parse_error(&self, status: StatusCode, response: reqwest::Response) -> Error {
@@ -193,12 +538,205 @@ impl Pxshot {
             },
             Err(_) => Error::Api {
                 status: status.as_u16(),
-                message: status.canonical_reason().unwrap_or("Unknown error").to_string(),
+                message: status
+                    .canonical_reason()
+                    .unwrap_or("Unknown error")
+                    .to_string(),
             },
         }
     }
 }
 
+/// Builder for [`Pxshot`].
+///
+/// Created with [`Pxshot::builder`].
+pub struct PxshotBuilder {
+    api_key: String,
+    base_url: String,
+    rate_limiter: Option<RateLimiter>,
+    budget: Option<BudgetGuard>,
+    hooks: Hooks,
+    cassette: Option<Cassette>,
+    defaults: ScreenshotOptions,
+    url_policy: Option<UrlPolicy>,
+    #[cfg(feature = "tracing")]
+    propagate_trace_context: bool,
+}
+
+impl std::fmt::Debug for PxshotBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PxshotBuilder")
+            .field("api_key", &"<redacted>")
+            .field("base_url", &self.base_url)
+            .field("rate_limiter", &self.rate_limiter)
+            .field("budget", &self.budget)
+            .field("hooks", &self.hooks)
+            .field("cassette", &self.cassette)
+            .field("defaults", &self.defaults)
+            .field("url_policy", &self.url_policy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PxshotBuilder {
+    fn new(api_key: impl AsRef<str>) -> Self {
+        Self {
+            api_key: api_key.as_ref().to_string(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            rate_limiter: None,
+            budget: None,
+            hooks: Hooks::default(),
+            cassette: None,
+            defaults: ScreenshotOptions::default(),
+            url_policy: None,
+            #[cfg(feature = "tracing")]
+            propagate_trace_context: false,
+        }
+    }
+
+    /// Set a custom base URL.
+    ///
+    /// This is primarily useful for testing or self-hosted instances.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Pace requests through a [`RateLimiter`].
+    ///
+    /// The limiter may be shared with other clients by cloning it.
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Enforce a per-period quota with a [`BudgetGuard`].
+    ///
+    /// Captures are refused with [`Error::BudgetExceeded`] once the budget is
+    /// reached. The guard may be shared with other clients by cloning it.
+    pub fn budget(mut self, budget: BudgetGuard) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Add a [`Hook`] invoked around every screenshot request.
+    ///
+    /// May be called several times to stack hooks. `before_request` runs in
+    /// registration order and `after_response` in reverse order.
+    pub fn hook(mut self, hook: impl Hook + 'static) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Record API interactions to, or replay them from, a [`Cassette`].
+    pub fn cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Set default options applied to every screenshot request.
+    ///
+    /// A default is used only when the request leaves the field unset, so
+    /// requests can still override any of them. Defaults are applied before
+    /// hooks run.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pxshot::{ImageFormat, Pxshot, ScreenshotOptions};
+    ///
+    /// # fn main() -> pxshot::Result<()> {
+    /// let client = Pxshot::builder("px_your_api_key")
+    ///     .defaults(ScreenshotOptions {
+    ///         format: Some(ImageFormat::Webp),
+    ///         width: Some(1280),
+    ///         block_ads: Some(true),
+    ///         ..Default::default()
+    ///     })
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn defaults(mut self, defaults: ScreenshotOptions) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Check every URL against a [`UrlPolicy`] before capturing it.
+    ///
+    /// Rejected requests fail with [`Error::PolicyViolation`] without any
+    /// API call. The policy is checked after hooks have run.
+    pub fn url_policy(mut self, policy: UrlPolicy) -> Self {
+        self.url_policy = Some(policy);
+        self
+    }
+
+    /// Send a W3C `traceparent` header with every request.
+    ///
+    /// A trace ID is generated per SDK call and recorded on its span as
+    /// `trace_id`, so server-side logs can be correlated with local traces.
+    #[cfg(feature = "tracing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+    pub fn propagate_trace_context(mut self, enabled: bool) -> Self {
+        self.propagate_trace_context = enabled;
+        self
+    }
+
+    /// Build the async client.
+    pub fn build(self) -> Result<Pxshot> {
+        let headers = self.default_headers()?;
+        let client = Client::builder()
+            .default_headers(headers)
+            .build()
+            .map_err(|e| Error::Config(format!("failed to build HTTP client: {}", e)))?;
+
+        Ok(Pxshot {
+            client,
+            options: self.into_options(),
+        })
+    }
+
+    /// Build the blocking client.
+    #[cfg(feature = "blocking")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "blocking")))]
+    pub fn build_blocking(self) -> Result<BlockingPxshot> {
+        let headers = self.default_headers()?;
+        let client = reqwest::blocking::Client::builder()
+            .default_headers(headers)
+            .build()
+            .map_err(|e| Error::Config(format!("failed to build HTTP client: {}", e)))?;
+
+        Ok(BlockingPxshot {
+            client,
+            options: self.into_options(),
+        })
+    }
+
+    fn default_headers(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        let auth_value = format!("Bearer {}", self.api_key);
+        let mut auth_value = HeaderValue::from_str(&auth_value)
+            .map_err(|_| Error::Config("invalid API key".to_string()))?;
+        auth_value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, auth_value);
+        Ok(headers)
+    }
+
+    fn into_options(self) -> ClientOptions {
+        ClientOptions {
+            base_url: self.base_url.trim_end_matches('/').to_string(),
+            rate_limiter: self.rate_limiter,
+            budget: self.budget,
+            hooks: self.hooks,
+            cassette: self.cassette,
+            defaults: self.defaults,
+            url_policy: self.url_policy,
+            #[cfg(feature = "tracing")]
+            propagate_trace_context: self.propagate_trace_context,
+        }
+    }
+}
+
 #[cfg(feature = "blocking")]
 mod blocking {
     use super::*;
@@ -227,8 +765,8 @@ mod blocking {
     /// ```
     #[derive(Debug, Clone)]
     pub struct Pxshot {
-        client: reqwest::blocking::Client,
-        base_url: String,
+        pub(super) client: reqwest::blocking::Client,
+        pub(super) options: ClientOptions,
     }
 
     impl Pxshot {
@@ -239,37 +777,109 @@ mod blocking {
 
         /// Create a new blocking Pxshot client with a custom base URL.
         pub fn with_base_url(api_key: impl AsRef<str>, base_url: impl Into<String>) -> Self {
+            Self::builder(api_key)
+                .base_url(base_url)
+                .build_blocking()
+                .expect("failed to build Pxshot client")
+        }
+
+        /// Create a new builder for a Pxshot client.
+        ///
+        /// Finish it with [`PxshotBuilder::build_blocking`].
+        pub fn builder(api_key: impl AsRef<str>) -> PxshotBuilder {
+            PxshotBuilder::new(api_key)
+        }
+
+        /// Capture a screenshot (blocking).
+        pub fn screenshot(&self, request: ScreenshotRequest) -> Result<ScreenshotResponse> {
+            let mut request = self.options.apply_defaults(&request);
             let mut headers = HeaderMap::new();
-            let auth_value = format!("Bearer {}", api_key.as_ref());
-            headers.insert(
-                AUTHORIZATION,
-                HeaderValue::from_str(&auth_value).expect("invalid API key"),
-            );
+            self.options
+                .hooks
+                .before_request(&mut request, &mut headers);
+
+            let span = Span::screenshot(&request);
+            let started = Instant::now();
+            let mut exchange = Exchange::default();
 
-            let client = reqwest::blocking::Client::builder()
-                .default_headers(headers)
-                .build()
-                .expect("failed to build HTTP client");
+            let result =
+                span.in_scope(|| self.guarded_capture(&request, &headers, &span, &mut exchange));
 
-            Self {
-                client,
-                base_url: base_url.into().trim_end_matches('/').to_string(),
+            if let Ok(response) = &result {
+                span.record_bytes(response.size_bytes());
             }
+            span.record_result(&result, started.elapsed());
+            telemetry::screenshot_completed(&request, exchange.status, started.elapsed(), &result);
+            self.options
+                .after_response(request, exchange, started.elapsed(), &result);
+            result
         }
 
-        /// Capture a screenshot (blocking).
-        pub fn screenshot(&self, request: ScreenshotRequest) -> Result<ScreenshotResponse> {
+        /// Default options applied to every screenshot request.
+        pub fn defaults(&self) -> &ScreenshotOptions {
+            &self.options.defaults
+        }
+
+        /// The request that [`screenshot`](Self::screenshot) sends for
+        /// `request`, with the client defaults applied.
+        pub fn effective_request(&self, request: &ScreenshotRequest) -> ScreenshotRequest {
+            self.options.apply_defaults(request)
+        }
+
+        fn guarded_capture(
+            &self,
+            request: &ScreenshotRequest,
+            headers: &HeaderMap,
+            span: &Span,
+            exchange: &mut Exchange,
+        ) -> Result<ScreenshotResponse> {
+            request.validate()?;
+            if let Some(policy) = &self.options.url_policy {
+                policy.check_blocking(&request.url)?;
+            }
+
+            let Some(budget) = &self.options.budget else {
+                return self.capture(request, headers, span, exchange);
+            };
+
+            if budget.needs_refresh() {
+                match self.usage() {
+                    Ok(usage) => budget.refresh(&usage),
+                    Err(e) if !budget.is_seeded() => return Err(e),
+                    Err(_) => budget.refresh_failed(),
+                }
+            }
+
+            budget.reserve()?;
+            let result = self.capture(request, headers, span, exchange);
+            match &result {
+                Ok(response) => budget.commit(response.size_bytes()),
+                Err(_) => budget.cancel(),
+            }
+            result
+        }
+
+        fn capture(
+            &self,
+            request: &ScreenshotRequest,
+            headers: &HeaderMap,
+            span: &Span,
+            exchange: &mut Exchange,
+        ) -> Result<ScreenshotResponse> {
             let store = request.store.unwrap_or(false);
-            let url = format!("{}/v1/screenshot", self.base_url);
+            let url = format!("{}/v1/screenshot", self.options.base_url);
 
-            let response = self
-                .client
-                .post(&url)
-                .header(CONTENT_TYPE, "application/json")
-                .json(&request)
-                .send()?;
+            let (response, _permit) = self.send(span, exchange, || {
+                self.client
+                    .post(&url)
+                    .headers(headers.clone())
+                    .header(CONTENT_TYPE, "application/json")
+                    .json(request)
+            })?;
 
             let status = response.status();
+            span.record_status(status.as_u16());
+            exchange.record_blocking(&response);
 
             if !status.is_success() {
                 return Err(self.parse_error(status, response));
@@ -288,19 +898,145 @@ mod blocking {
 
         /// Get API usage statistics (blocking).
         pub fn usage(&self) -> Result<Usage> {
-            let url = format!("{}/v1/usage", self.base_url);
+            let url = format!("{}/v1/usage", self.options.base_url);
 
-            let response = self.client.get(&url).send()?;
+            self.get_json(Span::usage("usage"), "usage", || self.client.get(&url))
+        }
 
-            let status = response.status();
+        /// Get usage history with breakdowns (blocking).
+        pub fn usage_history(&self, range: UsageRange) -> Result<UsageHistory> {
+            let url = format!("{}/v1/usage/history", self.options.base_url);
 
-            if !status.is_success() {
-                return Err(self.parse_error(status, response));
+            self.get_json(Span::usage("usage_history"), "usage history", || {
+                self.client.get(&url).query(&range)
+            })
+        }
+
+        fn get_json<T: DeserializeOwned>(
+            &self,
+            span: Span,
+            what: &str,
+            request: impl Fn() -> reqwest::blocking::RequestBuilder,
+        ) -> Result<T> {
+            let started = Instant::now();
+
+            let result = span.in_scope(|| {
+                let (response, _permit) = self.send(&span, &mut Exchange::default(), request)?;
+
+                let status = response.status();
+                span.record_status(status.as_u16());
+
+                if !status.is_success() {
+                    return Err(self.parse_error(status, response));
+                }
+
+                response
+                    .json()
+                    .map_err(|e| Error::Parse(format!("failed to parse {} response: {}", what, e)))
+            });
+
+            span.record_result(&result, started.elapsed());
+            result
+        }
+
+        /// Send a request, pacing it through the rate limiter if one is configured.
+        ///
+        /// The returned permit must be held until the response body has been read.
+        fn send(
+            &self,
+            span: &Span,
+            exchange: &mut Exchange,
+            request: impl Fn() -> reqwest::blocking::RequestBuilder,
+        ) -> Result<(reqwest::blocking::Response, Option<RateLimitPermit>)> {
+            #[cfg(feature = "tracing")]
+            let trace = self.options.propagate_trace_context.then(|| {
+                let trace = TraceContext::new();
+                span.record_trace_id(trace.trace_id());
+                trace
+            });
+            #[cfg(not(feature = "tracing"))]
+            let _ = span;
+
+            let send_attempt = |attempt: u32| {
+                #[allow(unused_mut)]
+                let mut builder = request();
+                #[cfg(feature = "tracing")]
+                if let Some(trace) = &trace {
+                    builder = builder.header("traceparent", trace.traceparent());
+                }
+
+                let attempt_span = Span::attempt(attempt);
+                attempt_span.in_scope(|| {
+                    let started = Instant::now();
+                    let result = self.execute(builder);
+                    if let Ok(response) = &result {
+                        attempt_span.record_status(response.status().as_u16());
+                    }
+                    attempt_span.record_latency(started.elapsed());
+                    result
+                })
+            };
+
+            let Some(limiter) = &self.options.rate_limiter else {
+                exchange.attempts = 1;
+                return Ok((send_attempt(1)?, None));
+            };
+
+            let mut attempt = 1;
+            loop {
+                let queued = Instant::now();
+                let permit = limiter.acquire_blocking();
+                telemetry::rate_limit_wait(queued.elapsed());
+                exchange.attempts = attempt;
+                let response = send_attempt(attempt)?;
+
+                if response.status() == StatusCode::TOO_MANY_REQUESTS
+                    && attempt <= limiter.max_retries()
+                {
+                    let delay = limiter.throttled(response.headers(), attempt);
+                    telemetry::retry(attempt, delay);
+                    attempt += 1;
+                    continue;
+                }
+
+                limiter.observe(response.headers());
+                return Ok((response, Some(permit)));
             }
+        }
+
+        /// Send one HTTP request, or answer it from the cassette if one is configured.
+        fn execute(
+            &self,
+            builder: reqwest::blocking::RequestBuilder,
+        ) -> Result<reqwest::blocking::Response> {
+            let Some(cassette) = &self.options.cassette else {
+                return Ok(builder.send()?);
+            };
 
-            response
-                .json()
-                .map_err(|e| Error::Parse(format!("failed to parse usage response: {}", e)))
+            let request = builder.build()?;
+            let recorded = RecordedRequest::new(
+                request.method(),
+                request.url(),
+                request.headers(),
+                request.body().and_then(|body| body.as_bytes()),
+            );
+
+            match cassette.mode() {
+                CassetteMode::Replay => {
+                    let response = cassette.replay_response(&recorded)?;
+                    telemetry::cache_hit();
+                    Ok(response.into())
+                }
+                CassetteMode::Record => {
+                    let response = self.client.execute(request)?;
+                    let status = response.status();
+                    let headers = response.headers().clone();
+                    let body = response.bytes()?.to_vec();
+                    Ok(cassette
+                        .record_response(recorded, status, &headers, body)?
+                        .into())
+                }
+            }
         }
 
         fn parse_error(&self, status: StatusCode, response: reqwest::blocking::Response) -> Error {
@@ -311,7 +1047,10 @@ mod blocking {
                 },
                 Err(_) => Error::Api {
                     status: status.as_u16(),
-                    message: status.canonical_reason().unwrap_or("Unknown error").to_string(),
+                    message: status
+                        .canonical_reason()
+                        .unwrap_or("Unknown error")
+                        .to_string(),
                 },
             }
         }
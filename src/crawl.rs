@@ -0,0 +1,747 @@
+//! Crawl a site and capture every page found.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use futures_util::stream::{self, StreamExt};
+use url::{Origin, Url};
+
+use crate::batch::{glob_match, unique_path, Batch, BatchReport, Manifest, ManifestItem};
+use crate::error::{Error, Result};
+use crate::output::url_path;
+use crate::robots::Robots;
+use crate::traits::ScreenshotClient;
+use crate::types::{ImageFormat, ScreenshotOptions, ScreenshotRequest};
+use crate::urls::{normalize_url, IntoUrl};
+
+const DEFAULT_MAX_DEPTH: usize = 2;
+const DEFAULT_MAX_PAGES: usize = 100;
+const DEFAULT_PER_HOST_CONCURRENCY: usize = 2;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_USER_AGENT: &str = concat!("pxshot/", env!("CARGO_PKG_VERSION"));
+/// Longest part of a page read when looking for links.
+const MAX_HTML_BYTES: usize = 5 * 1024 * 1024;
+
+/// A page found by a [`Crawler`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrawledPage {
+    /// URL of the page, after redirects and normalization.
+    pub url: Url,
+    /// Number of links followed from the seed to reach the page.
+    pub depth: usize,
+    /// The page the link to this page was found on, or `None` for the seed.
+    pub referrer: Option<Url>,
+}
+
+/// A crawl of one site, turned into a batch [`Manifest`].
+///
+/// Starting from a seed URL, pages are fetched directly (not through the
+/// API) to find links to further pages on the same origin: the same scheme,
+/// host and port. If the seed redirects to another origin, as from `http`
+/// to `https`, that origin is crawled instead. Links are followed
+/// breadth-first up to a maximum depth and number of pages. URLs are
+/// [normalized](normalize_url) so a page is visited once however it is
+/// linked, and the site's `robots.txt` is respected. Links marked `rel="nofollow"` are not followed, and pages
+/// that fail to load or are not HTML are skipped.
+///
+/// Every page is captured with the options of a base request and written to
+/// a path derived from its URL, as for a [`Sitemap`](crate::Sitemap).
+/// Exclude patterns use `*` for any run of characters and `?` for a single
+/// character; patterns containing `://` are matched against the whole URL,
+/// others against its path and query.
+///
+/// # Example
+///
+/// ```no_run
+/// use pxshot::{Crawler, Pxshot, ScreenshotRequest};
+///
+/// #[tokio::main]
+/// async fn main() -> pxshot::Result<()> {
+///     let base = ScreenshotRequest::builder()
+///         .url("https://example.com")
+///         .full_page(true)
+///         .build()?;
+///
+///     let crawler = Crawler::builder("https://example.com")
+///         .base_request(base)
+///         .output_dir("audit")
+///         .max_depth(3)
+///         .max_pages(500)
+///         .exclude("/logout*")
+///         .build()?;
+///
+///     let client = Pxshot::new("px_your_api_key");
+///     let report = crawler.run(&client).await?;
+///     report.write_json("audit/report.json")?;
+///     println!("{}", report);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Crawler {
+    seed: Url,
+    options: ScreenshotOptions,
+    output_dir: Option<PathBuf>,
+    max_depth: usize,
+    max_pages: usize,
+    exclude: Vec<String>,
+    respect_robots: bool,
+    user_agent: String,
+    per_host_concurrency: usize,
+    timeout: Duration,
+    http: reqwest::Client,
+}
+
+/// A fetched HTML page and the links on it.
+struct Fetched {
+    url: Url,
+    links: Vec<Url>,
+}
+
+impl Crawler {
+    /// Create a new builder for a crawl starting at `seed`.
+    pub fn builder(seed: impl IntoUrl) -> CrawlerBuilder {
+        CrawlerBuilder {
+            seed: seed.into_url(),
+            options: ScreenshotOptions::default(),
+            output_dir: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_pages: DEFAULT_MAX_PAGES,
+            exclude: Vec::new(),
+            respect_robots: true,
+            user_agent: DEFAULT_USER_AGENT.into(),
+            per_host_concurrency: DEFAULT_PER_HOST_CONCURRENCY,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// The URL the crawl starts at.
+    pub fn seed(&self) -> &Url {
+        &self.seed
+    }
+
+    /// Crawl the site and list the pages found, in breadth-first order.
+    ///
+    /// Fails if the seed page cannot be fetched or is not HTML; other pages
+    /// that fail are skipped.
+    pub async fn pages(&self) -> Result<Vec<CrawledPage>> {
+        let seed = normalize_url(&self.seed);
+        let mut origin = seed.origin();
+        let mut robots = self.robots(&seed).await?;
+        if !robots.is_allowed(&seed) {
+            return Err(Error::Config(format!(
+                "crawl seed {} is disallowed by robots.txt",
+                seed
+            )));
+        }
+
+        let mut pages: Vec<CrawledPage> = Vec::new();
+        let mut seen = HashSet::from([seed.clone()]);
+        let mut frontier = vec![(seed, None)];
+
+        for depth in 0..=self.max_depth {
+            let mut next = Vec::new();
+            // In order, so the pages kept at the limit don't depend on which
+            // server responses happen to arrive first.
+            let mut fetches = stream::iter(frontier)
+                .map(|(url, referrer): (Url, Option<Url>)| async move {
+                    let fetched = self.fetch(&url).await;
+                    (url, referrer, fetched)
+                })
+                .buffered(self.per_host_concurrency);
+
+            while let Some((url, referrer, fetched)) = fetches.next().await {
+                let fetched = match fetched {
+                    Ok(Some(fetched)) => fetched,
+                    Ok(None) if depth == 0 => {
+                        return Err(Error::Config(format!(
+                            "crawl seed {} is not an HTML page",
+                            url
+                        )))
+                    }
+                    Err(e) if depth == 0 => return Err(e),
+                    _ => continue,
+                };
+
+                let page_url = normalize_url(&fetched.url);
+                if depth == 0 {
+                    if page_url.origin() != origin {
+                        // The seed redirected to another origin, as from
+                        // `http` to `https` or to a `www.` host: crawl that
+                        // site instead.
+                        origin = page_url.origin();
+                        robots = self.robots(&page_url).await?;
+                    }
+                    if !robots.is_allowed(&page_url) {
+                        return Err(Error::Config(format!(
+                            "crawl seed {} redirects to {}, which is disallowed by robots.txt",
+                            url, page_url
+                        )));
+                    }
+                    seen.insert(page_url.clone());
+                } else if page_url != url
+                    // A redirect may lead off the site or to a page already seen.
+                    && (!self.is_followable(&page_url, &origin, &robots)
+                        || !seen.insert(page_url.clone()))
+                {
+                    continue;
+                }
+                if depth < self.max_depth {
+                    for link in fetched.links {
+                        let link = normalize_url(&link);
+                        if self.is_followable(&link, &origin, &robots) && seen.insert(link.clone())
+                        {
+                            next.push((link, Some(page_url.clone())));
+                        }
+                    }
+                }
+
+                pages.push(CrawledPage {
+                    url: page_url,
+                    depth,
+                    referrer,
+                });
+                if pages.len() >= self.max_pages {
+                    return Ok(pages);
+                }
+            }
+
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+        Ok(pages)
+    }
+
+    /// Crawl the site and build a manifest capturing its pages.
+    pub async fn manifest(&self) -> Result<Manifest> {
+        let pages = self.pages().await?;
+        Ok(self.manifest_for(&pages))
+    }
+
+    /// Build a manifest capturing `pages`, e.g. from [`Crawler::pages`].
+    pub fn manifest_for(&self, pages: &[CrawledPage]) -> Manifest {
+        self.manifest_with(pages, self.options.format, self.options.store)
+    }
+
+    /// Build a manifest naming outputs for captures in `format`, unless
+    /// they are stored.
+    fn manifest_with(
+        &self,
+        pages: &[CrawledPage],
+        format: Option<ImageFormat>,
+        store: Option<bool>,
+    ) -> Manifest {
+        let extension = format.unwrap_or_default().as_str();
+        let stored = store == Some(true);
+
+        let mut outputs = HashSet::new();
+        let items = pages
+            .iter()
+            .map(|page| {
+                let mut item = ManifestItem::new(page.url.clone());
+                if !stored {
+                    item.output = Some(unique_path(&mut outputs, url_path(&page.url), extension));
+                }
+                item
+            })
+            .collect();
+
+        Manifest {
+            output_dir: self.output_dir.clone(),
+            defaults: self.options.clone(),
+            items,
+            ..Manifest::default()
+        }
+    }
+
+    /// Crawl the site and capture every page found.
+    ///
+    /// Captures run as a [`Batch`] with the crawl's per-host concurrency.
+    /// The report lists the image path of each page URL.
+    pub async fn run<C>(&self, client: &C) -> Result<BatchReport>
+    where
+        C: ScreenshotClient + ?Sized,
+    {
+        let pages = self.pages().await?;
+        // The client's defaults may choose the format or store captures.
+        let request = client.effective_request(&self.options.to_request(self.seed.clone()));
+        let manifest = self.manifest_with(&pages, request.format, request.store);
+        Batch::builder(manifest)
+            .concurrency(self.per_host_concurrency)
+            .per_host_concurrency(self.per_host_concurrency)
+            .build()?
+            .run(client)
+            .await
+    }
+
+    /// Whether `url` is on the crawled `origin`, allowed and not excluded.
+    fn is_followable(&self, url: &Url, origin: &Origin, robots: &Robots) -> bool {
+        if url.origin() != *origin || !robots.is_allowed(url) {
+            return false;
+        }
+        !self.exclude.iter().any(|pattern| {
+            if pattern.contains("://") {
+                glob_match(pattern, url.as_str())
+            } else {
+                let path = match url.query() {
+                    Some(query) => format!("{}?{}", url.path(), query),
+                    None => url.path().to_string(),
+                };
+                glob_match(pattern, &path)
+            }
+        })
+    }
+
+    /// Fetch the `robots.txt` of the site `page` is on, unless robots are
+    /// ignored.
+    ///
+    /// A missing file allows everything. A server error disallows
+    /// everything, since the site may be struggling.
+    async fn robots(&self, page: &Url) -> Result<Robots> {
+        if !self.respect_robots {
+            return Ok(Robots::allow_all());
+        }
+        let mut url = page.clone();
+        url.set_path("/robots.txt");
+        url.set_query(None);
+        url.set_fragment(None);
+        let response = self.http.get(url).timeout(self.timeout).send().await?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(Robots::parse(&response.text().await?, &self.user_agent))
+        } else if status.is_server_error() {
+            Ok(Robots::disallow_all())
+        } else {
+            Ok(Robots::allow_all())
+        }
+    }
+
+    /// Fetch `url` and find the links on it, or `None` if it is not HTML.
+    async fn fetch(&self, url: &Url) -> Result<Option<Fetched>> {
+        let mut response = self
+            .http
+            .get(url.clone())
+            .timeout(self.timeout)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let is_html = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                let value = value.to_ascii_lowercase();
+                value.starts_with("text/html") || value.starts_with("application/xhtml+xml")
+            });
+        if !is_html {
+            return Ok(None);
+        }
+
+        let url = response.url().clone();
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() >= MAX_HTML_BYTES {
+                body.truncate(MAX_HTML_BYTES);
+                break;
+            }
+        }
+
+        let html = String::from_utf8_lossy(&body);
+        let (base, hrefs) = extract_links(&html);
+        let base = base
+            .and_then(|base| url.join(base.trim()).ok())
+            .unwrap_or_else(|| url.clone());
+        let links = hrefs
+            .iter()
+            .filter_map(|href| base.join(href.trim()).ok())
+            .filter(|link| matches!(link.scheme(), "http" | "https"))
+            .collect();
+        Ok(Some(Fetched { url, links }))
+    }
+}
+
+/// The `<base href>` of `html`, if any, and the `href`s of its `<a>` and
+/// `<area>` elements that are not marked `rel="nofollow"`.
+///
+/// This is a lenient scan rather than a full HTML parser: it skips
+/// comments and the contents of `<script>` and `<style>` elements, and
+/// reads the attributes of every other tag.
+fn extract_links(html: &str) -> (Option<String>, Vec<String>) {
+    // ASCII lowercasing keeps byte offsets, so positions carry over to `html`.
+    let lower = html.to_ascii_lowercase();
+    let mut base = None;
+    let mut hrefs = Vec::new();
+    let mut pos = 0;
+
+    while let Some(offset) = lower[pos..].find('<') {
+        let start = pos + offset;
+        if lower[start..].starts_with("<!--") {
+            pos = lower[start + 4..]
+                .find("-->")
+                .map_or(lower.len(), |end| start + 4 + end + 3);
+            continue;
+        }
+
+        let name_start = start + 1;
+        let name_end = lower[name_start..]
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .map_or(lower.len(), |end| name_start + end);
+        let name = &lower[name_start..name_end];
+        if name.is_empty() {
+            pos = name_start;
+            continue;
+        }
+        let (attributes, end) = parse_attributes(html, name_end);
+        pos = end;
+
+        let attribute = |wanted: &str| {
+            attributes
+                .iter()
+                .find(|(name, _)| name == wanted)
+                .map(|(_, value)| value.clone())
+        };
+        match name {
+            "script" | "style" => {
+                let close = format!("</{}", name);
+                pos = lower[pos..].find(&close).map_or(lower.len(), |at| pos + at);
+            }
+            "a" | "area" => {
+                let nofollow = attribute("rel").is_some_and(|rel| {
+                    rel.split_ascii_whitespace()
+                        .any(|value| value.eq_ignore_ascii_case("nofollow"))
+                });
+                if let Some(href) = attribute("href").filter(|_| !nofollow) {
+                    hrefs.push(href);
+                }
+            }
+            "base" if base.is_none() => base = attribute("href"),
+            _ => {}
+        }
+    }
+    (base, hrefs)
+}
+
+/// Parse the attributes of a tag from `pos` up to its closing `>`.
+///
+/// Returns the attributes, with lowercased names and decoded values, and
+/// the position after the tag.
+fn parse_attributes(html: &str, mut pos: usize) -> (Vec<(String, String)>, usize) {
+    let bytes = html.as_bytes();
+    let mut attributes = Vec::new();
+
+    loop {
+        while pos < bytes.len() && (bytes[pos].is_ascii_whitespace() || bytes[pos] == b'/') {
+            pos += 1;
+        }
+        if pos >= bytes.len() {
+            return (attributes, pos);
+        }
+        if bytes[pos] == b'>' {
+            return (attributes, pos + 1);
+        }
+
+        let name_start = pos;
+        while pos < bytes.len()
+            && !bytes[pos].is_ascii_whitespace()
+            && !matches!(bytes[pos], b'=' | b'>' | b'/')
+        {
+            pos += 1;
+        }
+        // A stray `=` would otherwise never be consumed.
+        if pos == name_start {
+            pos += 1;
+            continue;
+        }
+        let name = html[name_start..pos].to_ascii_lowercase();
+
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        let mut value = String::new();
+        if pos < bytes.len() && bytes[pos] == b'=' {
+            pos += 1;
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            let value_end = match bytes.get(pos) {
+                Some(&quote @ (b'"' | b'\'')) => {
+                    pos += 1;
+                    let end = html[pos..]
+                        .find(quote as char)
+                        .map_or(bytes.len(), |end| pos + end);
+                    value = decode_entities(&html[pos..end]);
+                    (end + 1).min(bytes.len())
+                }
+                _ => {
+                    let end = html[pos..]
+                        .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                        .map_or(bytes.len(), |end| pos + end);
+                    value = decode_entities(&html[pos..end]);
+                    end
+                }
+            };
+            pos = value_end;
+        }
+        attributes.push((name, value));
+    }
+}
+
+/// Decode the character references likely to appear in a URL.
+fn decode_entities(value: &str) -> String {
+    if !value.contains('&') {
+        return value.to_string();
+    }
+    value
+        .replace("&quot;", "\"")
+        .replace("&#34;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&#38;", "&")
+        .replace("&amp;", "&")
+}
+
+/// Builder for [`Crawler`].
+#[derive(Debug)]
+pub struct CrawlerBuilder {
+    seed: Result<Url>,
+    options: ScreenshotOptions,
+    output_dir: Option<PathBuf>,
+    max_depth: usize,
+    max_pages: usize,
+    exclude: Vec<String>,
+    respect_robots: bool,
+    user_agent: String,
+    per_host_concurrency: usize,
+    timeout: Duration,
+}
+
+impl CrawlerBuilder {
+    /// Capture every page with the options of `request`; its URL is ignored.
+    pub fn base_request(mut self, request: ScreenshotRequest) -> Self {
+        self.options = request.options();
+        self
+    }
+
+    /// Capture every page with `options`.
+    pub fn options(mut self, options: ScreenshotOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Directory the screenshots are written to (default: the current
+    /// directory).
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = Some(dir.into());
+        self
+    }
+
+    /// Follow links at most this many steps from the seed (default: 2).
+    /// A depth of 0 captures only the seed.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Stop after finding this many pages (default: 100).
+    pub fn max_pages(mut self, max: usize) -> Self {
+        self.max_pages = max;
+        self
+    }
+
+    /// Neither visit nor capture pages matching this pattern.
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Whether to obey the site's `robots.txt` (default: true).
+    pub fn respect_robots(mut self, respect: bool) -> Self {
+        self.respect_robots = respect;
+        self
+    }
+
+    /// User agent sent when fetching pages and matched against `robots.txt`
+    /// groups (default: `pxshot/<version>`).
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Maximum number of page fetches, and of captures, in flight against
+    /// the site at once (default: 2).
+    pub fn per_host_concurrency(mut self, concurrency: usize) -> Self {
+        self.per_host_concurrency = concurrency;
+        self
+    }
+
+    /// Timeout for fetching each page (default: 30 seconds).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Build the crawler.
+    pub fn build(self) -> Result<Crawler> {
+        let seed = self.seed?;
+        if !matches!(seed.scheme(), "http" | "https") {
+            return Err(Error::Config(format!(
+                "invalid crawl seed `{}`; expected http or https",
+                seed
+            )));
+        }
+        if let Some(pattern) = self.exclude.iter().find(|pattern| pattern.is_empty()) {
+            return Err(Error::Config(format!(
+                "invalid crawl pattern `{}`",
+                pattern
+            )));
+        }
+        if self.max_pages == 0 {
+            return Err(Error::Config("crawl max_pages must be at least 1".into()));
+        }
+        if self.per_host_concurrency == 0 {
+            return Err(Error::Config(
+                "per-host concurrency must be at least 1".into(),
+            ));
+        }
+        let http = reqwest::Client::builder()
+            .user_agent(self.user_agent.clone())
+            .build()
+            .map_err(|e| Error::Config(format!("invalid crawl user agent: {}", e)))?;
+
+        Ok(Crawler {
+            seed,
+            options: self.options,
+            output_dir: self.output_dir,
+            max_depth: self.max_depth,
+            max_pages: self.max_pages,
+            exclude: self.exclude,
+            respect_robots: self.respect_robots,
+            user_agent: self.user_agent,
+            per_host_concurrency: self.per_host_concurrency,
+            timeout: self.timeout,
+            http,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(html: &str) -> Vec<String> {
+        extract_links(html).1
+    }
+
+    #[test]
+    fn finds_anchor_and_area_links() {
+        let html = r#"<p><A HREF="/a">A</A> <area href='/b'> <link href="/style.css"></p>"#;
+        assert_eq!(links(html), ["/a", "/b"]);
+    }
+
+    #[test]
+    fn skips_comments_scripts_and_styles() {
+        let html = r#"
+            <!-- <a href="/commented">x</a> -->
+            <script>document.write('<a href="/scripted">x</a>');</script>
+            <style>a[href="/styled"] { color: red }</style>
+            <SCRIPT type="text/javascript">if (a < b) { "<a href='/upper'>" }</SCRIPT>
+            <a href="/kept">kept</a>
+        "#;
+        assert_eq!(links(html), ["/kept"]);
+    }
+
+    #[test]
+    fn unterminated_comment_hides_the_rest() {
+        assert_eq!(links(r#"<a href="/a"><!-- <a href="/b">"#), ["/a"]);
+    }
+
+    #[test]
+    fn skips_nofollow_links() {
+        let html = r#"
+            <a href="/sponsored" rel="sponsored nofollow">x</a>
+            <a href="/upper" rel=NOFOLLOW>x</a>
+            <a href="/followed" rel="noopener">x</a>
+        "#;
+        assert_eq!(links(html), ["/followed"]);
+    }
+
+    #[test]
+    fn reads_first_base_href() {
+        let html = r#"<head><base href="/docs/"><base href="/other/"></head><a href="page">x</a>"#;
+        assert_eq!(
+            extract_links(html),
+            (Some("/docs/".to_string()), vec!["page".to_string()])
+        );
+        assert_eq!(extract_links(r#"<base target="_blank">"#).0, None);
+    }
+
+    #[test]
+    fn multibyte_text_around_tags() {
+        let html =
+            "<p>日本語 ✓</p><a href=\"/ünïcödé\">naïve</a>é<<é<a title=\"ä\" href=/🚀>🚀</a>";
+        assert_eq!(links(html), ["/ünïcödé", "/🚀"]);
+    }
+
+    #[test]
+    fn attribute_values() {
+        let html = r#"<a id=x href = "/a?b=1&amp;c=2" data-x='it&#39;s' disabled rel=next>"#;
+        let (attributes, end) = parse_attributes(html, 2);
+        assert_eq!(end, html.len());
+        assert_eq!(
+            attributes,
+            [
+                ("id".to_string(), "x".to_string()),
+                ("href".to_string(), "/a?b=1&c=2".to_string()),
+                ("data-x".to_string(), "it's".to_string()),
+                ("disabled".to_string(), String::new()),
+                ("rel".to_string(), "next".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unquoted_values_end_at_whitespace_or_tag_end() {
+        let html = "<a HREF=/path/page.html>text</a>";
+        let (attributes, end) = parse_attributes(html, 2);
+        assert_eq!(
+            attributes,
+            [("href".to_string(), "/path/page.html".to_string())]
+        );
+        assert_eq!(&html[end..], "text</a>");
+        assert_eq!(links("<a href=/x\tclass=y>"), ["/x"]);
+    }
+
+    #[test]
+    fn self_closing_and_malformed_tags() {
+        let (attributes, end) = parse_attributes("<base href=\"/b/\"/>after", 5);
+        assert_eq!(attributes, [("href".to_string(), "/b/".to_string())]);
+        assert_eq!(end, "<base href=\"/b/\"/>".len());
+
+        let (attributes, end) = parse_attributes("<a = href=\"/x\">", 2);
+        assert_eq!(attributes, [("href".to_string(), "/x".to_string())]);
+        assert_eq!(end, "<a = href=\"/x\">".len());
+
+        let html = "<a href=\"/unterminated";
+        let (attributes, end) = parse_attributes(html, 2);
+        assert_eq!(
+            attributes,
+            [("href".to_string(), "/unterminated".to_string())]
+        );
+        assert_eq!(end, html.len());
+    }
+
+    #[test]
+    fn decodes_entities() {
+        assert_eq!(decode_entities("/a?x=1&amp;y=2"), "/a?x=1&y=2");
+        assert_eq!(decode_entities("&amp;lt;"), "&lt;");
+        assert_eq!(decode_entities("plain"), "plain");
+    }
+}
@@ -0,0 +1,707 @@
+//! Visual diffing between screenshots.
+//!
+//! Requires the `image` feature. [`compare`] takes two encoded screenshots
+//! and reports how many pixels changed, where, and a highlighted diff image:
+//!
+//! ```no_run
+//! use pxshot::diff::{self, DiffOptions, Region};
+//!
+//! # fn main() -> pxshot::Result<()> {
+//! let baseline = std::fs::read("baseline.png")?;
+//! let current = std::fs::read("current.png")?;
+//!
+//! let options = DiffOptions::new()
+//!     .tolerance(8)
+//!     // The clock in the header changes on every capture.
+//!     .ignore(Region::new(1100, 0, 180, 60));
+//!
+//! let result = diff::compare(&baseline, &current, &options)?;
+//! println!("{:.2}% changed in {} regions", result.percentage(), result.regions.len());
+//! result.save_image("diff.png")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Images of different sizes are compared over the larger of the two; pixels
+//! present in only one image count as changed.
+
+use std::collections::VecDeque;
+use std::path::Path;
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::imaging;
+use crate::traits::ScreenshotClient;
+use crate::types::{ImageFormat, ScreenshotRequest, ScreenshotResponse};
+
+const DEFAULT_MERGE_DISTANCE: u32 = 16;
+
+const CHANGED_COLOR: Rgba<u8> = Rgba([255, 0, 0, 255]);
+const ANTI_ALIASED_COLOR: Rgba<u8> = Rgba([255, 200, 0, 255]);
+const IGNORED_COLOR: Rgba<u8> = Rgba([200, 220, 255, 255]);
+/// How strongly unchanged pixels are faded towards white in the diff image.
+const FADE: f64 = 0.1;
+
+/// A rectangle of pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Region {
+    /// Left edge in pixels.
+    pub x: u32,
+    /// Top edge in pixels.
+    pub y: u32,
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+}
+
+impl Region {
+    /// Create a region from its top-left corner and size.
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Whether the pixel at (`x`, `y`) lies inside the region.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x
+            && y >= self.y
+            && u64::from(x) < u64::from(self.x) + u64::from(self.width)
+            && u64::from(y) < u64::from(self.y) + u64::from(self.height)
+    }
+
+    /// Number of pixels in the region.
+    pub fn area(&self) -> u64 {
+        u64::from(self.width) * u64::from(self.height)
+    }
+
+    fn right(&self) -> u32 {
+        self.x.saturating_add(self.width)
+    }
+
+    fn bottom(&self) -> u32 {
+        self.y.saturating_add(self.height)
+    }
+
+    fn union(&self, other: &Region) -> Region {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Region::new(
+            x,
+            y,
+            self.right().max(other.right()) - x,
+            self.bottom().max(other.bottom()) - y,
+        )
+    }
+}
+
+/// Settings for [`compare`].
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    tolerance: u8,
+    detect_anti_aliasing: bool,
+    ignore: Vec<Region>,
+    merge_distance: u32,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 0,
+            detect_anti_aliasing: true,
+            ignore: Vec::new(),
+            merge_distance: DEFAULT_MERGE_DISTANCE,
+        }
+    }
+}
+
+impl DiffOptions {
+    /// Create options with the defaults: exact matching, anti-aliasing
+    /// detection on, no ignored regions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Largest difference in any color or alpha channel (0-255) for a pixel
+    /// to still count as unchanged (default: 0).
+    pub fn tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Don't count pixels that look like anti-aliased edges as changed
+    /// (default: true).
+    ///
+    /// Font smoothing and subpixel rendering often differ slightly between
+    /// captures of the same page. Anti-aliased pixels are still shown in the
+    /// diff image, in yellow.
+    pub fn detect_anti_aliasing(mut self, detect: bool) -> Self {
+        self.detect_anti_aliasing = detect;
+        self
+    }
+
+    /// Ignore a region, e.g. one showing a clock, an ad or an animation.
+    ///
+    /// May be called several times. Ignored pixels are excluded from the
+    /// counts and shown in blue in the diff image.
+    pub fn ignore(mut self, region: Region) -> Self {
+        self.ignore.push(region);
+        self
+    }
+
+    /// Ignore several regions.
+    pub fn ignore_all(mut self, regions: impl IntoIterator<Item = Region>) -> Self {
+        self.ignore.extend(regions);
+        self
+    }
+
+    /// Changes closer than about this many pixels are reported as a single
+    /// region (default: 16).
+    pub fn merge_distance(mut self, pixels: u32) -> Self {
+        self.merge_distance = pixels.max(1);
+        self
+    }
+}
+
+/// Result of comparing two screenshots.
+#[derive(Debug, Clone)]
+pub struct DiffResult {
+    /// Width of the compared area in pixels.
+    pub width: u32,
+
+    /// Height of the compared area in pixels.
+    pub height: u32,
+
+    /// Whether the two images have different dimensions.
+    pub dimensions_differ: bool,
+
+    /// Number of pixels compared, excluding ignored regions.
+    pub compared_pixels: u64,
+
+    /// Number of changed pixels.
+    pub changed_pixels: u64,
+
+    /// Number of differing pixels treated as anti-aliasing and not counted
+    /// as changed.
+    pub anti_aliased_pixels: u64,
+
+    /// Bounding boxes of the changed areas, from top to bottom.
+    pub regions: Vec<Region>,
+
+    /// The baseline, faded, with changed pixels in red, anti-aliased pixels
+    /// in yellow and ignored regions in blue.
+    pub image: RgbaImage,
+}
+
+impl DiffResult {
+    /// Percentage of compared pixels that changed (0-100).
+    pub fn percentage(&self) -> f64 {
+        if self.compared_pixels == 0 {
+            return 0.0;
+        }
+        self.changed_pixels as f64 * 100.0 / self.compared_pixels as f64
+    }
+
+    /// Whether no pixel changed.
+    pub fn is_identical(&self) -> bool {
+        self.changed_pixels == 0 && !self.dimensions_differ
+    }
+
+    /// Whether at most `percentage` percent of the pixels changed.
+    pub fn within(&self, percentage: f64) -> bool {
+        self.percentage() <= percentage
+    }
+
+    /// Encode the diff image as PNG.
+    pub fn image_png(&self) -> Result<Vec<u8>> {
+        imaging::encode(
+            &DynamicImage::ImageRgba8(self.image.clone()),
+            ImageFormat::Png,
+            None,
+        )
+    }
+
+    /// Write the diff image to `path` as PNG.
+    pub fn save_image(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.image_png()?)?;
+        Ok(())
+    }
+}
+
+/// Compare two encoded screenshots (PNG, JPEG or WebP).
+pub fn compare(baseline: &[u8], current: &[u8], options: &DiffOptions) -> Result<DiffResult> {
+    Ok(compare_images(
+        &decode(baseline, "baseline")?,
+        &decode(current, "current")?,
+        options,
+    ))
+}
+
+/// Capture `request` and compare it against the baseline image at `path`.
+///
+/// Returns the fresh screenshot along with the comparison, so it can be
+/// saved as the new baseline if the change is expected. `request` must not
+/// set `store`.
+pub async fn compare_with_baseline<C>(
+    client: &C,
+    request: ScreenshotRequest,
+    path: impl AsRef<Path>,
+    options: &DiffOptions,
+) -> Result<(ScreenshotResponse, DiffResult)>
+where
+    C: ScreenshotClient + ?Sized,
+{
+    let baseline = std::fs::read(path)?;
+    let response = client.screenshot(request).await?;
+    let current = response.bytes().ok_or_else(|| {
+        Error::Image("cannot compare a stored screenshot; capture without `store`".into())
+    })?;
+    let result = compare(&baseline, current, options)?;
+    Ok((response, result))
+}
+
+pub(crate) fn decode(bytes: &[u8], which: &str) -> Result<DynamicImage> {
+    let format = ImageFormat::detect(bytes)
+        .ok_or_else(|| Error::Image(format!("unrecognized format for {} image", which)))?;
+    imaging::decode(bytes, format)
+}
+
+/// Compare two decoded images.
+pub fn compare_images(
+    baseline: &DynamicImage,
+    current: &DynamicImage,
+    options: &DiffOptions,
+) -> DiffResult {
+    let baseline = baseline.to_rgba8();
+    let current = current.to_rgba8();
+
+    let width = baseline.width().max(current.width());
+    let height = baseline.height().max(current.height());
+    let dimensions_differ = baseline.dimensions() != current.dimensions();
+
+    let mut image = RgbaImage::new(width, height);
+    let mut changed = vec![false; width as usize * height as usize];
+    let mut compared_pixels = 0;
+    let mut changed_pixels = 0;
+    let mut anti_aliased_pixels = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let a = pixel(&baseline, x, y);
+            let b = pixel(&current, x, y);
+
+            if options.ignore.iter().any(|region| region.contains(x, y)) {
+                image.put_pixel(x, y, blend(a.or(b), IGNORED_COLOR));
+                continue;
+            }
+            compared_pixels += 1;
+
+            let (a, b) = match (a, b) {
+                (Some(a), Some(b)) => (a, b),
+                _ => {
+                    changed_pixels += 1;
+                    changed[y as usize * width as usize + x as usize] = true;
+                    image.put_pixel(x, y, CHANGED_COLOR);
+                    continue;
+                }
+            };
+
+            if max_channel_delta(a, b) <= options.tolerance {
+                image.put_pixel(x, y, fade(a));
+            } else if options.detect_anti_aliasing
+                && (anti_aliased(&baseline, &current, x, y)
+                    || anti_aliased(&current, &baseline, x, y))
+            {
+                anti_aliased_pixels += 1;
+                image.put_pixel(x, y, ANTI_ALIASED_COLOR);
+            } else {
+                changed_pixels += 1;
+                changed[y as usize * width as usize + x as usize] = true;
+                image.put_pixel(x, y, CHANGED_COLOR);
+            }
+        }
+    }
+
+    DiffResult {
+        width,
+        height,
+        dimensions_differ,
+        compared_pixels,
+        changed_pixels,
+        anti_aliased_pixels,
+        regions: regions(&changed, width, height, options.merge_distance),
+        image,
+    }
+}
+
+fn pixel(image: &RgbaImage, x: u32, y: u32) -> Option<Rgba<u8>> {
+    (x < image.width() && y < image.height()).then(|| *image.get_pixel(x, y))
+}
+
+fn max_channel_delta(a: Rgba<u8>, b: Rgba<u8>) -> u8 {
+    a.0.iter()
+        .zip(b.0.iter())
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Brightness of a pixel blended over white, in 0-255.
+fn brightness(pixel: Rgba<u8>) -> f64 {
+    let [r, g, b, a] = pixel.0.map(f64::from);
+    let alpha = a / 255.0;
+    let blend = |channel: f64| 255.0 + (channel - 255.0) * alpha;
+    blend(r) * 0.298_895_31 + blend(g) * 0.586_622_47 + blend(b) * 0.114_482_23
+}
+
+fn fade(pixel: Rgba<u8>) -> Rgba<u8> {
+    let value = (255.0 + (brightness(pixel) - 255.0) * FADE).round() as u8;
+    Rgba([value, value, value, 255])
+}
+
+fn blend(pixel: Option<Rgba<u8>>, tint: Rgba<u8>) -> Rgba<u8> {
+    let base = pixel.map_or(255.0, brightness) / 255.0;
+    Rgba([
+        (f64::from(tint[0]) * base).round() as u8,
+        (f64::from(tint[1]) * base).round() as u8,
+        (f64::from(tint[2]) * base).round() as u8,
+        255,
+    ])
+}
+
+/// Whether the pixel at (`x`, `y`) of `image` looks like an anti-aliased
+/// edge, following the approach of `pixelmatch` (Vyšniauskas, 2009).
+///
+/// A pixel is anti-aliased if its neighbours include both a darker and a
+/// brighter pixel, and the darkest or brightest of them sits in a flat area
+/// in both images, i.e. the pixel lies on a smooth edge between two flat
+/// colors.
+fn anti_aliased(image: &RgbaImage, other: &RgbaImage, x: u32, y: u32) -> bool {
+    let (width, height) = image.dimensions();
+    let center = brightness(*image.get_pixel(x, y));
+
+    let mut zeroes = usize::from(x == 0 || y == 0 || x + 1 == width || y + 1 == height);
+    // Most negative and most positive brightness deltas, and where they are.
+    let mut darkest = (0.0, x, y);
+    let mut brightest = (0.0, x, y);
+
+    for (nx, ny) in neighbours(x, y, width, height) {
+        let delta = center - brightness(*image.get_pixel(nx, ny));
+        if delta == 0.0 {
+            zeroes += 1;
+            if zeroes > 2 {
+                return false;
+            }
+        } else if delta < darkest.0 {
+            darkest = (delta, nx, ny);
+        } else if delta > brightest.0 {
+            brightest = (delta, nx, ny);
+        }
+    }
+
+    if darkest.0 == 0.0 || brightest.0 == 0.0 {
+        return false;
+    }
+    let flat_in_both = |(_, x, y): (f64, u32, u32)| {
+        has_many_siblings(image, x, y) && has_many_siblings(other, x, y)
+    };
+    flat_in_both(darkest) || flat_in_both(brightest)
+}
+
+/// Whether the pixel at (`x`, `y`) has more than two identical neighbours.
+fn has_many_siblings(image: &RgbaImage, x: u32, y: u32) -> bool {
+    let (width, height) = image.dimensions();
+    if x >= width || y >= height {
+        return false;
+    }
+    let center = image.get_pixel(x, y);
+
+    let mut zeroes = usize::from(x == 0 || y == 0 || x + 1 == width || y + 1 == height);
+    for (nx, ny) in neighbours(x, y, width, height) {
+        if image.get_pixel(nx, ny) == center {
+            zeroes += 1;
+            if zeroes > 2 {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// The up to eight pixels surrounding (`x`, `y`).
+fn neighbours(x: u32, y: u32, width: u32, height: u32) -> impl Iterator<Item = (u32, u32)> {
+    let xs = x.saturating_sub(1)..=(x + 1).min(width - 1);
+    let ys = y.saturating_sub(1)..=(y + 1).min(height - 1);
+    ys.flat_map(move |ny| xs.clone().map(move |nx| (nx, ny)))
+        .filter(move |&(nx, ny)| (nx, ny) != (x, y))
+}
+
+/// Group changed pixels into bounding boxes.
+///
+/// The image is divided into cells of `cell` pixels; adjacent cells with
+/// changes are joined, and each group is reported as the bounding box of its
+/// changed pixels.
+fn regions(changed: &[bool], width: u32, height: u32, cell: u32) -> Vec<Region> {
+    let columns = width.div_ceil(cell) as usize;
+    let rows = height.div_ceil(cell) as usize;
+
+    let mut cells: Vec<Option<Region>> = vec![None; columns * rows];
+    for y in 0..height {
+        for x in 0..width {
+            if !changed[y as usize * width as usize + x as usize] {
+                continue;
+            }
+            let index = (y / cell) as usize * columns + (x / cell) as usize;
+            let point = Region::new(x, y, 1, 1);
+            cells[index] = Some(cells[index].map_or(point, |region| region.union(&point)));
+        }
+    }
+
+    let mut visited = vec![false; cells.len()];
+    let mut regions = Vec::new();
+    for start in 0..cells.len() {
+        let Some(mut region) = cells[start].filter(|_| !visited[start]) else {
+            continue;
+        };
+        visited[start] = true;
+
+        let mut queue = VecDeque::from([start]);
+        while let Some(index) = queue.pop_front() {
+            let (row, column) = (index / columns, index % columns);
+            for next_row in row.saturating_sub(1)..=(row + 1).min(rows - 1) {
+                for next_column in column.saturating_sub(1)..=(column + 1).min(columns - 1) {
+                    let next = next_row * columns + next_column;
+                    if visited[next] {
+                        continue;
+                    }
+                    if let Some(cell) = cells[next] {
+                        visited[next] = true;
+                        region = region.union(&cell);
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        regions.push(region);
+    }
+
+    regions.sort_by_key(|region| (region.y, region.x));
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+    const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+    fn solid(width: u32, height: u32, color: Rgba<u8>) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, color)
+    }
+
+    fn diff(baseline: &RgbaImage, current: &RgbaImage, options: &DiffOptions) -> DiffResult {
+        compare_images(
+            &DynamicImage::ImageRgba8(baseline.clone()),
+            &DynamicImage::ImageRgba8(current.clone()),
+            options,
+        )
+    }
+
+    fn png(image: &RgbaImage) -> Vec<u8> {
+        imaging::encode(
+            &DynamicImage::ImageRgba8(image.clone()),
+            ImageFormat::Png,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn identical_images() {
+        let image = solid(20, 10, WHITE);
+        let result = compare(&png(&image), &png(&image), &DiffOptions::new()).unwrap();
+
+        assert!(result.is_identical());
+        assert!(!result.dimensions_differ);
+        assert_eq!((result.width, result.height), (20, 10));
+        assert_eq!(result.compared_pixels, 200);
+        assert_eq!(result.changed_pixels, 0);
+        assert_eq!(result.percentage(), 0.0);
+        assert!(result.regions.is_empty());
+    }
+
+    #[test]
+    fn undecodable_input_is_an_error() {
+        let image = png(&solid(2, 2, WHITE));
+        assert!(compare(b"not an image", &image, &DiffOptions::new()).is_err());
+        assert!(compare(&image, &image[..8], &DiffOptions::new()).is_err());
+    }
+
+    #[test]
+    fn different_sizes_compare_over_the_larger_area() {
+        let result = diff(
+            &solid(4, 4, WHITE),
+            &solid(4, 6, WHITE),
+            &DiffOptions::new(),
+        );
+
+        assert!(result.dimensions_differ);
+        assert!(!result.is_identical());
+        assert_eq!((result.width, result.height), (4, 6));
+        assert_eq!(result.compared_pixels, 24);
+        assert_eq!(
+            result.changed_pixels, 8,
+            "the two extra rows count as changed"
+        );
+        assert_eq!(result.regions, vec![Region::new(0, 4, 4, 2)]);
+    }
+
+    #[test]
+    fn single_changed_pixel() {
+        let baseline = solid(10, 10, WHITE);
+        let mut current = baseline.clone();
+        current.put_pixel(3, 7, BLACK);
+
+        let result = diff(&baseline, &current, &DiffOptions::new());
+        assert_eq!(result.changed_pixels, 1);
+        assert_eq!(result.anti_aliased_pixels, 0);
+        assert_eq!(result.regions, vec![Region::new(3, 7, 1, 1)]);
+        assert_eq!(*result.image.get_pixel(3, 7), CHANGED_COLOR);
+        assert_eq!(result.percentage(), 1.0);
+        assert!(result.within(1.0));
+        assert!(!result.within(0.99));
+    }
+
+    #[test]
+    fn tolerance_is_inclusive() {
+        let baseline = solid(5, 5, WHITE);
+        let mut current = baseline.clone();
+        current.put_pixel(2, 2, Rgba([255, 250, 255, 255]));
+
+        let at_threshold = diff(&baseline, &current, &DiffOptions::new().tolerance(5));
+        assert_eq!(
+            at_threshold.changed_pixels, 0,
+            "a delta equal to the tolerance is unchanged"
+        );
+
+        let below = diff(&baseline, &current, &DiffOptions::new().tolerance(4));
+        assert_eq!(below.changed_pixels, 1);
+    }
+
+    #[test]
+    fn tolerance_applies_to_alpha() {
+        let baseline = solid(5, 5, WHITE);
+        let mut current = baseline.clone();
+        current.put_pixel(2, 2, Rgba([255, 255, 255, 0]));
+
+        assert_eq!(
+            diff(&baseline, &current, &DiffOptions::new().tolerance(254)).changed_pixels,
+            1
+        );
+        assert_eq!(
+            diff(&baseline, &current, &DiffOptions::new().tolerance(255)).changed_pixels,
+            0
+        );
+    }
+
+    /// Black above white, with a grey anti-aliased row between them.
+    fn edge(grey: u8) -> RgbaImage {
+        RgbaImage::from_fn(10, 10, |_, y| match y {
+            0..=4 => BLACK,
+            5 => Rgba([grey, grey, grey, 255]),
+            _ => WHITE,
+        })
+    }
+
+    #[test]
+    fn anti_aliased_pixels_are_ignored() {
+        let baseline = edge(128);
+        let mut current = baseline.clone();
+        current.put_pixel(5, 5, Rgba([100, 100, 100, 255]));
+
+        let result = diff(&baseline, &current, &DiffOptions::new());
+        assert_eq!(result.changed_pixels, 0);
+        assert_eq!(result.anti_aliased_pixels, 1);
+        assert!(result.regions.is_empty());
+        assert_eq!(*result.image.get_pixel(5, 5), ANTI_ALIASED_COLOR);
+
+        let strict = diff(
+            &baseline,
+            &current,
+            &DiffOptions::new().detect_anti_aliasing(false),
+        );
+        assert_eq!(strict.changed_pixels, 1);
+        assert_eq!(strict.anti_aliased_pixels, 0);
+    }
+
+    #[test]
+    fn changes_in_flat_areas_are_not_anti_aliasing() {
+        let baseline = edge(128);
+        let mut current = baseline.clone();
+        current.put_pixel(5, 8, Rgba([128, 128, 128, 255]));
+
+        let result = diff(&baseline, &current, &DiffOptions::new());
+        assert_eq!(result.changed_pixels, 1);
+        assert_eq!(result.anti_aliased_pixels, 0);
+    }
+
+    #[test]
+    fn ignored_regions_are_excluded() {
+        let baseline = solid(10, 10, WHITE);
+        let mut current = baseline.clone();
+        current.put_pixel(1, 1, BLACK);
+        current.put_pixel(8, 8, BLACK);
+
+        let options = DiffOptions::new().ignore(Region::new(0, 0, 3, 3));
+        let result = diff(&baseline, &current, &options);
+        assert_eq!(result.compared_pixels, 91);
+        assert_eq!(result.changed_pixels, 1);
+        assert_eq!(result.regions, vec![Region::new(8, 8, 1, 1)]);
+
+        let options =
+            DiffOptions::new().ignore_all([Region::new(0, 0, 3, 3), Region::new(8, 8, 1, 1)]);
+        assert!(diff(&baseline, &current, &options).is_identical());
+    }
+
+    #[test]
+    fn region_contains_edges() {
+        let region = Region::new(2, 3, 4, 5);
+        assert!(region.contains(2, 3));
+        assert!(region.contains(5, 7));
+        assert!(!region.contains(6, 7));
+        assert!(!region.contains(5, 8));
+        assert!(!region.contains(1, 3));
+        assert_eq!(region.area(), 20);
+
+        let huge = Region::new(u32::MAX - 1, 0, u32::MAX, 1);
+        assert!(huge.contains(u32::MAX, 0), "edges must not overflow");
+    }
+
+    #[test]
+    fn nearby_changes_merge_into_one_region() {
+        let baseline = solid(100, 40, WHITE);
+        let mut current = baseline.clone();
+        current.put_pixel(2, 2, BLACK);
+        current.put_pixel(20, 5, BLACK);
+        current.put_pixel(90, 30, BLACK);
+
+        let result = diff(&baseline, &current, &DiffOptions::new());
+        assert_eq!(
+            result.regions,
+            vec![Region::new(2, 2, 19, 4), Region::new(90, 30, 1, 1)]
+        );
+
+        let result = diff(&baseline, &current, &DiffOptions::new().merge_distance(4));
+        assert_eq!(result.regions.len(), 3);
+
+        let result = diff(&baseline, &current, &DiffOptions::new().merge_distance(64));
+        assert_eq!(result.regions, vec![Region::new(2, 2, 89, 29)]);
+    }
+}
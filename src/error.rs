@@ -2,6 +2,8 @@
 
 use thiserror::Error;
 
+use crate::budget::BudgetResource;
+
 /// Errors that can occur when using the Pxshot SDK.
 #[derive(Error, Debug)]
 pub enum Error {
@@ -9,6 +11,25 @@ pub enum Error {
     #[error("missing required field: {0}")]
     MissingField(&'static str),
 
+    /// A request option is out of range.
+    #[error("invalid {field}: {message}")]
+    InvalidField {
+        /// Name of the field.
+        field: &'static str,
+        /// What is wrong with the value.
+        message: String,
+    },
+
+    /// The URL to capture was rejected by the client's
+    /// [`UrlPolicy`](crate::UrlPolicy). No API call was made.
+    #[error("URL {url} rejected by policy: {reason}")]
+    PolicyViolation {
+        /// The rejected URL.
+        url: String,
+        /// Why the URL was rejected.
+        reason: String,
+    },
+
     /// HTTP request failed.
     #[error("HTTP request failed: {0}")]
     Request(#[from] reqwest::Error),
@@ -26,6 +47,33 @@ pub enum Error {
     #[error("failed to parse response: {0}")]
     Parse(String),
 
+    /// I/O error.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// A configured budget has been reached for this period.
+    #[error("{resource} budget exceeded: {used} used of {limit}")]
+    BudgetExceeded {
+        /// Resource whose budget was reached.
+        resource: BudgetResource,
+        /// Amount used this period.
+        used: u64,
+        /// Budget for the period.
+        limit: u64,
+    },
+
+    /// No recorded interaction matches the request, or the cassette is invalid.
+    #[error("cassette error: {0}")]
+    Cassette(String),
+
+    /// An image could not be decoded or encoded.
+    #[error("image error: {0}")]
+    Image(String),
+
+    /// A batch manifest could not be read.
+    #[error("invalid manifest: {0}")]
+    Manifest(String),
+
     /// Invalid configuration.
     #[error("invalid configuration: {0}")]
     Config(String),
@@ -0,0 +1,538 @@
+//! Static HTML reports of batch runs.
+
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+#[cfg(feature = "image")]
+use base64::engine::general_purpose::STANDARD as BASE64;
+#[cfg(feature = "image")]
+use base64::Engine;
+use chrono::Utc;
+
+use crate::batch::{BatchItemReport, BatchItemStatus, BatchReport};
+#[cfg(feature = "image")]
+use crate::diff::{self, DiffOptions};
+use crate::error::Result;
+#[cfg(feature = "image")]
+use crate::imaging;
+use crate::output::image_dimensions;
+#[cfg(feature = "image")]
+use crate::types::ImageFormat;
+
+const DEFAULT_TITLE: &str = "Screenshot report";
+/// Size of embedded thumbnails, twice the size they are shown at for
+/// high-density displays.
+#[cfg(feature = "image")]
+const THUMBNAIL_SIZE: (u32, u32) = (480, 320);
+#[cfg(feature = "image")]
+const THUMBNAIL_QUALITY: u8 = 75;
+/// Bytes first read from an image to find its dimensions, and the most read
+/// when a JPEG has large metadata segments before its frame header.
+const HEADER_BYTES: usize = 4096;
+const MAX_HEADER_BYTES: usize = 1024 * 1024;
+
+/// A self-contained HTML page for reviewing a [`BatchReport`], e.g. from a
+/// [`Batch`](crate::Batch) or [`Crawler`](crate::Crawler) run.
+///
+/// Every item is listed with a thumbnail, its URL, dimensions, file size,
+/// timing and any error, and can be filtered by URL and status in the
+/// browser. Styles and scripts are inline, and captures are linked by paths
+/// relative to the report, so the report and screenshots can be zipped up or
+/// published together. With the `image` feature, thumbnails are embedded in
+/// the page so it stays light however large the captures are; without it,
+/// the captures themselves are shown scaled down.
+///
+/// With [`baselines`](Gallery::baselines), each capture is shown side by
+/// side with its baseline from an earlier run. With the `image` feature,
+/// a [diff](crate::diff) of the two is embedded as well, and items that
+/// changed can be filtered for.
+///
+/// # Example
+///
+/// ```no_run
+/// use pxshot::{Batch, Gallery, Manifest, Pxshot};
+///
+/// #[tokio::main]
+/// async fn main() -> pxshot::Result<()> {
+///     let client = Pxshot::new("px_your_api_key");
+///     let manifest = Manifest::from_path("screenshots.toml")?;
+///     let report = Batch::builder(manifest).build()?.run(&client).await?;
+///
+///     Gallery::new(&report)
+///         .title("Nightly capture")
+///         .baselines("screenshots", "baseline")
+///         .write("screenshots/report.html")?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Gallery<'a> {
+    report: &'a BatchReport,
+    title: String,
+    baselines: Option<(PathBuf, PathBuf)>,
+    #[cfg(feature = "image")]
+    diff_options: DiffOptions,
+}
+
+/// A capture compared with its baseline.
+struct Comparison {
+    baseline: PathBuf,
+    /// Percentage of pixels changed and the diff image as a data URI.
+    diff: Option<(f64, String)>,
+    error: Option<String>,
+}
+
+impl<'a> Gallery<'a> {
+    /// Create a gallery of `report`.
+    pub fn new(report: &'a BatchReport) -> Self {
+        Self {
+            report,
+            title: DEFAULT_TITLE.into(),
+            baselines: None,
+            #[cfg(feature = "image")]
+            diff_options: DiffOptions::default(),
+        }
+    }
+
+    /// Heading and page title (default: "Screenshot report").
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Compare each capture under `output_dir` with the file at the same
+    /// path under `baseline_dir`, where one exists.
+    pub fn baselines(
+        mut self,
+        output_dir: impl Into<PathBuf>,
+        baseline_dir: impl Into<PathBuf>,
+    ) -> Self {
+        self.baselines = Some((output_dir.into(), baseline_dir.into()));
+        self
+    }
+
+    /// Options for diffing captures against their baselines.
+    #[cfg(feature = "image")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+    pub fn diff_options(mut self, options: DiffOptions) -> Self {
+        self.diff_options = options;
+        self
+    }
+
+    /// Write the report to `path`, linking captures relative to it.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let dir = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new(""));
+        if !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.render(dir))?;
+        Ok(())
+    }
+
+    /// Render the report as HTML, linking captures relative to `dir`.
+    pub fn render(&self, dir: impl AsRef<Path>) -> String {
+        let dir = dir.as_ref();
+        let report = self.report;
+        let mut html = String::new();
+
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+             <title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<header>\n\
+             <h1>{title}</h1>\n<p class=\"summary\">{summary} &middot; generated {generated}</p>\n\
+             <div class=\"filters\">\n\
+             <input type=\"search\" id=\"filter\" placeholder=\"Filter by URL\">\n\
+             <select id=\"status\">\n<option value=\"\">All ({total})</option>\n\
+             <option value=\"succeeded\">Captured ({succeeded})</option>\n\
+             <option value=\"skipped\">Skipped ({skipped})</option>\n\
+             <option value=\"failed\">Failed ({failed})</option>\n",
+            title = escape(&self.title),
+            summary = format_args!(
+                "{} captured, {} skipped, {} failed in {:.1}s",
+                report.succeeded(),
+                report.skipped(),
+                report.failed(),
+                report.elapsed.as_secs_f64()
+            ),
+            generated = Utc::now().format("%Y-%m-%d %H:%M:%S UTC"),
+            total = report.items.len(),
+            succeeded = report.succeeded(),
+            skipped = report.skipped(),
+            failed = report.failed(),
+        );
+
+        let mut items = String::new();
+        let mut changed = 0;
+        for item in &report.items {
+            let comparison = self.compare(item);
+            let is_changed = comparison
+                .as_ref()
+                .and_then(|comparison| comparison.diff.as_ref())
+                .is_some_and(|(percentage, _)| *percentage > 0.0);
+            if is_changed {
+                changed += 1;
+            }
+            render_item(&mut items, item, comparison.as_ref(), is_changed, dir);
+        }
+
+        if self.baselines.is_some() && cfg!(feature = "image") {
+            let _ = writeln!(
+                html,
+                "<option value=\"changed\">Changed ({})</option>",
+                changed
+            );
+        }
+        let _ = write!(
+            html,
+            "</select>\n<span id=\"count\"></span>\n</div>\n</header>\n<main>\n{}</main>\n\
+             <script>{}</script>\n</body>\n</html>\n",
+            items, SCRIPT
+        );
+        html
+    }
+
+    /// Compare `item` with its baseline, if it has one.
+    fn compare(&self, item: &BatchItemReport) -> Option<Comparison> {
+        let (output_dir, baseline_dir) = self.baselines.as_ref()?;
+        let output = item.output.as_ref()?;
+        let baseline = baseline_dir.join(output.strip_prefix(output_dir).ok()?);
+        if !baseline.is_file() {
+            return None;
+        }
+
+        let (diff, error) = match item.status {
+            BatchItemStatus::Failed => (None, None),
+            _ => self.diff(&baseline, output),
+        };
+        Some(Comparison {
+            baseline,
+            diff,
+            error,
+        })
+    }
+
+    /// The percentage of pixels changed from `baseline` to `current` and
+    /// the diff image as a data URI, or the error diffing them.
+    #[cfg(feature = "image")]
+    fn diff(&self, baseline: &Path, current: &Path) -> (Option<(f64, String)>, Option<String>) {
+        let diff = fs::read(baseline)
+            .map_err(crate::Error::from)
+            .and_then(|baseline| Ok((baseline, fs::read(current)?)))
+            .and_then(|(baseline, current)| diff::compare(&baseline, &current, &self.diff_options))
+            .and_then(|result| Ok((result.percentage(), result.image_png()?)));
+        match diff {
+            Ok((percentage, png)) => (
+                Some((
+                    percentage,
+                    format!("data:image/png;base64,{}", BASE64.encode(png)),
+                )),
+                None,
+            ),
+            Err(e) => (None, Some(e.to_string())),
+        }
+    }
+
+    #[cfg(not(feature = "image"))]
+    fn diff(&self, _baseline: &Path, _current: &Path) -> (Option<(f64, String)>, Option<String>) {
+        (None, None)
+    }
+}
+
+impl BatchReport {
+    /// Write the report to `path` as a browsable HTML [`Gallery`].
+    pub fn write_html(&self, path: impl AsRef<Path>) -> Result<()> {
+        Gallery::new(self).write(path)
+    }
+}
+
+fn render_item(
+    html: &mut String,
+    item: &BatchItemReport,
+    comparison: Option<&Comparison>,
+    changed: bool,
+    dir: &Path,
+) {
+    let status = match item.status {
+        BatchItemStatus::Succeeded => "succeeded",
+        BatchItemStatus::Skipped => "skipped",
+        BatchItemStatus::Failed => "failed",
+    };
+    // A failed item's file, if any, is left over from an earlier run.
+    let output = item
+        .output
+        .as_deref()
+        .filter(|output| item.status != BatchItemStatus::Failed && output.is_file());
+    let image = match (output, &item.stored_url) {
+        _ if item.status == BatchItemStatus::Failed => None,
+        (Some(output), _) => Some(link(output, dir)),
+        (_, Some(stored_url)) => Some(stored_url.clone()),
+        _ => None,
+    };
+
+    let _ = writeln!(
+        html,
+        "<article class=\"item {status}{changed}\" data-url=\"{url}\">",
+        changed = if changed { " changed" } else { "" },
+        url = escape(item.url.as_str()),
+    );
+    match &image {
+        Some(image) => {
+            let thumbnail = output.and_then(thumbnail);
+            let _ = writeln!(
+                html,
+                "<a class=\"thumb\" href=\"{}\"><img src=\"{}\" loading=\"lazy\" alt=\"\"></a>",
+                escape(image),
+                escape(thumbnail.as_deref().unwrap_or(image))
+            );
+        }
+        None => html.push_str("<div class=\"thumb empty\">No image</div>\n"),
+    }
+
+    let _ = write!(
+        html,
+        "<div class=\"details\">\n<h2><a href=\"{0}\">{0}</a></h2>\n<dl>\n\
+         <dt>Status</dt><dd class=\"status\">{1}</dd>\n",
+        escape(item.url.as_str()),
+        match item.status {
+            BatchItemStatus::Succeeded => "Captured",
+            BatchItemStatus::Skipped => "Skipped (captured by a previous run)",
+            BatchItemStatus::Failed => "Failed",
+        }
+    );
+    if let Some(output) = &item.output {
+        let _ = writeln!(
+            html,
+            "<dt>File</dt><dd>{}</dd>",
+            escape(&output.display().to_string())
+        );
+    }
+    let dimensions = match (item.width, item.height) {
+        (Some(width), Some(height)) => Some((width, height)),
+        _ => item
+            .output
+            .as_ref()
+            .and_then(|output| read_dimensions(output)),
+    };
+    if let Some((width, height)) = dimensions {
+        let _ = writeln!(
+            html,
+            "<dt>Dimensions</dt><dd>{} &times; {}</dd>",
+            width, height
+        );
+    }
+    if let Some(bytes) = item.bytes {
+        let _ = writeln!(html, "<dt>Size</dt><dd>{}</dd>", format_size(bytes));
+    }
+    if item.status != BatchItemStatus::Skipped {
+        let _ = write!(html, "<dt>Time</dt><dd>{:.2}s", item.duration.as_secs_f64());
+        if item.attempts > 1 {
+            let _ = write!(html, " ({} attempts)", item.attempts);
+        }
+        html.push_str("</dd>\n");
+    }
+    html.push_str("</dl>\n");
+    if let Some(error) = &item.error {
+        let _ = writeln!(html, "<p class=\"error\">{}</p>", escape(error));
+    }
+
+    if let Some(comparison) = comparison {
+        let summary = match (&comparison.diff, &comparison.error) {
+            (Some((percentage, _)), _) if *percentage > 0.0 => {
+                format!(
+                    "Changed: {:.2}% of pixels differ from the baseline",
+                    percentage
+                )
+            }
+            (Some(_), _) => "Unchanged from the baseline".to_string(),
+            (None, Some(_)) => "Could not compare with the baseline".to_string(),
+            (None, None) => "Compare with the baseline".to_string(),
+        };
+        let _ = write!(
+            html,
+            "<details class=\"compare\"{open}>\n<summary>{summary}</summary>\n<div class=\"sides\">\n",
+            open = if changed { " open" } else { "" },
+        );
+        figure(html, &link(&comparison.baseline, dir), "Baseline");
+        if let Some(image) = &image {
+            figure(html, image, "Current");
+        }
+        if let Some((_, diff)) = &comparison.diff {
+            figure(html, diff, "Diff");
+        }
+        html.push_str("</div>\n");
+        if let Some(error) = &comparison.error {
+            let _ = writeln!(html, "<p class=\"error\">{}</p>", escape(error));
+        }
+        html.push_str("</details>\n");
+    }
+    html.push_str("</div>\n</article>\n");
+}
+
+/// A thumbnail of the top of the image at `path`, as much as the report
+/// shows, as a data URI.
+#[cfg(feature = "image")]
+fn thumbnail(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    let image = imaging::decode(&bytes, ImageFormat::detect(&bytes)?).ok()?;
+    let (width, height) = THUMBNAIL_SIZE;
+    // Crop before scaling so long pages aren't scaled whole.
+    let visible = u64::from(image.width()) * u64::from(height) / u64::from(width);
+    let visible = u32::try_from(visible).unwrap_or(u32::MAX).max(1);
+    let top = image.crop_imm(0, 0, image.width(), image.height().min(visible));
+    let thumbnail = imaging::thumbnail(&top, width, height);
+    let jpeg = imaging::encode(&thumbnail, ImageFormat::Jpeg, Some(THUMBNAIL_QUALITY)).ok()?;
+    Some(format!("data:image/jpeg;base64,{}", BASE64.encode(jpeg)))
+}
+
+#[cfg(not(feature = "image"))]
+fn thumbnail(_path: &Path) -> Option<String> {
+    None
+}
+
+/// The dimensions of the image at `path`, read from its header.
+fn read_dimensions(path: &Path) -> Option<(u32, u32)> {
+    let mut file = File::open(path).ok()?;
+    let mut header = Vec::new();
+    let mut limit = HEADER_BYTES;
+    loop {
+        let remaining = (limit - header.len()) as u64;
+        (&mut file).take(remaining).read_to_end(&mut header).ok()?;
+        if let Some(dimensions) = image_dimensions(&header) {
+            return Some(dimensions);
+        }
+        if header.len() < limit || limit >= MAX_HEADER_BYTES {
+            return None;
+        }
+        limit *= 4;
+    }
+}
+
+fn figure(html: &mut String, src: &str, caption: &str) {
+    let _ = writeln!(
+        html,
+        "<figure><a href=\"{0}\"><img src=\"{0}\" loading=\"lazy\" alt=\"{1}\"></a>\
+         <figcaption>{1}</figcaption></figure>",
+        escape(src),
+        caption
+    );
+}
+
+/// A URL for `path` relative to `dir`.
+fn link(path: &Path, dir: &Path) -> String {
+    let absolute = |path: &Path| -> Vec<String> {
+        let path = std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf());
+        let mut parts: Vec<String> = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+                Component::ParentDir => {
+                    parts.pop();
+                }
+                _ => {}
+            }
+        }
+        parts
+    };
+
+    let (target, base) = (absolute(path), absolute(dir));
+    let common = target.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut segments: Vec<String> = vec!["..".into(); base.len() - common];
+    segments.extend(target[common..].iter().map(|part| percent_encode(part)));
+    segments.join("/")
+}
+
+fn percent_encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+    encoded
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+const STYLE: &str = "
+body { margin: 0; font: 14px/1.4 system-ui, sans-serif; color: #1f2328; background: #f6f8fa; }
+header { position: sticky; top: 0; z-index: 1; padding: 12px 24px; background: #fff; border-bottom: 1px solid #d0d7de; }
+h1 { margin: 0 0 4px; font-size: 20px; }
+.summary { margin: 0 0 8px; color: #59636e; }
+.filters { display: flex; gap: 8px; align-items: center; }
+.filters input { flex: 0 1 360px; padding: 4px 8px; }
+#count { color: #59636e; }
+main { padding: 16px 24px; display: grid; gap: 12px; }
+.item { display: flex; gap: 16px; padding: 12px; background: #fff; border: 1px solid #d0d7de; border-left: 4px solid #1a7f37; border-radius: 6px; }
+.item[hidden] { display: none; }
+.item.skipped { border-left-color: #8c959f; }
+.item.failed { border-left-color: #cf222e; }
+.item.changed { border-left-color: #bf8700; }
+.thumb { flex: 0 0 240px; height: 160px; overflow: hidden; border: 1px solid #d0d7de; background: #f6f8fa; }
+.thumb img { width: 100%; }
+.thumb.empty { display: flex; align-items: center; justify-content: center; color: #8c959f; }
+.details { flex: 1; min-width: 0; }
+h2 { margin: 0 0 8px; font-size: 15px; overflow-wrap: anywhere; }
+dl { display: grid; grid-template-columns: max-content 1fr; gap: 2px 12px; margin: 0; }
+dt { color: #59636e; }
+dd { margin: 0; overflow-wrap: anywhere; }
+.error { color: #cf222e; white-space: pre-wrap; }
+.compare { margin-top: 8px; }
+.compare summary { cursor: pointer; }
+.sides { display: grid; grid-template-columns: repeat(3, 1fr); gap: 8px; margin-top: 8px; }
+figure { margin: 0; }
+figure img { width: 100%; border: 1px solid #d0d7de; }
+figcaption { color: #59636e; text-align: center; }
+";
+
+const SCRIPT: &str = "
+const filter = document.getElementById('filter');
+const status = document.getElementById('status');
+const count = document.getElementById('count');
+const items = Array.from(document.querySelectorAll('.item'));
+function update() {
+  const text = filter.value.toLowerCase();
+  let shown = 0;
+  for (const item of items) {
+    const visible = item.dataset.url.toLowerCase().includes(text)
+      && (!status.value || item.classList.contains(status.value));
+    item.hidden = !visible;
+    if (visible) shown++;
+  }
+  count.textContent = shown + ' of ' + items.length + ' shown';
+}
+filter.addEventListener('input', update);
+status.addEventListener('change', update);
+update();
+";
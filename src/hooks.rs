@@ -0,0 +1,117 @@
+//! Request and response hooks.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::header::HeaderMap;
+
+use crate::error::Result;
+use crate::types::{ScreenshotRequest, ScreenshotResponse};
+
+/// Middleware invoked around every screenshot request.
+///
+/// Hooks are registered with [`PxshotBuilder::hook`](crate::PxshotBuilder::hook)
+/// and work with both the async and blocking clients. Several hooks can be
+/// stacked: `before_request` runs in registration order and `after_response`
+/// in reverse order, so the first hook registered is the outermost.
+///
+/// Both methods have empty default implementations.
+///
+/// # Example
+///
+/// ```
+/// use pxshot::header::{HeaderMap, HeaderValue};
+/// use pxshot::{Hook, Pxshot, ResponseMeta, ScreenshotRequest, ScreenshotResponse};
+///
+/// struct TenantHeader(&'static str);
+///
+/// impl Hook for TenantHeader {
+///     fn before_request(&self, _request: &mut ScreenshotRequest, headers: &mut HeaderMap) {
+///         headers.insert("x-tenant-id", HeaderValue::from_static(self.0));
+///     }
+///
+///     fn after_response(&self, meta: &ResponseMeta, result: &pxshot::Result<ScreenshotResponse>) {
+///         println!(
+///             "{} -> {:?} in {:?} ({})",
+///             meta.request.url,
+///             meta.status,
+///             meta.latency,
+///             if result.is_ok() { "ok" } else { "error" }
+///         );
+///     }
+/// }
+///
+/// # fn main() -> pxshot::Result<()> {
+/// let client = Pxshot::builder("px_your_api_key")
+///     .hook(TenantHeader("acme"))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Hook: Send + Sync {
+    /// Called before a screenshot request is sent.
+    ///
+    /// The hook may modify the request and add headers. Headers added here are
+    /// sent with every attempt of the request; the `Authorization` header is
+    /// managed by the client and is not included.
+    fn before_request(&self, request: &mut ScreenshotRequest, headers: &mut HeaderMap) {
+        let _ = (request, headers);
+    }
+
+    /// Called once the screenshot request has completed, successfully or not.
+    fn after_response(&self, meta: &ResponseMeta, result: &Result<ScreenshotResponse>) {
+        let _ = (meta, result);
+    }
+}
+
+/// Information about a completed screenshot request.
+#[derive(Debug, Clone)]
+pub struct ResponseMeta {
+    /// The request as sent, after all `before_request` hooks ran.
+    pub request: ScreenshotRequest,
+
+    /// HTTP status of the final response, if one was received.
+    pub status: Option<u16>,
+
+    /// Headers of the final response (empty if none was received).
+    pub headers: HeaderMap,
+
+    /// Number of HTTP attempts made, including rate-limit retries.
+    pub attempts: u32,
+
+    /// Total time taken, including time spent waiting on the rate limiter.
+    pub latency: Duration,
+}
+
+/// Stack of hooks registered on a client.
+#[derive(Clone, Default)]
+pub(crate) struct Hooks(Vec<Arc<dyn Hook>>);
+
+impl Hooks {
+    pub(crate) fn push(&mut self, hook: Arc<dyn Hook>) {
+        self.0.push(hook);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn before_request(&self, request: &mut ScreenshotRequest, headers: &mut HeaderMap) {
+        for hook in &self.0 {
+            hook.before_request(request, headers);
+        }
+    }
+
+    pub(crate) fn after_response(&self, meta: &ResponseMeta, result: &Result<ScreenshotResponse>) {
+        for hook in self.0.iter().rev() {
+            hook.after_response(meta, result);
+        }
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks").field("len", &self.0.len()).finish()
+    }
+}
@@ -0,0 +1,276 @@
+//! Decoding, resizing and re-encoding screenshots.
+//!
+//! Requires the `image` feature. Decoded images are [`DynamicImage`]s from
+//! the [`image`](https://docs.rs/image) crate, re-exported as
+//! [`pxshot::image`](crate::image).
+//!
+//! # Example
+//!
+//! ```no_run
+//! use pxshot::imaging;
+//! use pxshot::{ImageFormat, Pxshot, ScreenshotRequest};
+//!
+//! #[tokio::main]
+//! async fn main() -> pxshot::Result<()> {
+//!     let client = Pxshot::new("px_your_api_key");
+//!     let response = client
+//!         .screenshot(ScreenshotRequest::builder().url("https://example.com").build()?)
+//!         .await?;
+//!
+//!     let image = response.decode()?;
+//!     println!("{}x{}", image.width(), image.height());
+//!
+//!     for width in [320, 640] {
+//!         let thumbnail = imaging::thumbnail(&image, width, width);
+//!         let jpeg = imaging::encode(&thumbnail, ImageFormat::Jpeg, Some(75))?;
+//!         std::fs::write(format!("thumb-{}.jpg", width), jpeg)?;
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageReader};
+
+use crate::error::{Error, Result};
+use crate::types::{ImageFormat, ScreenshotResponse};
+
+/// JPEG quality used when none is given, matching the API default.
+const DEFAULT_JPEG_QUALITY: u8 = 80;
+
+impl ScreenshotResponse {
+    /// Decode the screenshot, detecting its format from the image data.
+    ///
+    /// Fails with [`Error::Image`] for stored screenshots, which carry no
+    /// image data, or if the data cannot be decoded.
+    #[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+    pub fn decode(&self) -> Result<DynamicImage> {
+        let bytes = self.image_bytes()?;
+        let format = ImageFormat::detect(bytes)
+            .ok_or_else(|| Error::Image("unrecognized image format".into()))?;
+        decode(bytes, format)
+    }
+
+    /// Decode the screenshot as `format`, e.g. the format that was requested.
+    #[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+    pub fn decode_as(&self, format: ImageFormat) -> Result<DynamicImage> {
+        decode(self.image_bytes()?, format)
+    }
+
+    /// Decode the screenshot and scale it down to fit within `max_width` by
+    /// `max_height`. See [`thumbnail`].
+    #[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+    pub fn thumbnail(&self, max_width: u32, max_height: u32) -> Result<DynamicImage> {
+        Ok(thumbnail(&self.decode()?, max_width, max_height))
+    }
+
+    /// Decode the screenshot and encode it again as `format`.
+    ///
+    /// See [`encode`]; WebP is encoded losslessly and takes no `quality`.
+    #[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+    pub fn reencode(&self, format: ImageFormat, quality: Option<u8>) -> Result<Vec<u8>> {
+        encode(&self.decode()?, format, quality)
+    }
+
+    fn image_bytes(&self) -> Result<&[u8]> {
+        self.bytes().ok_or_else(|| {
+            Error::Image("stored screenshots have no image data; download the stored URL".into())
+        })
+    }
+}
+
+/// Decode image data in the given format.
+pub fn decode(bytes: &[u8], format: ImageFormat) -> Result<DynamicImage> {
+    let mut reader = ImageReader::new(Cursor::new(bytes));
+    reader.set_format(image_format(format));
+    reader
+        .decode()
+        .map_err(|e| Error::Image(format!("failed to decode {}: {}", format.as_str(), e)))
+}
+
+/// Scale `image` down to fit within `max_width` by `max_height`, keeping
+/// its aspect ratio.
+///
+/// Images that already fit are returned unchanged rather than enlarged.
+pub fn thumbnail(image: &DynamicImage, max_width: u32, max_height: u32) -> DynamicImage {
+    if image.width() <= max_width && image.height() <= max_height {
+        return image.clone();
+    }
+    image.resize(max_width.max(1), max_height.max(1), FilterType::Lanczos3)
+}
+
+/// Scale and crop `image` to exactly `width` by `height`, keeping its
+/// aspect ratio and anchoring the crop at the top of the page.
+///
+/// Useful for uniform preview tiles, where the top of the page is usually
+/// the most recognizable part.
+pub fn thumbnail_fill(image: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+    let (width, height) = (width.max(1), height.max(1));
+    let scale = f64::max(
+        f64::from(width) / f64::from(image.width()),
+        f64::from(height) / f64::from(image.height()),
+    );
+    let scaled_width = ((f64::from(image.width()) * scale).round() as u32).max(width);
+    let scaled_height = ((f64::from(image.height()) * scale).round() as u32).max(height);
+
+    let scaled = image.resize_exact(scaled_width, scaled_height, FilterType::Lanczos3);
+    let x = (scaled_width - width) / 2;
+    scaled.crop_imm(x, 0, width, height)
+}
+
+/// Encode `image` as `format`.
+///
+/// `quality` (1-100) applies to JPEG and defaults to 80, and is ignored for
+/// PNG. WebP images are always encoded losslessly: passing a `quality` for
+/// WebP fails with [`Error::Image`] rather than silently producing a larger
+/// file than expected. Transparency is dropped when encoding JPEG.
+pub fn encode(image: &DynamicImage, format: ImageFormat, quality: Option<u8>) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+
+    let result = match format {
+        ImageFormat::Png => image.write_with_encoder(PngEncoder::new(&mut out)),
+        ImageFormat::Jpeg => {
+            let quality = quality.unwrap_or(DEFAULT_JPEG_QUALITY).clamp(1, 100);
+            DynamicImage::ImageRgb8(image.to_rgb8())
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut out, quality))
+        }
+        ImageFormat::Webp => {
+            if let Some(quality) = quality {
+                return Err(Error::Image(format!(
+                    "WebP is always encoded losslessly; quality {} is not supported",
+                    quality
+                )));
+            }
+            let image = if image.color().has_alpha() {
+                DynamicImage::ImageRgba8(image.to_rgba8())
+            } else {
+                DynamicImage::ImageRgb8(image.to_rgb8())
+            };
+            image.write_with_encoder(WebPEncoder::new_lossless(&mut out))
+        }
+    };
+
+    result.map_err(|e| Error::Image(format!("failed to encode {}: {}", format.as_str(), e)))?;
+    Ok(out)
+}
+
+fn image_format(format: ImageFormat) -> image::ImageFormat {
+    match format {
+        ImageFormat::Png => image::ImageFormat::Png,
+        ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+        ImageFormat::Webp => image::ImageFormat::WebP,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{Rgba, RgbaImage};
+
+    /// A noisy image, so lossy encoding has something to lose.
+    fn noisy(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            let value = (x.wrapping_mul(7919) ^ y.wrapping_mul(104_729)) as u8;
+            Rgba([value, value.wrapping_mul(3), 255 - value, 255])
+        }))
+    }
+
+    #[test]
+    fn encodes_and_decodes_every_format() {
+        let image = noisy(32, 24);
+        for format in [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::Webp] {
+            let bytes = encode(&image, format, None).unwrap();
+            assert_eq!(ImageFormat::detect(&bytes), Some(format));
+            let decoded = decode(&bytes, format).unwrap();
+            assert_eq!(
+                (decoded.width(), decoded.height()),
+                (32, 24),
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn lossless_formats_round_trip_exactly() {
+        let image = noisy(16, 16);
+        for format in [ImageFormat::Png, ImageFormat::Webp] {
+            let decoded = decode(&encode(&image, format, None).unwrap(), format).unwrap();
+            assert_eq!(decoded.to_rgba8(), image.to_rgba8(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn jpeg_quality_changes_the_output() {
+        let image = noisy(64, 64);
+        let low = encode(&image, ImageFormat::Jpeg, Some(10)).unwrap();
+        let high = encode(&image, ImageFormat::Jpeg, Some(95)).unwrap();
+        assert!(low.len() < high.len(), "{} >= {}", low.len(), high.len());
+
+        // Out-of-range qualities are clamped rather than rejected.
+        assert!(encode(&image, ImageFormat::Jpeg, Some(0)).is_ok());
+        assert!(encode(&image, ImageFormat::Jpeg, Some(255)).is_ok());
+    }
+
+    #[test]
+    fn webp_rejects_a_quality() {
+        let error = encode(&noisy(4, 4), ImageFormat::Webp, Some(80))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("losslessly"), "{}", error);
+
+        assert!(encode(&noisy(4, 4), ImageFormat::Png, Some(80)).is_ok());
+    }
+
+    #[test]
+    fn decode_reports_the_format() {
+        let error = decode(b"not a png", ImageFormat::Png)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("failed to decode png"), "{}", error);
+    }
+
+    #[test]
+    fn thumbnail_fits_without_enlarging() {
+        let image = noisy(200, 100);
+        let small = thumbnail(&image, 50, 50);
+        assert_eq!((small.width(), small.height()), (50, 25));
+
+        let same = thumbnail(&image, 400, 400);
+        assert_eq!((same.width(), same.height()), (200, 100));
+    }
+
+    #[test]
+    fn thumbnail_fill_has_the_exact_size() {
+        let image = noisy(200, 100);
+        for (width, height) in [(50, 50), (300, 20), (10, 200), (0, 0)] {
+            let tile = thumbnail_fill(&image, width, height);
+            assert_eq!(
+                (tile.width(), tile.height()),
+                (width.max(1), height.max(1)),
+                "{}x{}",
+                width,
+                height
+            );
+        }
+    }
+
+    #[test]
+    fn stored_responses_cannot_be_decoded() {
+        let response = ScreenshotResponse::Stored(crate::types::StoredScreenshot {
+            url: "https://cdn.example.com/a.png".into(),
+            expires_at: chrono::Utc::now(),
+            width: 1,
+            height: 1,
+            size_bytes: 1,
+        });
+        assert!(response.decode().is_err());
+        assert!(response.reencode(ImageFormat::Png, None).is_err());
+    }
+}
@@ -8,6 +8,25 @@
 //! - **Strongly typed**: Full type safety with serde serialization
 //! - **Builder pattern**: Ergonomic request construction
 //! - **Optional blocking client**: Enable with the `blocking` feature
+//! - **Client-side rate limiting**: Pace requests to match your plan limits
+//! - **Budget guard**: Cap screenshots or bytes per billing period
+//! - **Tracing**: Enable the `tracing` feature to instrument requests with spans
+//! - **Hooks**: Add headers, record metrics or audit requests with middleware
+//! - **Metrics**: Enable the `metrics` feature to record counters and histograms
+//! - **Testable**: Depend on [`ScreenshotClient`] and use [`testing::MockClient`] in tests
+//! - **Presets**: Load named capture settings from JSON or TOML and apply them to any URL
+//! - **Output templates**: Save captures to templated paths with JSON metadata sidecars
+//! - **Image processing**: Enable the `image` feature to decode, resize and re-encode screenshots
+//! - **Visual diffing**: Compare screenshots against a baseline with the `image` feature
+//! - **Perceptual similarity**: SSIM scores and perceptual hashes for fuzzy matching and deduplication
+//! - **Change monitoring**: Enable the `monitor` feature to capture pages on a schedule and get notified of changes
+//! - **Visual regression tests**: Assert captures match approved baselines with `assert_screenshot!`
+//! - **SSRF protection**: Reject internal and metadata-service URLs with a [`UrlPolicy`]
+//! - **Batch capture**: Enable the `batch` feature to run resumable jobs from TOML, JSON or CSV manifests
+//! - **Sitemap capture**: Enable the `sitemap` feature to capture every page listed in a sitemap
+//! - **Site crawling**: Enable the `crawl` feature to discover and capture the pages of a site
+//! - **HTML reports**: Review batch and crawl results in a static gallery with baseline diffs
+//! - **Command-line tool**: Enable the `cli` feature to build the `pxshot` binary
 //!
 //! ## Quick Start
 //!
@@ -95,6 +114,118 @@
 //! }
 //! ```
 //!
+//! ## Rate Limiting
+//!
+//! Attach a [`RateLimiter`] to pace requests instead of hitting `429` errors.
+//! Callers over the limit wait in a queue, and the limiter adjusts itself to
+//! the rate-limit headers returned by the API:
+//!
+//! ```no_run
+//! use pxshot::{Pxshot, RateLimiter};
+//!
+//! # fn main() -> pxshot::Result<()> {
+//! let limiter = RateLimiter::builder()
+//!     .per_minute(60)
+//!     .max_concurrent(4)
+//!     .build()?;
+//!
+//! let client = Pxshot::builder("px_your_api_key")
+//!     .rate_limiter(limiter)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Budgets
+//!
+//! A [`BudgetGuard`] refuses new captures with [`Error::BudgetExceeded`] once a
+//! per-period screenshot or byte budget is reached, and can alert you as usage
+//! approaches it:
+//!
+//! ```no_run
+//! use pxshot::{BudgetGuard, Pxshot};
+//!
+//! # fn main() -> pxshot::Result<()> {
+//! let budget = BudgetGuard::builder()
+//!     .max_screenshots(10_000)
+//!     .alert_at(0.8)
+//!     .alert_at(0.95)
+//!     .on_alert(|alert| eprintln!("{} at {:.0}%", alert.resource, alert.threshold * 100.0))
+//!     .build()?;
+//!
+//! let client = Pxshot::builder("px_your_api_key").budget(budget).build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Tracing
+//!
+//! With the `tracing` feature enabled, `screenshot`, `usage` and each HTTP
+//! attempt (including rate-limit retries) are wrapped in spans recording the
+//! target host, format, `full_page`, status, bytes, attempt number and latency.
+//! The API key and target URL paths, queries and credentials are never
+//! recorded. Enable [`PxshotBuilder::propagate_trace_context`] to also send a
+//! W3C `traceparent` header with each request.
+//!
+//! ## Metrics
+//!
+//! With the `metrics` feature enabled, the SDK records the following through
+//! the [`metrics`](https://docs.rs/metrics) facade, so any installed exporter
+//! (Prometheus, OpenMetrics, ...) picks them up:
+//!
+//! - `pxshot_screenshot_requests_total` (counter): labels `outcome`, `status`, `format`, `host`
+//! - `pxshot_screenshot_duration_seconds` (histogram): labels `outcome`, `format`
+//! - `pxshot_downloaded_bytes_total` (counter): labels `format`, `host`
+//! - `pxshot_retries_total` (counter)
+//! - `pxshot_rate_limiter_wait_seconds` (histogram)
+//! - `pxshot_cache_hits_total` (counter): requests answered from a [`Cassette`]
+//!
+//! ## Testing
+//!
+//! Write code against the [`ScreenshotClient`] trait, which [`Pxshot`]
+//! implements, and substitute a scripted [`testing::MockClient`] in unit
+//! tests:
+//!
+//! ```
+//! use pxshot::testing::MockClient;
+//! use pxshot::{ScreenshotClient, ScreenshotRequest, ScreenshotResponse};
+//!
+//! async fn thumbnail(client: &impl ScreenshotClient, url: &str) -> pxshot::Result<Vec<u8>> {
+//!     let request = ScreenshotRequest::builder().url(url).width(320).build()?;
+//!     Ok(client.screenshot(request).await?.into_bytes().unwrap_or_default())
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> pxshot::Result<()> {
+//! let mock = MockClient::new();
+//! mock.push_screenshot(ScreenshotResponse::Bytes(vec![1, 2, 3]));
+//!
+//! assert_eq!(thumbnail(&mock, "https://example.com").await?, vec![1, 2, 3]);
+//! assert_eq!(mock.last_request().unwrap().width, Some(320));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! To run screenshot-dependent tests in CI without an API key, record the
+//! real API once with a [`Cassette`] and replay it afterwards:
+//!
+//! ```no_run
+//! use pxshot::{Cassette, Pxshot};
+//!
+//! # fn main() -> pxshot::Result<()> {
+//! let api_key = std::env::var("PXSHOT_API_KEY").unwrap_or_default();
+//! let client = Pxshot::builder(api_key)
+//!     .cassette(Cassette::auto("tests/cassettes/homepage.json")?)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! For integration tests, the `test-server` feature adds
+//! `testing::FakeServer`, a local fake of the API that serves placeholder
+//! images in the real wire format and can inject latency, `429`s and server
+//! errors.
+//!
 //! ## Usage Statistics
 //!
 //! Check your API usage:
@@ -110,23 +241,101 @@
 //!     println!("Screenshots this period: {}", usage.screenshots);
 //!     println!("Bytes used: {}", usage.bytes);
 //!
+//!     // Project end-of-period consumption from the last 30 days
+//!     let history = client.usage_history(pxshot::UsageRange::last_days(30)).await?;
+//!     println!("Projected screenshots: {}", history.project(&usage).screenshots);
+//!
 //!     Ok(())
 //! }
 //! ```
 
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+#[cfg(feature = "batch")]
+mod batch;
+mod budget;
+mod cassette;
 mod client;
+#[cfg(feature = "crawl")]
+mod crawl;
 mod error;
+#[cfg(feature = "batch")]
+mod gallery;
+mod hooks;
+mod output;
+mod policy;
+mod presets;
+mod rate_limit;
+#[cfg(feature = "crawl")]
+mod robots;
+#[cfg(feature = "sitemap")]
+mod sitemap;
+mod telemetry;
+mod traits;
 mod types;
+mod urls;
+mod usage;
 
-pub use client::Pxshot;
+#[cfg(feature = "batch")]
+#[cfg_attr(docsrs, doc(cfg(feature = "batch")))]
+pub use batch::{
+    Batch, BatchBuilder, BatchItemReport, BatchItemStatus, BatchReport, Manifest, ManifestItem,
+};
+pub use budget::{BudgetAlert, BudgetGuard, BudgetGuardBuilder, BudgetResource, BudgetStatus};
+pub use cassette::{Cassette, CassetteMode};
+pub use client::{Pxshot, PxshotBuilder};
+#[cfg(feature = "crawl")]
+#[cfg_attr(docsrs, doc(cfg(feature = "crawl")))]
+pub use crawl::{CrawledPage, Crawler, CrawlerBuilder};
 pub use error::{Error, Result};
+#[cfg(feature = "batch")]
+#[cfg_attr(docsrs, doc(cfg(feature = "batch")))]
+pub use gallery::Gallery;
+pub use hooks::{Hook, ResponseMeta};
+pub use output::{CaptureMetadata, CaptureWriter, CaptureWriterBuilder, OutputTemplate, SavedCapture};
+pub use policy::{UrlPolicy, UrlPolicyBuilder};
+pub use presets::Presets;
+pub use rate_limit::{RateLimiter, RateLimiterBuilder};
+#[cfg(feature = "sitemap")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sitemap")))]
+pub use sitemap::{Sitemap, SitemapBuilder, SitemapEntry};
+pub use traits::ScreenshotClient;
 pub use types::{
-    ImageFormat, ScreenshotRequest, ScreenshotRequestBuilder, ScreenshotResponse,
-    StoredScreenshot, Usage, WaitUntil,
+    HasUrl, ImageFormat, NoUrl, ScreenshotOptions, ScreenshotRequest, ScreenshotRequestBuilder,
+    ScreenshotResponse, StoredScreenshot, Usage, WaitUntil,
+};
+/// HTTP header types used by [`Hook`].
+pub use reqwest::header;
+pub use url::Url;
+pub use urls::{is_private_address, is_private_ip, normalize_url, IntoUrl};
+pub use usage::{
+    ApiKeyUsage, DeliveryUsage, FormatUsage, Granularity, UsageHistory, UsagePoint,
+    UsageProjection, UsageRange, UsageRate, UsageTotals,
 };
 
+#[cfg(feature = "image")]
+#[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+pub mod diff;
+#[cfg(feature = "image")]
+#[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+pub mod imaging;
+#[cfg(feature = "monitor")]
+#[cfg_attr(docsrs, doc(cfg(feature = "monitor")))]
+pub mod monitor;
+#[cfg(feature = "image")]
+#[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+pub mod similarity;
+#[cfg(feature = "image")]
+#[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+pub mod snapshot;
+pub mod testing;
+
+/// The [`image`](https://docs.rs/image) crate, for working with decoded
+/// screenshots.
+#[cfg(feature = "image")]
+#[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+pub use image;
+
 /// Blocking client module (requires `blocking` feature).
 #[cfg(feature = "blocking")]
 #[cfg_attr(docsrs, doc(cfg(feature = "blocking")))]
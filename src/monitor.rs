@@ -0,0 +1,908 @@
+//! Scheduled change monitoring for pages.
+//!
+//! Requires the `monitor` feature. A [`Monitor`] captures each [`Watch`] on
+//! its [`Schedule`], keeps the captures in a history directory and compares
+//! every capture with the previous one. When the change exceeds the
+//! threshold, it calls the registered callbacks and posts a [`ChangeEvent`]
+//! as JSON to the configured webhooks.
+//!
+//! History is stored as `<history_dir>/<watch name>/<timestamp>.<ext>`, with
+//! a highlighted `<timestamp>.diff.png` next to captures that changed. The
+//! monitor picks up where it left off after a restart.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use chrono::NaiveTime;
+//! use pxshot::monitor::{Monitor, Schedule, Watch};
+//! use pxshot::{Pxshot, ScreenshotRequest};
+//!
+//! #[tokio::main]
+//! async fn main() -> pxshot::Result<()> {
+//!     let client = Pxshot::new("px_your_api_key");
+//!
+//!     let monitor = Monitor::builder("monitor-history")
+//!         .watch(Watch::new(
+//!             "acme-pricing",
+//!             ScreenshotRequest::builder()
+//!                 .url("https://acme.example/pricing")
+//!                 .full_page(true)
+//!                 .build()?,
+//!             Schedule::daily(NaiveTime::from_hms_opt(6, 0, 0).unwrap()),
+//!         ))
+//!         .watch(
+//!             Watch::new(
+//!                 "acme-home",
+//!                 ScreenshotRequest::builder().url("https://acme.example").build()?,
+//!                 Schedule::every(Duration::from_secs(6 * 3600)),
+//!             )
+//!             .threshold(2.0),
+//!         )
+//!         .threshold(0.5)
+//!         .on_change(|event| {
+//!             println!("{} changed by {:.2}%", event.name, event.percentage);
+//!         })
+//!         .webhook("https://hooks.example.com/pxshot")
+//!         .build()?;
+//!
+//!     monitor.run(&client).await
+//! }
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDateTime, NaiveTime, SubsecRound, TimeDelta, Utc};
+use serde::Serialize;
+use url::Url;
+
+use crate::diff::{self, DiffOptions, Region};
+use crate::error::{Error, Result};
+use crate::traits::ScreenshotClient;
+use crate::types::{ImageFormat, ScreenshotRequest};
+use crate::urls::IntoUrl;
+
+/// Format of the timestamps history files are named by.
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+const DIFF_SUFFIX: &str = ".diff.png";
+const DEFAULT_WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+type ChangeCallback = Arc<dyn Fn(&ChangeEvent) + Send + Sync>;
+type ErrorCallback = Arc<dyn Fn(&str, &Error) + Send + Sync>;
+
+/// When a [`Watch`] is captured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// At a fixed interval after the previous capture.
+    Every(Duration),
+    /// Once a day at a time of day, in UTC.
+    Daily(NaiveTime),
+}
+
+impl Schedule {
+    /// Capture at a fixed interval.
+    pub fn every(interval: Duration) -> Self {
+        Self::Every(interval)
+    }
+
+    /// Capture once a day at `time`, in UTC.
+    pub fn daily(time: NaiveTime) -> Self {
+        Self::Daily(time)
+    }
+
+    /// The first capture time after a capture at `previous`.
+    pub fn next_after(&self, previous: DateTime<Utc>) -> DateTime<Utc> {
+        match *self {
+            Self::Every(interval) => TimeDelta::from_std(interval)
+                .ok()
+                .and_then(|interval| previous.checked_add_signed(interval))
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            Self::Daily(time) => {
+                let today = previous.date_naive().and_time(time).and_utc();
+                if today > previous {
+                    today
+                } else {
+                    today + TimeDelta::days(1)
+                }
+            }
+        }
+    }
+}
+
+/// A page watched by a [`Monitor`].
+#[derive(Debug, Clone)]
+pub struct Watch {
+    name: String,
+    request: ScreenshotRequest,
+    schedule: Schedule,
+    threshold: Option<f64>,
+}
+
+impl Watch {
+    /// Watch the page captured by `request` on `schedule`.
+    ///
+    /// `name` identifies the watch in events and names its history
+    /// directory. It may contain letters, digits, `-`, `_` and `.`.
+    pub fn new(name: impl Into<String>, request: ScreenshotRequest, schedule: Schedule) -> Self {
+        Self {
+            name: name.into(),
+            request,
+            schedule,
+            threshold: None,
+        }
+    }
+
+    /// Percentage of pixels (0-100) that must change to notify, overriding
+    /// the monitor's threshold.
+    pub fn threshold(mut self, percentage: f64) -> Self {
+        self.threshold = Some(percentage);
+        self
+    }
+
+    /// The name of the watch.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The request captured.
+    pub fn request(&self) -> &ScreenshotRequest {
+        &self.request
+    }
+
+    /// When the page is captured.
+    pub fn schedule(&self) -> Schedule {
+        self.schedule
+    }
+}
+
+/// A change that exceeded a watch's threshold.
+///
+/// This is the JSON body posted to webhooks.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeEvent {
+    /// Name of the watch.
+    pub name: String,
+    /// The captured URL.
+    pub url: Url,
+    /// When the page was captured.
+    pub captured_at: DateTime<Utc>,
+    /// When the previous capture was taken.
+    pub previous_at: DateTime<Utc>,
+    /// Percentage of pixels that changed (0-100).
+    pub percentage: f64,
+    /// Number of pixels that changed.
+    pub changed_pixels: u64,
+    /// Whether the page size changed.
+    pub dimensions_changed: bool,
+    /// Bounding boxes of the changed areas.
+    pub regions: Vec<Region>,
+    /// Path of the capture in the history directory.
+    pub capture: PathBuf,
+    /// Path of the previous capture.
+    pub previous: PathBuf,
+    /// Path of the highlighted diff image.
+    pub diff: PathBuf,
+}
+
+/// The result of capturing a watch once.
+#[derive(Debug, Clone)]
+pub struct CheckReport {
+    /// Name of the watch.
+    pub name: String,
+    /// When the page was captured.
+    pub captured_at: DateTime<Utc>,
+    /// Path of the capture in the history directory.
+    pub capture: PathBuf,
+    /// Percentage of pixels that changed since the previous capture, or
+    /// `None` for the first capture.
+    pub percentage: Option<f64>,
+    /// The change, if it exceeded the threshold.
+    pub change: Option<ChangeEvent>,
+}
+
+/// Captures pages on a schedule and reports visual changes.
+///
+/// See the [module documentation](self) for an example.
+pub struct Monitor {
+    history_dir: PathBuf,
+    watches: Vec<Watch>,
+    threshold: f64,
+    options: DiffOptions,
+    keep: Option<usize>,
+    on_change: Vec<ChangeCallback>,
+    on_error: Vec<ErrorCallback>,
+    webhooks: Vec<Url>,
+    http: reqwest::Client,
+}
+
+impl fmt::Debug for Monitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Monitor")
+            .field("history_dir", &self.history_dir)
+            .field("watches", &self.watches)
+            .field("threshold", &self.threshold)
+            .field("options", &self.options)
+            .field("keep", &self.keep)
+            .field("on_change", &self.on_change.len())
+            .field("on_error", &self.on_error.len())
+            .field("webhooks", &self.webhooks)
+            .finish()
+    }
+}
+
+impl Monitor {
+    /// Create a new builder storing history in `history_dir`.
+    pub fn builder(history_dir: impl Into<PathBuf>) -> MonitorBuilder {
+        MonitorBuilder {
+            history_dir: history_dir.into(),
+            watches: Vec::new(),
+            threshold: 0.0,
+            options: DiffOptions::default(),
+            keep: None,
+            on_change: Vec::new(),
+            on_error: Vec::new(),
+            webhooks: Vec::new(),
+            webhook_timeout: DEFAULT_WEBHOOK_TIMEOUT,
+        }
+    }
+
+    /// The watches, in the order they were added.
+    pub fn watches(&self) -> &[Watch] {
+        &self.watches
+    }
+
+    /// Capture every watch on its schedule until the future is dropped.
+    ///
+    /// Watches without history are captured right away; others continue
+    /// their schedule from their latest capture. Failed checks are reported
+    /// to the [`on_error`](MonitorBuilder::on_error) callbacks and retried
+    /// at the next scheduled time. Only failures to read the history
+    /// directory at startup are returned.
+    pub async fn run<C>(&self, client: &C) -> Result<()>
+    where
+        C: ScreenshotClient + ?Sized,
+    {
+        if self.watches.is_empty() {
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let mut due = Vec::with_capacity(self.watches.len());
+        for watch in &self.watches {
+            let latest = self.history(watch)?.pop();
+            due.push(latest.map_or(now, |(at, _)| watch.schedule.next_after(at)));
+        }
+
+        loop {
+            let (index, at) = due
+                .iter()
+                .copied()
+                .enumerate()
+                .min_by_key(|&(_, at)| at)
+                .expect("monitor has watches");
+            if let Ok(wait) = (at - Utc::now()).to_std() {
+                tokio::time::sleep(wait).await;
+            }
+
+            let watch = &self.watches[index];
+            if let Err(e) = self.check_watch(client, watch).await {
+                for callback in &self.on_error {
+                    callback(&watch.name, &e);
+                }
+            }
+            // Skip runs missed while capturing or while the process was asleep.
+            due[index] = watch.schedule.next_after(at.max(Utc::now()));
+        }
+    }
+
+    /// Capture every watch once, now, regardless of its schedule.
+    ///
+    /// Useful when an external scheduler such as cron runs the monitor.
+    /// Returns one result per watch, in order.
+    pub async fn run_once<C>(&self, client: &C) -> Vec<Result<CheckReport>>
+    where
+        C: ScreenshotClient + ?Sized,
+    {
+        let mut reports = Vec::with_capacity(self.watches.len());
+        for watch in &self.watches {
+            reports.push(self.check_watch(client, watch).await);
+        }
+        reports
+    }
+
+    /// Capture the watch `name` now and compare it with its previous capture.
+    pub async fn check<C>(&self, client: &C, name: &str) -> Result<CheckReport>
+    where
+        C: ScreenshotClient + ?Sized,
+    {
+        let watch = self
+            .watches
+            .iter()
+            .find(|watch| watch.name == name)
+            .ok_or_else(|| Error::Config(format!("unknown watch `{}`", name)))?;
+        self.check_watch(client, watch).await
+    }
+
+    /// Captures of the watch `name` in the history directory, oldest first.
+    pub fn history_of(&self, name: &str) -> Result<Vec<(DateTime<Utc>, PathBuf)>> {
+        let watch = self
+            .watches
+            .iter()
+            .find(|watch| watch.name == name)
+            .ok_or_else(|| Error::Config(format!("unknown watch `{}`", name)))?;
+        self.history(watch)
+    }
+
+    async fn check_watch<C>(&self, client: &C, watch: &Watch) -> Result<CheckReport>
+    where
+        C: ScreenshotClient + ?Sized,
+    {
+        let previous = self.history(watch)?.pop();
+
+        let response = client.screenshot(watch.request.clone()).await?;
+        // Match the precision of the timestamps in history file names.
+        let captured_at = Utc::now().trunc_subsecs(3);
+        let bytes = response.bytes().ok_or_else(|| {
+            Error::Image("cannot monitor a stored screenshot; capture without `store`".into())
+        })?;
+        let format = ImageFormat::detect(bytes)
+            .ok_or_else(|| Error::Image("unrecognized image format".into()))?;
+
+        let dir = self.history_dir.join(&watch.name);
+        fs::create_dir_all(&dir)?;
+        let stamp = captured_at.format(TIMESTAMP_FORMAT).to_string();
+        let capture = dir.join(format!("{}.{}", stamp, format.as_str()));
+        fs::write(&capture, bytes)?;
+
+        let mut report = CheckReport {
+            name: watch.name.clone(),
+            captured_at,
+            capture: capture.clone(),
+            percentage: None,
+            change: None,
+        };
+
+        if let Some((previous_at, previous)) = previous {
+            let result = diff::compare(&fs::read(&previous)?, bytes, &self.options)?;
+            report.percentage = Some(result.percentage());
+
+            let threshold = watch.threshold.unwrap_or(self.threshold);
+            let changed = result.dimensions_differ
+                || (result.changed_pixels > 0 && result.percentage() > threshold);
+            if changed {
+                let diff = dir.join(format!("{}{}", stamp, DIFF_SUFFIX));
+                result.save_image(&diff)?;
+
+                let event = ChangeEvent {
+                    name: watch.name.clone(),
+                    url: watch.request.url.clone(),
+                    captured_at,
+                    previous_at,
+                    percentage: result.percentage(),
+                    changed_pixels: result.changed_pixels,
+                    dimensions_changed: result.dimensions_differ,
+                    regions: result.regions,
+                    capture,
+                    previous,
+                    diff,
+                };
+                // Keep the previous capture as the latest until the change
+                // is delivered, so a failed webhook reports it again at the
+                // next check instead of losing it. Webhooks that did receive
+                // it receive it again too.
+                if let Err(e) = self.notify(&event).await {
+                    remove_if_exists(&event.capture)?;
+                    remove_if_exists(&event.diff)?;
+                    return Err(e);
+                }
+                for callback in &self.on_change {
+                    callback(&event);
+                }
+                report.change = Some(event);
+            }
+        }
+
+        self.prune(watch)?;
+        Ok(report)
+    }
+
+    /// Post `event` to every webhook, returning the first failure.
+    ///
+    /// A failing webhook doesn't keep the event from the ones after it.
+    async fn notify(&self, event: &ChangeEvent) -> Result<()> {
+        let mut result = Ok(());
+        for webhook in &self.webhooks {
+            let sent = async {
+                self.http
+                    .post(webhook.clone())
+                    .json(event)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok(())
+            };
+            if let Err(e) = sent.await {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+
+    /// Captures of `watch`, oldest first.
+    fn history(&self, watch: &Watch) -> Result<Vec<(DateTime<Utc>, PathBuf)>> {
+        let entries = match fs::read_dir(self.history_dir.join(&watch.name)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut captures = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if name.ends_with(DIFF_SUFFIX) {
+                continue;
+            }
+            let Some((stamp, _)) = name.rsplit_once('.') else {
+                continue;
+            };
+            if let Ok(at) = NaiveDateTime::parse_from_str(stamp, TIMESTAMP_FORMAT) {
+                captures.push((at.and_utc(), path));
+            }
+        }
+        captures.sort();
+        Ok(captures)
+    }
+
+    /// Remove all but the latest `keep` captures of `watch`.
+    fn prune(&self, watch: &Watch) -> Result<()> {
+        let Some(keep) = self.keep else {
+            return Ok(());
+        };
+        let captures = self.history(watch)?;
+        let excess = captures.len().saturating_sub(keep);
+        for (_, path) in &captures[..excess] {
+            fs::remove_file(path)?;
+            remove_if_exists(&path.with_extension("diff.png"))?;
+        }
+        Ok(())
+    }
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Builder for [`Monitor`].
+pub struct MonitorBuilder {
+    history_dir: PathBuf,
+    watches: Vec<Watch>,
+    threshold: f64,
+    options: DiffOptions,
+    keep: Option<usize>,
+    on_change: Vec<ChangeCallback>,
+    on_error: Vec<ErrorCallback>,
+    webhooks: Vec<Result<Url>>,
+    webhook_timeout: Duration,
+}
+
+impl fmt::Debug for MonitorBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MonitorBuilder")
+            .field("history_dir", &self.history_dir)
+            .field("watches", &self.watches)
+            .field("threshold", &self.threshold)
+            .field("options", &self.options)
+            .field("keep", &self.keep)
+            .field("on_change", &self.on_change.len())
+            .field("on_error", &self.on_error.len())
+            .field("webhooks", &self.webhooks.len())
+            .field("webhook_timeout", &self.webhook_timeout)
+            .finish()
+    }
+}
+
+impl MonitorBuilder {
+    /// Add a page to watch.
+    pub fn watch(mut self, watch: Watch) -> Self {
+        self.watches.push(watch);
+        self
+    }
+
+    /// Add several pages to watch.
+    pub fn watches(mut self, watches: impl IntoIterator<Item = Watch>) -> Self {
+        self.watches.extend(watches);
+        self
+    }
+
+    /// Percentage of pixels (0-100) that must change to notify (default: 0,
+    /// i.e. any change). Watches may override it.
+    pub fn threshold(mut self, percentage: f64) -> Self {
+        self.threshold = percentage;
+        self
+    }
+
+    /// Set the options for comparing captures, e.g. to ignore a region that
+    /// changes on every load.
+    pub fn diff_options(mut self, options: DiffOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Keep only the latest `captures` captures of each watch (default: keep
+    /// all).
+    pub fn keep(mut self, captures: usize) -> Self {
+        self.keep = Some(captures);
+        self
+    }
+
+    /// Call `callback` for every change that exceeds the threshold.
+    pub fn on_change(mut self, callback: impl Fn(&ChangeEvent) + Send + Sync + 'static) -> Self {
+        self.on_change.push(Arc::new(callback));
+        self
+    }
+
+    /// Call `callback` with the watch name when a scheduled check fails in
+    /// [`Monitor::run`].
+    pub fn on_error(mut self, callback: impl Fn(&str, &Error) + Send + Sync + 'static) -> Self {
+        self.on_error.push(Arc::new(callback));
+        self
+    }
+
+    /// Post every change that exceeds the threshold as a JSON
+    /// [`ChangeEvent`] to `url`.
+    ///
+    /// May be called several times. Every change is posted to each webhook
+    /// in turn. If any of them fails or responds with an error status, the
+    /// check fails and the new capture is discarded, so the change is posted
+    /// again, to every webhook, at the next check. Delivery is therefore
+    /// at-least-once: receivers can tell repeats apart by the event's `name`
+    /// and `previous_at`.
+    pub fn webhook(mut self, url: impl IntoUrl) -> Self {
+        self.webhooks.push(url.into_url());
+        self
+    }
+
+    /// Maximum time to wait for each webhook request (default: 30 seconds).
+    pub fn webhook_timeout(mut self, timeout: Duration) -> Self {
+        self.webhook_timeout = timeout;
+        self
+    }
+
+    /// Build the monitor.
+    pub fn build(self) -> Result<Monitor> {
+        for (index, watch) in self.watches.iter().enumerate() {
+            let valid = !watch.name.is_empty()
+                && !watch.name.starts_with('.')
+                && watch
+                    .name
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || b"-_.".contains(&byte));
+            if !valid {
+                return Err(Error::Config(format!(
+                    "invalid watch name `{}`; use letters, digits, `-`, `_` and `.`",
+                    watch.name
+                )));
+            }
+            if self.watches[..index]
+                .iter()
+                .any(|other| other.name == watch.name)
+            {
+                return Err(Error::Config(format!("duplicate watch `{}`", watch.name)));
+            }
+            if matches!(watch.schedule, Schedule::Every(interval) if interval.is_zero()) {
+                return Err(Error::Config(format!(
+                    "watch `{}` has a zero interval",
+                    watch.name
+                )));
+            }
+            if let Some(threshold) = watch.threshold {
+                check_threshold(threshold)?;
+            }
+            watch.request.validate()?;
+        }
+        check_threshold(self.threshold)?;
+        if self.keep == Some(0) {
+            return Err(Error::Config(
+                "monitor must keep at least one capture".into(),
+            ));
+        }
+
+        let webhooks = self
+            .webhooks
+            .into_iter()
+            .map(|url| {
+                let url = url.map_err(|e| Error::Config(format!("invalid webhook URL: {}", e)))?;
+                if matches!(url.scheme(), "http" | "https") {
+                    Ok(url)
+                } else {
+                    Err(Error::Config(format!(
+                        "invalid webhook URL `{}`; expected http or https",
+                        url
+                    )))
+                }
+            })
+            .collect::<Result<_>>()?;
+        let http = reqwest::Client::builder()
+            .timeout(self.webhook_timeout)
+            .build()
+            .map_err(|e| Error::Config(format!("failed to build HTTP client: {}", e)))?;
+
+        Ok(Monitor {
+            history_dir: self.history_dir,
+            watches: self.watches,
+            threshold: self.threshold,
+            options: self.options,
+            keep: self.keep,
+            on_change: self.on_change,
+            on_error: self.on_error,
+            webhooks,
+            http,
+        })
+    }
+}
+
+fn check_threshold(threshold: f64) -> Result<()> {
+    if (0.0..=100.0).contains(&threshold) {
+        Ok(())
+    } else {
+        Err(Error::Config(format!(
+            "monitor threshold must be between 0 and 100, got {}",
+            threshold
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::TimeZone;
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    use crate::imaging;
+    use crate::testing::MockClient;
+    use crate::types::ScreenshotResponse;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 6, hour, minute, 0).unwrap()
+    }
+
+    /// An empty directory under the system temp directory for one test.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("pxshot-monitor-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn watch(name: &str) -> Watch {
+        Watch::new(
+            name,
+            ScreenshotRequest::builder()
+                .url("https://example.com")
+                .build()
+                .unwrap(),
+            Schedule::every(Duration::from_secs(3600)),
+        )
+    }
+
+    /// A white page, with a red block if `changed`.
+    fn page(changed: bool) -> Vec<u8> {
+        let image = RgbaImage::from_fn(40, 40, |x, y| {
+            if changed && (10..20).contains(&x) && (10..20).contains(&y) {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        });
+        imaging::encode(&DynamicImage::ImageRgba8(image), ImageFormat::Png, None).unwrap()
+    }
+
+    fn touch(path: PathBuf) -> PathBuf {
+        fs::write(&path, b"").unwrap();
+        path
+    }
+
+    /// Accept webhook posts on 127.0.0.1, answering `200 OK` and counting them.
+    fn webhook_server() -> (Url, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let url = Url::parse(&format!("http://{}/hook", listener.local_addr().unwrap())).unwrap();
+        let received = Arc::new(AtomicUsize::new(0));
+        let count = received.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                // Read the whole request before answering.
+                let mut request = Vec::new();
+                let mut buffer = [0; 4096];
+                while let Ok(len @ 1..) = stream.read(&mut buffer) {
+                    request.extend_from_slice(&buffer[..len]);
+                    let text = String::from_utf8_lossy(&request).to_ascii_lowercase();
+                    let Some(end) = text.find("\r\n\r\n") else {
+                        continue;
+                    };
+                    let length = text
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length:"))
+                        .and_then(|value| value.trim().parse::<usize>().ok())
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+                count.fetch_add(1, Ordering::SeqCst);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+            }
+        });
+        (url, received)
+    }
+
+    /// A URL nothing listens on.
+    fn closed_url() -> Url {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        Url::parse(&format!("http://{}/hook", listener.local_addr().unwrap())).unwrap()
+    }
+
+    #[test]
+    fn every_adds_the_interval() {
+        let schedule = Schedule::every(Duration::from_secs(90 * 60));
+        assert_eq!(schedule.next_after(at(6, 0)), at(7, 30));
+    }
+
+    #[test]
+    fn every_saturates_instead_of_overflowing() {
+        let schedule = Schedule::every(Duration::MAX);
+        assert_eq!(schedule.next_after(at(6, 0)), DateTime::<Utc>::MAX_UTC);
+
+        let schedule = Schedule::every(Duration::from_secs(3600));
+        assert_eq!(
+            schedule.next_after(DateTime::<Utc>::MAX_UTC),
+            DateTime::<Utc>::MAX_UTC
+        );
+    }
+
+    #[test]
+    fn daily_picks_the_next_occurrence() {
+        let schedule = Schedule::daily(NaiveTime::from_hms_opt(6, 0, 0).unwrap());
+        assert_eq!(schedule.next_after(at(5, 59)), at(6, 0));
+        assert_eq!(
+            schedule.next_after(at(6, 0)),
+            at(6, 0) + TimeDelta::days(1),
+            "a capture at the scheduled time moves on to the next day"
+        );
+        assert_eq!(
+            schedule.next_after(at(23, 0)),
+            at(6, 0) + TimeDelta::days(1)
+        );
+    }
+
+    #[test]
+    fn history_lists_captures_oldest_first() {
+        let dir = scratch_dir("history");
+        let monitor = Monitor::builder(&dir).watch(watch("home")).build().unwrap();
+        assert!(
+            monitor.history_of("home").unwrap().is_empty(),
+            "no directory yet"
+        );
+
+        let watch_dir = dir.join("home");
+        fs::create_dir_all(&watch_dir).unwrap();
+        let newer = touch(watch_dir.join("20240506T070000.000Z.jpeg"));
+        touch(watch_dir.join("20240506T070000.000Z.diff.png"));
+        let older = touch(watch_dir.join("20240506T060000.500Z.png"));
+        touch(watch_dir.join("notes.txt"));
+        touch(watch_dir.join("20240506.png"));
+        touch(watch_dir.join("20240506T060000.000Z"));
+
+        let history = monitor.history_of("home").unwrap();
+        assert_eq!(
+            history,
+            [
+                (at(6, 0) + TimeDelta::milliseconds(500), older),
+                (at(7, 0), newer)
+            ]
+        );
+        assert!(monitor.history_of("other").is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn prune_keeps_the_latest_captures_and_their_diffs() {
+        let dir = scratch_dir("prune");
+        let monitor = Monitor::builder(&dir)
+            .watch(watch("home"))
+            .keep(2)
+            .build()
+            .unwrap();
+        let watch_dir = dir.join("home");
+        fs::create_dir_all(&watch_dir).unwrap();
+        for hour in 1..=4 {
+            touch(watch_dir.join(format!("20240506T0{}0000.000Z.png", hour)));
+            touch(watch_dir.join(format!("20240506T0{}0000.000Z.diff.png", hour)));
+        }
+
+        monitor.prune(&monitor.watches()[0]).unwrap();
+
+        let mut left: Vec<_> = fs::read_dir(&watch_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        assert_eq!(
+            left,
+            [
+                "20240506T030000.000Z.diff.png",
+                "20240506T030000.000Z.png",
+                "20240506T040000.000Z.diff.png",
+                "20240506T040000.000Z.png",
+            ]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_webhook_does_not_stop_the_others() {
+        let dir = scratch_dir("webhooks");
+        let (working, received) = webhook_server();
+        let monitor = Monitor::builder(&dir)
+            .watch(watch("home"))
+            .webhook(closed_url())
+            .webhook(working)
+            .build()
+            .unwrap();
+
+        let client = MockClient::new();
+        client.push_screenshot(ScreenshotResponse::Bytes(page(false)));
+        client.respond_with(|_| Ok(ScreenshotResponse::Bytes(page(true))));
+
+        let first = monitor.check(&client, "home").await.unwrap();
+        assert!(
+            first.change.is_none(),
+            "the first capture has nothing to compare"
+        );
+
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        assert!(monitor.check(&client, "home").await.is_err());
+        assert_eq!(
+            received.load(Ordering::SeqCst),
+            1,
+            "the working webhook still got the change"
+        );
+        assert_eq!(
+            monitor.history_of("home").unwrap().len(),
+            1,
+            "the undelivered capture is discarded"
+        );
+
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        assert!(monitor.check(&client, "home").await.is_err());
+        assert_eq!(
+            received.load(Ordering::SeqCst),
+            2,
+            "the change is delivered again"
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,885 @@
+//! File naming templates and sidecar metadata for saved screenshots.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::error::{Error, Result};
+use crate::hooks::ResponseMeta;
+use crate::traits::ScreenshotClient;
+use crate::types::{ImageFormat, ScreenshotRequest, ScreenshotResponse};
+use crate::urls::normalize_url;
+
+/// Longest file or directory name derived from a URL path segment.
+const MAX_SEGMENT_LEN: usize = 100;
+/// Number of hex digits in `{hash}` and `{url_hash}` without a length.
+const DEFAULT_HASH_LEN: usize = 16;
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+const DEFAULT_TIME_FORMAT: &str = "%H%M%S";
+
+/// A template for the paths screenshots are saved to.
+///
+/// Placeholders in braces are replaced with values from the request, the
+/// captured image and the capture time:
+///
+/// | Placeholder | Value |
+/// |---|---|
+/// | `{host}` | Host of the URL, with the port if not the default |
+/// | `{path}` | URL path and query as nested directories, e.g. `blog/launch` (`index` for `/`) |
+/// | `{path_slug}` | URL path as one name, e.g. `blog-launch` (`index` for `/`) |
+/// | `{url_hash}` | SHA-256 of the [normalized](crate::normalize_url) URL |
+/// | `{width}`, `{height}` | Dimensions of the image, or of the viewport if unknown |
+/// | `{ext}` | Extension of the image format, e.g. `png` |
+/// | `{date}` | Capture date (UTC), as `%Y-%m-%d` or `{date:FORMAT}` |
+/// | `{time}` | Capture time (UTC), as `%H%M%S` or `{time:FORMAT}` |
+/// | `{timestamp}` | Capture time in seconds since the Unix epoch |
+/// | `{hash}` | SHA-256 of the image |
+///
+/// Hashes are cut to 16 hex digits, or to the length given as in
+/// `{hash:8}`. Formats use [`chrono`'s syntax](chrono::format::strftime).
+/// Write `{{` and `}}` for literal braces. Values are made safe for file
+/// names; only `{path}` and formats containing `/` produce directories.
+///
+/// If the template has no `{ext}` and does not already end with the
+/// format's extension, one is appended.
+///
+/// # Example
+///
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use pxshot::{OutputTemplate, ScreenshotRequest};
+///
+/// # fn main() -> pxshot::Result<()> {
+/// let template: OutputTemplate = "{host}/{path_slug}-{width}x{height}".parse()?;
+/// let request = ScreenshotRequest::builder()
+///     .url("https://example.com/blog/launch")
+///     .width(1280)
+///     .height(720)
+///     .build()?;
+///
+/// let at = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+/// let path = template.render(&request, &[], at);
+/// assert_eq!(path.to_str(), Some("example.com/blog-launch-1280x720.png"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct OutputTemplate {
+    template: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Host,
+    Path,
+    PathSlug,
+    UrlHash(usize),
+    Width,
+    Height,
+    Ext,
+    Date(String),
+    Timestamp,
+    Hash(usize),
+}
+
+impl OutputTemplate {
+    /// Parse a template.
+    ///
+    /// Fails with [`Error::Config`] on unknown placeholders, unbalanced
+    /// braces, invalid formats or hash lengths, and absolute paths or `..`
+    /// components, which could write outside the output directory.
+    pub fn new(template: impl Into<String>) -> Result<Self> {
+        let template = template.into();
+        let invalid = |reason: String| {
+            Error::Config(format!(
+                "invalid output template `{}`: {}",
+                template, reason
+            ))
+        };
+
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(invalid("unmatched `}`".into())),
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(invalid("unclosed `{`".into())),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(parse_placeholder(&placeholder).map_err(invalid)?);
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        if parts.is_empty() {
+            return Err(invalid("the template is empty".into()));
+        }
+        // Placeholder values are sanitized, so only literals can make the
+        // path absolute or climb out of the output directory.
+        let skeleton: String = parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(text) => text.as_str(),
+                _ => "_",
+            })
+            .collect();
+        let escapes = Path::new(&skeleton)
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir));
+        if escapes {
+            return Err(invalid(
+                "paths must be relative and may not contain `..`".into(),
+            ));
+        }
+
+        Ok(Self { template, parts })
+    }
+
+    /// The template as written.
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Whether rendering needs the captured image rather than just the
+    /// request, i.e. the template contains `{hash}`.
+    #[cfg(feature = "batch")]
+    pub(crate) fn needs_image(&self) -> bool {
+        self.parts.iter().any(|part| matches!(part, Part::Hash(_)))
+    }
+
+    /// The relative path for a capture of `request` producing `image`.
+    ///
+    /// The image format is detected from `image`, falling back to the
+    /// request's format; `image` may be empty if no placeholder needs it.
+    pub fn render(
+        &self,
+        request: &ScreenshotRequest,
+        image: &[u8],
+        captured_at: DateTime<Utc>,
+    ) -> PathBuf {
+        let format = ImageFormat::detect(image).unwrap_or(request.format.unwrap_or_default());
+        let dimensions = image_dimensions(image);
+        let url = &request.url;
+
+        let mut path = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => path.push_str(text),
+                Part::Host => path.push_str(&sanitize(&host(url))),
+                Part::Path => path.push_str(&url_path(url).to_string_lossy()),
+                Part::PathSlug => path.push_str(&path_slug(url)),
+                Part::UrlHash(len) => {
+                    path.push_str(&sha256_hex(normalize_url(url).as_str().as_bytes())[..*len])
+                }
+                Part::Width => {
+                    let width = dimensions.map(|(width, _)| width).or(request.width);
+                    path.push_str(&width.map_or_else(|| "unknown".into(), |w| w.to_string()));
+                }
+                Part::Height => {
+                    let height = dimensions.map(|(_, height)| height).or(request.height);
+                    path.push_str(&height.map_or_else(|| "unknown".into(), |h| h.to_string()));
+                }
+                Part::Ext => path.push_str(format.as_str()),
+                Part::Date(format) => {
+                    let date = captured_at.format(format).to_string();
+                    let segments: Vec<String> = date.split('/').map(sanitize).collect();
+                    path.push_str(&segments.join("/"));
+                }
+                Part::Timestamp => path.push_str(&captured_at.timestamp().to_string()),
+                Part::Hash(len) => path.push_str(&sha256_hex(image)[..*len]),
+            }
+        }
+
+        let has_ext = self.parts.contains(&Part::Ext)
+            || Path::new(&path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| extension_matches(ext, format));
+        if !has_ext {
+            path.push('.');
+            path.push_str(format.as_str());
+        }
+        PathBuf::from(path)
+    }
+}
+
+impl FromStr for OutputTemplate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<String> for OutputTemplate {
+    type Error = String;
+
+    fn try_from(template: String) -> std::result::Result<Self, String> {
+        Self::new(template).map_err(|e| match e {
+            Error::Config(message) => message,
+            e => e.to_string(),
+        })
+    }
+}
+
+impl From<OutputTemplate> for String {
+    fn from(template: OutputTemplate) -> Self {
+        template.template
+    }
+}
+
+impl fmt::Display for OutputTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+fn parse_placeholder(placeholder: &str) -> std::result::Result<Part, String> {
+    let (name, argument) = match placeholder.split_once(':') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (placeholder, None),
+    };
+    let hash_len = |argument: Option<&str>| match argument {
+        None => Ok(DEFAULT_HASH_LEN),
+        Some(len) => len
+            .parse()
+            .ok()
+            .filter(|len| (1..=64).contains(len))
+            .ok_or_else(|| format!("hash length `{}` must be from 1 to 64", len)),
+    };
+    let date = |default: &str| {
+        let format = argument.unwrap_or(default);
+        if format.is_empty() || StrftimeItems::new(format).any(|item| item == Item::Error) {
+            return Err(format!("invalid date format `{}`", format));
+        }
+        Ok(Part::Date(format.to_string()))
+    };
+
+    let part = match name {
+        "date" => return date(DEFAULT_DATE_FORMAT),
+        "time" => return date(DEFAULT_TIME_FORMAT),
+        "hash" => return hash_len(argument).map(Part::Hash),
+        "url_hash" => return hash_len(argument).map(Part::UrlHash),
+        "host" => Part::Host,
+        "path" => Part::Path,
+        "path_slug" => Part::PathSlug,
+        "width" => Part::Width,
+        "height" => Part::Height,
+        "ext" => Part::Ext,
+        "timestamp" => Part::Timestamp,
+        _ => return Err(format!("unknown placeholder `{{{}}}`", placeholder)),
+    };
+    match argument {
+        Some(_) => Err(format!("`{{{}}}` takes no argument", name)),
+        None => Ok(part),
+    }
+}
+
+fn extension_matches(ext: &str, format: ImageFormat) -> bool {
+    ext.eq_ignore_ascii_case(format.as_str())
+        || (format == ImageFormat::Jpeg && ext.eq_ignore_ascii_case("jpg"))
+}
+
+fn host(url: &Url) -> String {
+    let host = url.host_str().unwrap_or("unknown");
+    match url.port() {
+        Some(port) => format!("{}_{}", host, port),
+        None => host.to_string(),
+    }
+}
+
+/// The URL path as a single file name, e.g. `blog-launch`.
+fn path_slug(url: &Url) -> String {
+    let mut slug = String::new();
+    for c in url.path().chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '_' | '.') {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "index".into()
+    } else {
+        sanitize(slug)
+    }
+}
+
+/// A relative file path, without extension, for the page at `url`.
+pub(crate) fn url_path(url: &Url) -> PathBuf {
+    let mut segments: Vec<String> = url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .filter(|segment| !segment.is_empty())
+        .map(sanitize)
+        .collect();
+    if segments.is_empty() || url.path().ends_with('/') {
+        segments.push("index".into());
+    }
+    if let Some(query) = url.query().filter(|query| !query.is_empty()) {
+        let last = segments.last_mut().expect("at least one segment");
+        *last = sanitize(&format!("{}_{}", last, query));
+    }
+    segments.iter().collect()
+}
+
+/// Replace characters that are unsafe or awkward in file names.
+pub(crate) fn sanitize(segment: &str) -> String {
+    let name: String = segment
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_SEGMENT_LEN)
+        .collect();
+    match name.as_str() {
+        "" | "." | ".." => "_".into(),
+        _ => name,
+    }
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Width and height of a PNG, JPEG or WebP image, read from its header.
+pub(crate) fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let u16_be = |at: usize| Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let u32_be = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let u24_le = |at: usize| {
+        let b = bytes.get(at..at + 3)?;
+        Some(u32::from(b[0]) | (u32::from(b[1]) << 8) | (u32::from(b[2]) << 16))
+    };
+
+    match ImageFormat::detect(bytes)? {
+        ImageFormat::Png => Some((u32_be(16)?, u32_be(20)?)),
+        ImageFormat::Jpeg => {
+            // Walk the segments to the start-of-frame marker.
+            let mut at = 2;
+            while at + 9 < bytes.len() {
+                if bytes[at] != 0xff {
+                    return None;
+                }
+                let marker = bytes[at + 1];
+                let is_frame =
+                    matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc);
+                if is_frame {
+                    return Some((u32::from(u16_be(at + 7)?), u32::from(u16_be(at + 5)?)));
+                }
+                at += 2 + usize::from(u16_be(at + 2)?);
+            }
+            None
+        }
+        ImageFormat::Webp => match bytes.get(12..16)? {
+            b"VP8X" => Some((u24_le(24)? + 1, u24_le(27)? + 1)),
+            b"VP8 " => {
+                let u14_le = |at: usize| {
+                    Some(u32::from(
+                        u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?) & 0x3fff,
+                    ))
+                };
+                Some((u14_le(26)?, u14_le(28)?))
+            }
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+                Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            _ => None,
+        },
+    }
+}
+
+/// Metadata about a saved screenshot, written next to it as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureMetadata {
+    /// File name of the image, relative to the metadata file.
+    pub image: String,
+
+    /// URL captured.
+    pub url: Url,
+
+    /// The request the screenshot was captured with.
+    pub request: ScreenshotRequest,
+
+    /// When the screenshot was captured.
+    pub captured_at: DateTime<Utc>,
+
+    /// Format of the image.
+    pub format: ImageFormat,
+
+    /// Width of the image in pixels, if it could be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+
+    /// Height of the image in pixels, if it could be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+
+    /// Size of the image in bytes.
+    pub size_bytes: u64,
+
+    /// SHA-256 of the image, as 64 hex digits.
+    pub sha256: String,
+
+    /// HTTP status of the API response, when saved from [`ResponseMeta`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+
+    /// Number of attempts made, when saved from [`ResponseMeta`] or by a
+    /// batch run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<u32>,
+
+    /// Time the API took in milliseconds, when saved from [`ResponseMeta`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+
+    /// Headers of the API response, when saved from [`ResponseMeta`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl CaptureMetadata {
+    pub(crate) fn new(
+        path: &Path,
+        request: &ScreenshotRequest,
+        image: &[u8],
+        captured_at: DateTime<Utc>,
+        meta: Option<&ResponseMeta>,
+    ) -> Self {
+        let dimensions = image_dimensions(image);
+        Self {
+            image: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            url: request.url.clone(),
+            request: request.clone(),
+            captured_at,
+            format: ImageFormat::detect(image).unwrap_or(request.format.unwrap_or_default()),
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+            size_bytes: image.len() as u64,
+            sha256: sha256_hex(image),
+            status: meta.and_then(|meta| meta.status),
+            attempts: meta.map(|meta| meta.attempts),
+            latency_ms: meta.map(|meta| meta.latency.as_millis().try_into().unwrap_or(u64::MAX)),
+            headers: meta
+                .map(|meta| {
+                    meta.headers
+                        .iter()
+                        .filter_map(|(name, value)| {
+                            Some((name.to_string(), value.to_str().ok()?.to_string()))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The path of the metadata file for the image at `image_path`.
+    pub(crate) fn sidecar_path(image_path: &Path) -> PathBuf {
+        let mut path = image_path.to_path_buf().into_os_string();
+        path.push(".json");
+        PathBuf::from(path)
+    }
+
+    pub(crate) fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).map_err(|e| Error::Io(e.into()))
+    }
+
+    /// Read a metadata file written by a [`CaptureWriter`].
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        serde_json::from_slice(&fs::read(path)?).map_err(|e| {
+            Error::Parse(format!(
+                "invalid capture metadata {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+}
+
+/// A screenshot saved by a [`CaptureWriter`].
+#[derive(Debug, Clone)]
+pub struct SavedCapture {
+    /// Path of the image.
+    pub path: PathBuf,
+
+    /// Path of the metadata file, if one was written.
+    pub metadata_path: Option<PathBuf>,
+
+    /// Metadata about the capture.
+    pub metadata: CaptureMetadata,
+}
+
+/// Saves screenshots to paths from an [`OutputTemplate`], each with a JSON
+/// sidecar of [`CaptureMetadata`].
+///
+/// The sidecar is named after the image with `.json` appended, e.g.
+/// `example.com/index.png.json`. Files are written atomically, and an
+/// existing file at the same path is replaced; include `{hash}`, `{date}`
+/// or `{time}` in the template to keep earlier captures.
+///
+/// # Example
+///
+/// ```no_run
+/// use pxshot::{CaptureWriter, Pxshot, ScreenshotRequest};
+///
+/// #[tokio::main]
+/// async fn main() -> pxshot::Result<()> {
+///     let client = Pxshot::new("px_your_api_key");
+///     let writer = CaptureWriter::builder("{host}/{date}/{path_slug}-{hash:8}")
+///         .dir("captures")
+///         .build()?;
+///
+///     let request = ScreenshotRequest::builder().url("https://example.com").build()?;
+///     let saved = writer.capture(&client, request).await?;
+///     println!("saved {} ({})", saved.path.display(), saved.metadata.sha256);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CaptureWriter {
+    template: OutputTemplate,
+    dir: PathBuf,
+    sidecars: bool,
+}
+
+impl CaptureWriter {
+    /// Create a new builder saving to paths from `template`.
+    pub fn builder(template: impl Into<String>) -> CaptureWriterBuilder {
+        CaptureWriterBuilder {
+            template: template.into(),
+            dir: PathBuf::new(),
+            sidecars: true,
+        }
+    }
+
+    /// The template of the paths screenshots are saved to.
+    pub fn template(&self) -> &OutputTemplate {
+        &self.template
+    }
+
+    /// Capture `request` with `client` and save the screenshot.
+    ///
+    /// The path and metadata are based on the request the client sends,
+    /// with its defaults applied.
+    pub async fn capture<C>(&self, client: &C, request: ScreenshotRequest) -> Result<SavedCapture>
+    where
+        C: ScreenshotClient + ?Sized,
+    {
+        let request = client.effective_request(&request);
+        let response = client.screenshot(request.clone()).await?;
+        self.save(&request, &response)
+    }
+
+    /// Save a screenshot captured for `request`.
+    ///
+    /// Pass the request as sent, e.g. from
+    /// [`ScreenshotClient::effective_request`], so the path and metadata
+    /// reflect any client defaults.
+    ///
+    /// Fails with [`Error::Config`] for stored screenshots, which have no
+    /// image data to save.
+    pub fn save(
+        &self,
+        request: &ScreenshotRequest,
+        response: &ScreenshotResponse,
+    ) -> Result<SavedCapture> {
+        self.write(request, response, None)
+    }
+
+    /// Save a screenshot along with the API response metadata passed to
+    /// [`Hook::after_response`](crate::Hook::after_response).
+    pub fn save_with_meta(
+        &self,
+        meta: &ResponseMeta,
+        response: &ScreenshotResponse,
+    ) -> Result<SavedCapture> {
+        self.write(&meta.request, response, Some(meta))
+    }
+
+    fn write(
+        &self,
+        request: &ScreenshotRequest,
+        response: &ScreenshotResponse,
+        meta: Option<&ResponseMeta>,
+    ) -> Result<SavedCapture> {
+        let image = response.bytes().ok_or_else(|| {
+            Error::Config("cannot save a stored screenshot; it has no image data".into())
+        })?;
+        let captured_at = Utc::now();
+        let path = self
+            .dir
+            .join(self.template.render(request, image, captured_at));
+        let metadata = CaptureMetadata::new(&path, request, image, captured_at, meta);
+
+        write_atomic(&path, image)?;
+        let metadata_path = if self.sidecars {
+            let metadata_path = CaptureMetadata::sidecar_path(&path);
+            write_atomic(&metadata_path, &metadata.to_json()?)?;
+            Some(metadata_path)
+        } else {
+            None
+        };
+
+        Ok(SavedCapture {
+            path,
+            metadata_path,
+            metadata,
+        })
+    }
+}
+
+/// Write to a temporary file first so a crash never leaves a truncated file.
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let mut partial = path.to_path_buf().into_os_string();
+    partial.push(".partial");
+    fs::write(&partial, contents)?;
+    fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// Builder for [`CaptureWriter`].
+#[derive(Debug)]
+pub struct CaptureWriterBuilder {
+    template: String,
+    dir: PathBuf,
+    sidecars: bool,
+}
+
+impl CaptureWriterBuilder {
+    /// Directory the template's paths are relative to (default: the
+    /// current directory).
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    /// Whether to write a JSON metadata file next to each image (default:
+    /// true).
+    pub fn sidecars(mut self, sidecars: bool) -> Self {
+        self.sidecars = sidecars;
+        self
+    }
+
+    /// Build the writer, parsing the template.
+    pub fn build(self) -> Result<CaptureWriter> {
+        Ok(CaptureWriter {
+            template: OutputTemplate::new(self.template)?,
+            dir: self.dir,
+            sidecars: self.sidecars,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn request(url: &str) -> ScreenshotRequest {
+        ScreenshotRequest::builder()
+            .url(url)
+            .width(1280)
+            .height(720)
+            .build()
+            .unwrap()
+    }
+
+    fn render(template: &str, url: &str, image: &[u8]) -> String {
+        let at = Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 45).unwrap();
+        OutputTemplate::new(template)
+            .unwrap()
+            .render(&request(url), image, at)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn error(template: &str) -> String {
+        match OutputTemplate::new(template) {
+            Err(Error::Config(message)) => message,
+            other => panic!(
+                "expected a config error for `{}`, got {:?}",
+                template, other
+            ),
+        }
+    }
+
+    #[test]
+    fn url_placeholders() {
+        let url = "https://example.com:8443/blog/launch?ref=home";
+        assert_eq!(render("{host}", url, &[]), "example.com_8443.png");
+        assert_eq!(render("{path}", url, &[]), "blog/launch_ref_home.png");
+        assert_eq!(render("{path_slug}", url, &[]), "blog-launch.png");
+        assert_eq!(render("{path}", "https://example.com/", &[]), "index.png");
+        assert_eq!(
+            render("{path_slug}", "https://example.com/", &[]),
+            "index.png"
+        );
+        assert_eq!(render("{url_hash:8}", url, &[]).len(), "12345678.png".len());
+    }
+
+    #[test]
+    fn brace_escapes() {
+        let url = "https://example.com/";
+        assert_eq!(render("{{host}}", url, &[]), "{host}.png");
+        assert_eq!(render("a{{{host}}}b", url, &[]), "a{example.com}b.png");
+        assert_eq!(OutputTemplate::new("{{x}}").unwrap().as_str(), "{{x}}");
+    }
+
+    #[test]
+    fn date_formats_make_directories() {
+        let url = "https://example.com/";
+        assert_eq!(
+            render("{date}/{host}", url, &[]),
+            "2024-05-01/example.com.png"
+        );
+        assert_eq!(
+            render("{date:%Y/%m}/{time}", url, &[]),
+            "2024/05/123045.png"
+        );
+        assert_eq!(render("{time:%H-%M}", url, &[]), "12-30.png");
+        assert_eq!(render("{timestamp}", url, &[]), "1714566645.png");
+        // Format output is sanitized like any other value.
+        assert_eq!(render("{date:/%Y/../%m}", url, &[]), "_/2024/_/05.png");
+    }
+
+    #[test]
+    fn hash_lengths() {
+        let url = "https://example.com/";
+        let full = sha256_hex(b"image");
+        assert_eq!(
+            render("{hash}", url, b"image"),
+            format!("{}.png", &full[..16])
+        );
+        assert_eq!(
+            render("{hash:1}", url, b"image"),
+            format!("{}.png", &full[..1])
+        );
+        assert_eq!(render("{hash:64}", url, b"image"), format!("{}.png", full));
+
+        assert!(error("{hash:0}").contains("must be from 1 to 64"));
+        assert!(error("{hash:65}").contains("must be from 1 to 64"));
+        assert!(error("{url_hash:x}").contains("must be from 1 to 64"));
+    }
+
+    #[test]
+    fn extension_is_added_when_missing() {
+        let url = "https://example.com/";
+        let jpeg = [0xff, 0xd8, 0xff, 0xe0];
+        assert_eq!(render("{host}", url, &[]), "example.com.png");
+        assert_eq!(render("{host}.{ext}", url, &jpeg), "example.com.jpeg");
+        assert_eq!(render("{ext}/{host}", url, &jpeg), "jpeg/example.com");
+        assert_eq!(render("shot.png", url, &[]), "shot.png");
+        assert_eq!(render("shot.PNG", url, &[]), "shot.PNG");
+        assert_eq!(render("shot.jpg", url, &jpeg), "shot.jpg");
+        assert_eq!(render("shot.png", url, &jpeg), "shot.png.jpeg");
+        assert_eq!(render("{host}.v1", url, &[]), "example.com.v1.png");
+    }
+
+    #[test]
+    fn dimensions_fall_back_to_the_viewport() {
+        let url = "https://example.com/";
+        assert_eq!(render("{width}x{height}", url, &[]), "1280x720.png");
+
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend_from_slice(&800u32.to_be_bytes());
+        png.extend_from_slice(&600u32.to_be_bytes());
+        assert_eq!(render("{width}x{height}", url, &png), "800x600.png");
+    }
+
+    #[test]
+    fn invalid_templates() {
+        assert!(error("").contains("empty"));
+        assert!(error("{host").contains("unclosed `{`"));
+        assert!(error("host}").contains("unmatched `}`"));
+        assert!(error("{nope}").contains("unknown placeholder `{nope}`"));
+        assert!(error("{host:x}").contains("takes no argument"));
+        assert!(error("{date:}").contains("invalid date format"));
+        assert!(error("{date:%Q}").contains("invalid date format"));
+    }
+
+    #[test]
+    fn paths_stay_inside_the_output_directory() {
+        for template in [
+            "/tmp/{host}",
+            "../{host}",
+            "{host}/../../etc/passwd",
+            "{date}/..",
+        ] {
+            assert!(
+                error(template).contains("must be relative"),
+                "accepted `{}`",
+                template
+            );
+        }
+        assert_eq!(
+            render("./{host}/..{path_slug}", "https://example.com/a", &[]),
+            "./example.com/..a.png"
+        );
+    }
+
+    #[test]
+    fn template_serializes_as_a_string() {
+        let template: OutputTemplate = serde_json::from_str("\"{host}/{path}\"").unwrap();
+        assert_eq!(template.as_str(), "{host}/{path}");
+        assert_eq!(
+            serde_json::to_string(&template).unwrap(),
+            "\"{host}/{path}\""
+        );
+        let error = serde_json::from_str::<OutputTemplate>("\"/{host}\"").unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("invalid output template `/{host}`"));
+    }
+}
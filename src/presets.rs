@@ -0,0 +1,184 @@
+//! Named screenshot presets.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::types::{ScreenshotOptions, ScreenshotRequest};
+use crate::urls::IntoUrl;
+
+/// A set of named [`ScreenshotOptions`].
+///
+/// Presets let a team define common capture settings such as `og-card` or
+/// `mobile-full` once, typically in a configuration file, and apply them to
+/// any URL. Preset files map each name to its options, using the same field
+/// names as [`ScreenshotRequest`]:
+///
+/// ```toml
+/// [og-card]
+/// width = 1200
+/// height = 630
+/// format = "png"
+///
+/// [mobile-full]
+/// width = 390
+/// device_scale_factor = 3
+/// full_page = true
+/// ```
+///
+/// JSON files are always supported; TOML files require the `toml` feature.
+///
+/// # Example
+///
+/// ```
+/// use pxshot::{Presets, ScreenshotOptions};
+///
+/// # fn main() -> pxshot::Result<()> {
+/// let presets = Presets::from_json(r#"{
+///     "og-card": { "width": 1200, "height": 630, "format": "png" }
+/// }"#)?;
+///
+/// let request = presets.request("og-card", "https://example.com/blog/launch")?;
+/// assert_eq!(request.width, Some(1200));
+///
+/// // Layer per-call overrides on top of the preset.
+/// let request = request.merge(&ScreenshotOptions {
+///     height: Some(675),
+///     ..Default::default()
+/// });
+/// assert_eq!(request.height, Some(675));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Presets {
+    presets: BTreeMap<String, ScreenshotOptions>,
+}
+
+impl Presets {
+    /// Create an empty set of presets.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read presets from a file, choosing the format from its extension
+    /// (`.json`, or `.toml` with the `toml` feature).
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+
+        let presets = match extension.as_deref() {
+            Some("json") => Self::from_json(&contents),
+            #[cfg(feature = "toml")]
+            Some("toml") => Self::from_toml(&contents),
+            #[cfg(not(feature = "toml"))]
+            Some("toml") => Err(Error::Config(
+                "TOML presets require the `toml` feature".into(),
+            )),
+            _ => Err(Error::Config(
+                "unsupported presets format; expected .json or .toml".into(),
+            )),
+        };
+        presets.map_err(|e| match e {
+            Error::Config(message) => Error::Config(format!("{}: {}", path.display(), message)),
+            e => e,
+        })
+    }
+
+    /// Parse presets from JSON.
+    pub fn from_json(contents: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(contents)
+            .map_err(|e| Error::Config(format!("invalid presets: {}", e)))?;
+        Self::from_value(value)
+    }
+
+    /// Parse presets from TOML.
+    #[cfg(feature = "toml")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "toml")))]
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        let value: Value = toml::from_str(contents)
+            .map_err(|e| Error::Config(format!("invalid presets: {}", e)))?;
+        Self::from_value(value)
+    }
+
+    fn from_value(value: Value) -> Result<Self> {
+        let Value::Object(presets) = value else {
+            return Err(Error::Config(
+                "invalid presets: expected a table of named presets".into(),
+            ));
+        };
+
+        let mut parsed = BTreeMap::new();
+        for (name, options) in presets {
+            let Value::Object(fields) = &options else {
+                return Err(Error::Config(format!(
+                    "preset `{}` must be a table of options",
+                    name
+                )));
+            };
+            // Reject misspelled options rather than silently ignoring them.
+            if let Some(key) = fields
+                .keys()
+                .find(|key| !ScreenshotOptions::FIELDS.contains(&key.as_str()))
+            {
+                return Err(Error::Config(format!(
+                    "unknown option `{}` in preset `{}`",
+                    key, name
+                )));
+            }
+            let options = serde_json::from_value(options)
+                .map_err(|e| Error::Config(format!("invalid preset `{}`: {}", name, e)))?;
+            parsed.insert(name, options);
+        }
+        Ok(Self { presets: parsed })
+    }
+
+    /// Add a preset, returning the preset it replaced.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        options: ScreenshotOptions,
+    ) -> Option<ScreenshotOptions> {
+        self.presets.insert(name.into(), options)
+    }
+
+    /// Get a preset by name.
+    pub fn get(&self, name: &str) -> Option<&ScreenshotOptions> {
+        self.presets.get(name)
+    }
+
+    /// Names of the presets, in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.presets.keys().map(String::as_str)
+    }
+
+    /// Number of presets.
+    pub fn len(&self) -> usize {
+        self.presets.len()
+    }
+
+    /// Whether there are no presets.
+    pub fn is_empty(&self) -> bool {
+        self.presets.is_empty()
+    }
+
+    /// Create a request for `url` using the preset `name`.
+    ///
+    /// Fails with [`Error::Config`] if there is no such preset, or with
+    /// [`Error::InvalidField`] if the URL cannot be parsed.
+    pub fn request(&self, name: &str, url: impl IntoUrl) -> Result<ScreenshotRequest> {
+        let options = self
+            .get(name)
+            .ok_or_else(|| Error::Config(format!("unknown preset `{}`", name)))?;
+        Ok(options.to_request(url.into_url()?))
+    }
+}
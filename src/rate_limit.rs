@@ -0,0 +1,631 @@
+//! Client-side rate limiting.
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use tokio::sync::Notify;
+
+use crate::error::{Error, Result};
+
+const RATE_LIMIT_LIMIT: &str = "x-ratelimit-limit";
+const RATE_LIMIT_REMAINING: &str = "x-ratelimit-remaining";
+const RATE_LIMIT_RESET: &str = "x-ratelimit-reset";
+
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Token-bucket rate limiter for API requests.
+///
+/// A limiter paces requests per second and/or per minute and caps the number
+/// of requests in flight. Callers that exceed the limits are queued until
+/// capacity is available instead of failing.
+///
+/// Cloning a limiter is cheap and every clone shares the same buckets, so one
+/// limiter can be attached to several clients (or cloned clients) to enforce
+/// a single plan-wide limit.
+///
+/// When the API reports rate-limit headers (`X-RateLimit-Limit`,
+/// `X-RateLimit-Remaining`, `X-RateLimit-Reset`, `Retry-After`), the limiter
+/// tunes itself to match. `X-RateLimit-Limit` is interpreted as requests per
+/// minute. Requests rejected with `429 Too Many Requests` are retried after
+/// the advertised delay, up to [`RateLimiterBuilder::max_retries`] times.
+/// Advertised delays are capped at 30 seconds so a bogus header cannot stall
+/// the limiter indefinitely.
+///
+/// # Example
+///
+/// ```
+/// use pxshot::{Pxshot, RateLimiter};
+///
+/// # fn main() -> pxshot::Result<()> {
+/// let limiter = RateLimiter::builder()
+///     .per_second(5)
+///     .per_minute(120)
+///     .max_concurrent(4)
+///     .build()?;
+///
+/// let client = Pxshot::builder("px_your_api_key")
+///     .rate_limiter(limiter)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: Mutex<State>,
+    notify: Notify,
+    condvar: Condvar,
+    max_retries: u32,
+}
+
+#[derive(Debug)]
+struct State {
+    second: Option<Bucket>,
+    minute: Option<Bucket>,
+    max_concurrent: Option<usize>,
+    in_flight: usize,
+    paused_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn new(capacity: u32, window: Duration) -> Self {
+        let capacity = f64::from(capacity);
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: capacity / window.as_secs_f64(),
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Time until one token is available, or `None` if one is available now.
+    fn wait_time(&self) -> Option<Duration> {
+        if self.tokens >= 1.0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64(
+                (1.0 - self.tokens) / self.refill_per_sec,
+            ))
+        }
+    }
+}
+
+/// Why a request could not be admitted yet.
+enum Wait {
+    /// Capacity frees up after this duration.
+    For(Duration),
+    /// Capacity frees up when an in-flight request completes.
+    Release,
+}
+
+impl State {
+    fn try_acquire(&mut self, now: Instant) -> std::result::Result<(), Wait> {
+        if let Some(until) = self.paused_until {
+            if until > now {
+                return Err(Wait::For(until - now));
+            }
+            self.paused_until = None;
+        }
+
+        if let Some(max) = self.max_concurrent {
+            if self.in_flight >= max {
+                return Err(Wait::Release);
+            }
+        }
+
+        let mut wait = None;
+        for bucket in [&mut self.second, &mut self.minute].into_iter().flatten() {
+            bucket.refill(now);
+            wait = wait.max(bucket.wait_time());
+        }
+        if let Some(wait) = wait {
+            return Err(Wait::For(wait));
+        }
+
+        for bucket in [&mut self.second, &mut self.minute].into_iter().flatten() {
+            bucket.tokens -= 1.0;
+        }
+        self.in_flight += 1;
+        Ok(())
+    }
+
+    fn pause_for(&mut self, duration: Duration) {
+        let until = Instant::now() + duration;
+        self.paused_until = Some(
+            self.paused_until
+                .map_or(until, |current| current.max(until)),
+        );
+    }
+}
+
+impl RateLimiter {
+    /// Create a new builder for a rate limiter.
+    pub fn builder() -> RateLimiterBuilder {
+        RateLimiterBuilder::default()
+    }
+
+    /// Wait until a request may be sent.
+    ///
+    /// The returned permit counts towards the concurrency limit until dropped.
+    pub(crate) async fn acquire(&self) -> RateLimitPermit {
+        loop {
+            let notified = self.inner.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let wait = match self.lock().try_acquire(Instant::now()) {
+                Ok(()) => return self.permit(),
+                Err(wait) => wait,
+            };
+
+            match wait {
+                Wait::For(duration) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(duration) => {}
+                        _ = notified => {}
+                    }
+                }
+                Wait::Release => notified.await,
+            }
+        }
+    }
+
+    /// Block the current thread until a request may be sent.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn acquire_blocking(&self) -> RateLimitPermit {
+        let mut state = self.lock();
+        loop {
+            let wait = match state.try_acquire(Instant::now()) {
+                Ok(()) => return self.permit(),
+                Err(wait) => wait,
+            };
+
+            state = match wait {
+                Wait::For(duration) => {
+                    self.inner
+                        .condvar
+                        .wait_timeout(state, duration)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                Wait::Release => self
+                    .inner
+                    .condvar
+                    .wait(state)
+                    .unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+
+    /// Maximum number of times a throttled request is retried.
+    pub(crate) fn max_retries(&self) -> u32 {
+        self.inner.max_retries
+    }
+
+    /// Update the limiter from the rate-limit headers of a response.
+    pub(crate) fn observe(&self, headers: &HeaderMap) {
+        let limit = header_u64(headers, RATE_LIMIT_LIMIT);
+        let remaining = header_u64(headers, RATE_LIMIT_REMAINING);
+        let reset = header_u64(headers, RATE_LIMIT_RESET).map(reset_delay);
+
+        let mut state = self.lock();
+
+        if let Some(limit) = limit.and_then(|limit| u32::try_from(limit).ok()) {
+            let tune = match &state.minute {
+                Some(bucket) => f64::from(limit) < bucket.capacity,
+                None => limit > 0,
+            };
+            if tune {
+                let tokens = state.minute.as_ref().map(|bucket| bucket.tokens);
+                let mut bucket = Bucket::new(limit, Duration::from_secs(60));
+                if let Some(tokens) = tokens {
+                    bucket.tokens = tokens.min(bucket.capacity);
+                }
+                state.minute = Some(bucket);
+            }
+        }
+
+        if let Some(remaining) = remaining {
+            if let Some(bucket) = &mut state.minute {
+                bucket.tokens = bucket.tokens.min(remaining as f64);
+            }
+            if remaining == 0 {
+                if let Some(reset) = reset {
+                    state.pause_for(reset.min(MAX_BACKOFF));
+                }
+            }
+        }
+    }
+
+    /// Record a `429 Too Many Requests` response so queued requests back off.
+    ///
+    /// Returns the delay applied before the next request, capped at
+    /// `MAX_BACKOFF`.
+    pub(crate) fn throttled(&self, headers: &HeaderMap, attempt: u32) -> Duration {
+        self.observe(headers);
+
+        let delay = header_u64(headers, RETRY_AFTER.as_str())
+            .map(Duration::from_secs)
+            .or_else(|| header_u64(headers, RATE_LIMIT_RESET).map(reset_delay))
+            .unwrap_or_else(|| {
+                BASE_BACKOFF.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            })
+            .min(MAX_BACKOFF);
+
+        self.lock().pause_for(delay);
+        delay
+    }
+
+    fn permit(&self) -> RateLimitPermit {
+        RateLimitPermit {
+            limiter: self.clone(),
+        }
+    }
+
+    fn release(&self) {
+        {
+            let mut state = self.lock();
+            state.in_flight = state.in_flight.saturating_sub(1);
+        }
+        self.inner.notify.notify_waiters();
+        self.inner.condvar.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Admission to send one request, released when dropped.
+#[derive(Debug)]
+pub(crate) struct RateLimitPermit {
+    limiter: RateLimiter,
+}
+
+impl Drop for RateLimitPermit {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+/// Builder for [`RateLimiter`].
+#[derive(Debug)]
+pub struct RateLimiterBuilder {
+    per_second: Option<u32>,
+    per_minute: Option<u32>,
+    max_concurrent: Option<usize>,
+    max_retries: u32,
+}
+
+impl Default for RateLimiterBuilder {
+    fn default() -> Self {
+        Self {
+            per_second: None,
+            per_minute: None,
+            max_concurrent: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+        }
+    }
+}
+
+impl RateLimiterBuilder {
+    /// Maximum number of requests per second.
+    pub fn per_second(mut self, requests: u32) -> Self {
+        self.per_second = Some(requests);
+        self
+    }
+
+    /// Maximum number of requests per minute.
+    pub fn per_minute(mut self, requests: u32) -> Self {
+        self.per_minute = Some(requests);
+        self
+    }
+
+    /// Maximum number of requests in flight at once.
+    pub fn max_concurrent(mut self, requests: usize) -> Self {
+        self.max_concurrent = Some(requests);
+        self
+    }
+
+    /// Maximum number of retries for requests rejected with `429` (default: 3).
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    /// Build the rate limiter.
+    pub fn build(self) -> Result<RateLimiter> {
+        if self.per_second == Some(0) || self.per_minute == Some(0) {
+            return Err(Error::Config(
+                "rate limit must be at least 1 request".into(),
+            ));
+        }
+        if self.max_concurrent == Some(0) {
+            return Err(Error::Config("max_concurrent must be at least 1".into()));
+        }
+
+        let state = State {
+            second: self
+                .per_second
+                .map(|limit| Bucket::new(limit, Duration::from_secs(1))),
+            minute: self
+                .per_minute
+                .map(|limit| Bucket::new(limit, Duration::from_secs(60))),
+            max_concurrent: self.max_concurrent,
+            in_flight: 0,
+            paused_until: None,
+        };
+
+        Ok(RateLimiter {
+            inner: Arc::new(Inner {
+                state: Mutex::new(state),
+                notify: Notify::new(),
+                condvar: Condvar::new(),
+                max_retries: self.max_retries,
+            }),
+        })
+    }
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+/// Interpret an `X-RateLimit-Reset` value as a delay.
+///
+/// Small values are seconds until the reset; large values are Unix timestamps.
+fn reset_delay(value: u64) -> Duration {
+    const TIMESTAMP_THRESHOLD: u64 = 1_000_000_000;
+
+    if value < TIMESTAMP_THRESHOLD {
+        return Duration::from_secs(value);
+    }
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    Duration::from_secs(value.saturating_sub(now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn paused_for(limiter: &RateLimiter) -> Option<Duration> {
+        let until = limiter.lock().paused_until?;
+        Some(until.saturating_duration_since(Instant::now()))
+    }
+
+    #[test]
+    fn bucket_refills_over_time_up_to_capacity() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(10, Duration::from_secs(1));
+        bucket.last_refill = start;
+        bucket.tokens = 0.0;
+
+        assert_eq!(bucket.wait_time(), Some(Duration::from_millis(100)));
+
+        bucket.refill(start + Duration::from_millis(500));
+        assert!(
+            (bucket.tokens - 5.0).abs() < 1e-9,
+            "tokens = {}",
+            bucket.tokens
+        );
+        assert_eq!(bucket.wait_time(), None);
+
+        bucket.refill(start + Duration::from_secs(10));
+        assert_eq!(bucket.tokens, 10.0, "refill must not exceed capacity");
+    }
+
+    #[test]
+    fn try_acquire_spends_tokens_until_empty() {
+        let limiter = RateLimiter::builder().per_second(2).build().unwrap();
+        let now = Instant::now();
+        let mut state = limiter.lock();
+
+        assert!(state.try_acquire(now).is_ok());
+        assert!(state.try_acquire(now).is_ok());
+        match state.try_acquire(now) {
+            Err(Wait::For(wait)) => assert!(wait <= Duration::from_millis(500), "wait = {wait:?}"),
+            _ => panic!("third request in the same instant should wait for a token"),
+        }
+        assert_eq!(state.in_flight, 2);
+    }
+
+    #[test]
+    fn try_acquire_waits_for_release_at_max_concurrent() {
+        let limiter = RateLimiter::builder().max_concurrent(1).build().unwrap();
+        let now = Instant::now();
+
+        assert!(limiter.lock().try_acquire(now).is_ok());
+        assert!(matches!(
+            limiter.lock().try_acquire(now),
+            Err(Wait::Release)
+        ));
+
+        limiter.release();
+        assert!(limiter.lock().try_acquire(now).is_ok());
+    }
+
+    #[test]
+    fn try_acquire_honours_pause() {
+        let limiter = RateLimiter::builder().build().unwrap();
+        limiter.lock().pause_for(Duration::from_secs(5));
+
+        let now = Instant::now();
+        assert!(matches!(limiter.lock().try_acquire(now), Err(Wait::For(_))));
+        assert!(limiter
+            .lock()
+            .try_acquire(now + Duration::from_secs(6))
+            .is_ok());
+        assert_eq!(limiter.lock().paused_until, None);
+    }
+
+    #[test]
+    fn build_rejects_zero_limits() {
+        assert!(RateLimiter::builder().per_second(0).build().is_err());
+        assert!(RateLimiter::builder().per_minute(0).build().is_err());
+        assert!(RateLimiter::builder().max_concurrent(0).build().is_err());
+    }
+
+    #[test]
+    fn observe_adopts_advertised_limit() {
+        let limiter = RateLimiter::builder().build().unwrap();
+        limiter.observe(&headers(&[(RATE_LIMIT_LIMIT, "60")]));
+
+        let state = limiter.lock();
+        let bucket = state
+            .minute
+            .as_ref()
+            .expect("limit header creates a bucket");
+        assert_eq!(bucket.capacity, 60.0);
+        assert!((bucket.refill_per_sec - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn observe_only_lowers_configured_limit() {
+        let limiter = RateLimiter::builder().per_minute(30).build().unwrap();
+
+        limiter.observe(&headers(&[(RATE_LIMIT_LIMIT, "100")]));
+        assert_eq!(limiter.lock().minute.as_ref().unwrap().capacity, 30.0);
+
+        limiter.observe(&headers(&[(RATE_LIMIT_LIMIT, "10")]));
+        let state = limiter.lock();
+        let bucket = state.minute.as_ref().unwrap();
+        assert_eq!(bucket.capacity, 10.0);
+        assert!(bucket.tokens <= 10.0, "tokens = {}", bucket.tokens);
+    }
+
+    #[test]
+    fn observe_ignores_malformed_headers() {
+        let limiter = RateLimiter::builder().build().unwrap();
+        limiter.observe(&headers(&[
+            (RATE_LIMIT_LIMIT, "lots"),
+            (RATE_LIMIT_REMAINING, "-1"),
+            (RATE_LIMIT_RESET, "soon"),
+        ]));
+
+        let state = limiter.lock();
+        assert!(state.minute.is_none());
+        assert!(state.paused_until.is_none());
+    }
+
+    #[test]
+    fn observe_caps_tokens_at_remaining() {
+        let limiter = RateLimiter::builder().per_minute(60).build().unwrap();
+        limiter.observe(&headers(&[(RATE_LIMIT_REMAINING, " 3 ")]));
+
+        assert_eq!(limiter.lock().minute.as_ref().unwrap().tokens, 3.0);
+        assert!(limiter.lock().paused_until.is_none());
+    }
+
+    #[test]
+    fn observe_pauses_until_reset_when_exhausted() {
+        let limiter = RateLimiter::builder().build().unwrap();
+        limiter.observe(&headers(&[
+            (RATE_LIMIT_REMAINING, "0"),
+            (RATE_LIMIT_RESET, "10"),
+        ]));
+
+        let paused = paused_for(&limiter).expect("exhausted limit pauses");
+        assert!(paused > Duration::from_secs(9) && paused <= Duration::from_secs(10));
+    }
+
+    #[test]
+    fn observe_caps_pause_for_distant_reset() {
+        let limiter = RateLimiter::builder().build().unwrap();
+        limiter.observe(&headers(&[
+            (RATE_LIMIT_REMAINING, "0"),
+            (RATE_LIMIT_RESET, "999999999"),
+        ]));
+
+        assert!(paused_for(&limiter).unwrap() <= MAX_BACKOFF);
+    }
+
+    #[test]
+    fn throttled_backs_off_exponentially_up_to_max() {
+        let limiter = RateLimiter::builder().build().unwrap();
+        let none = HeaderMap::new();
+
+        assert_eq!(limiter.throttled(&none, 1), Duration::from_millis(500));
+        assert_eq!(limiter.throttled(&none, 2), Duration::from_secs(1));
+        assert_eq!(limiter.throttled(&none, 3), Duration::from_secs(2));
+        assert_eq!(limiter.throttled(&none, 10), MAX_BACKOFF);
+        assert_eq!(limiter.throttled(&none, u32::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn throttled_prefers_retry_after() {
+        let limiter = RateLimiter::builder().build().unwrap();
+        let delay = limiter.throttled(
+            &headers(&[(RETRY_AFTER.as_str(), "7"), (RATE_LIMIT_RESET, "20")]),
+            1,
+        );
+
+        assert_eq!(delay, Duration::from_secs(7));
+        assert!(paused_for(&limiter).unwrap() > Duration::from_secs(6));
+    }
+
+    #[test]
+    fn throttled_falls_back_to_reset() {
+        let limiter = RateLimiter::builder().build().unwrap();
+        let delay = limiter.throttled(&headers(&[(RATE_LIMIT_RESET, "4")]), 1);
+        assert_eq!(delay, Duration::from_secs(4));
+    }
+
+    #[test]
+    fn throttled_caps_retry_after() {
+        let limiter = RateLimiter::builder().build().unwrap();
+        let delay = limiter.throttled(&headers(&[(RETRY_AFTER.as_str(), "86400")]), 1);
+
+        assert_eq!(delay, MAX_BACKOFF);
+        assert!(paused_for(&limiter).unwrap() <= MAX_BACKOFF);
+    }
+
+    #[test]
+    fn reset_delay_accepts_seconds_and_timestamps() {
+        assert_eq!(reset_delay(0), Duration::ZERO);
+        assert_eq!(reset_delay(42), Duration::from_secs(42));
+        assert_eq!(reset_delay(1_000_000_000), Duration::ZERO, "past timestamp");
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let delay = reset_delay(now + 60);
+        assert!(delay > Duration::from_secs(58) && delay <= Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,266 @@
+//! `robots.txt` parsing for the crawler.
+
+use url::Url;
+
+/// The `Allow` and `Disallow` rules of a `robots.txt` that apply to one
+/// user agent.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Robots {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+/// A `User-agent` group and its rules.
+#[derive(Default)]
+struct Group {
+    agents: Vec<String>,
+    rules: Vec<Rule>,
+}
+
+impl Robots {
+    /// Rules that allow everything, as when a site has no `robots.txt`.
+    pub(crate) fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Rules that disallow everything, as when a site's `robots.txt` is
+    /// temporarily unavailable.
+    pub(crate) fn disallow_all() -> Self {
+        Self {
+            rules: vec![Rule {
+                allow: false,
+                pattern: "/".into(),
+            }],
+        }
+    }
+
+    /// Parse `contents`, keeping the rules for `user_agent`.
+    ///
+    /// Groups naming the agent's product token (the part before any `/`)
+    /// apply; if there are none, the `*` groups do.
+    pub(crate) fn parse(contents: &str, user_agent: &str) -> Self {
+        let token = user_agent
+            .split(['/', ' '])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        let mut groups: Vec<Group> = Vec::new();
+        let mut in_rules = true;
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((field, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match field.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    // Consecutive user-agent lines share the rules that follow.
+                    if in_rules {
+                        groups.push(Group::default());
+                        in_rules = false;
+                    }
+                    let group = groups.last_mut().expect("group was just pushed");
+                    group.agents.push(value.to_ascii_lowercase());
+                }
+                field @ ("allow" | "disallow") => {
+                    in_rules = true;
+                    let Some(group) = groups.last_mut() else {
+                        continue;
+                    };
+                    // An empty `Disallow` allows everything, like no rule.
+                    if !value.is_empty() {
+                        group.rules.push(Rule {
+                            allow: field == "allow",
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let applies = |agent: &str| agent != "*" && agent == token;
+        let specific = groups
+            .iter()
+            .any(|group| group.agents.iter().any(|agent| applies(agent)));
+        let rules = groups
+            .into_iter()
+            .filter(|group| {
+                group.agents.iter().any(|agent| {
+                    if specific {
+                        applies(agent)
+                    } else {
+                        agent == "*"
+                    }
+                })
+            })
+            .flat_map(|group| group.rules)
+            .collect();
+        Self { rules }
+    }
+
+    /// Whether `url` may be fetched.
+    ///
+    /// The rule with the longest matching pattern wins; if an `Allow` and a
+    /// `Disallow` rule match equally, the `Allow` rule does.
+    pub(crate) fn is_allowed(&self, url: &Url) -> bool {
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        if path == "/robots.txt" {
+            return true;
+        }
+
+        self.rules
+            .iter()
+            .filter(|rule| pattern_matches(&rule.pattern, &path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+}
+
+/// Match a `robots.txt` path pattern, where `*` matches any run of
+/// characters and a trailing `$` anchors the end, against the start of
+/// `path`.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(robots: &Robots, path: &str) -> bool {
+        let url = Url::parse("https://example.com")
+            .unwrap()
+            .join(path)
+            .unwrap();
+        robots.is_allowed(&url)
+    }
+
+    #[test]
+    fn pattern_prefix_and_wildcards() {
+        assert!(pattern_matches("/private", "/private/page"));
+        assert!(!pattern_matches("/private", "/public"));
+        assert!(pattern_matches("/*/edit", "/posts/1/edit"));
+        assert!(pattern_matches("/a*b*c", "/a-b-c-d"));
+        assert!(!pattern_matches("/a*b*c", "/a-c-b"));
+        assert!(pattern_matches("*", "/anything"));
+    }
+
+    #[test]
+    fn pattern_end_anchor() {
+        assert!(pattern_matches("/page$", "/page"));
+        assert!(!pattern_matches("/page$", "/page/more"));
+        assert!(pattern_matches("/*.pdf$", "/docs/report.pdf"));
+        assert!(!pattern_matches("/*.pdf$", "/docs/report.pdf?download=1"));
+        assert!(pattern_matches("/*.pdf$", "/a.pdf/b.pdf"));
+        assert!(pattern_matches("/*$", "/"));
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        let robots = Robots::parse(
+            "User-agent: *\nDisallow: /shop\nAllow: /shop/catalog\n",
+            "pxshot",
+        );
+        assert!(allowed(&robots, "/"));
+        assert!(!allowed(&robots, "/shop/cart"));
+        assert!(allowed(&robots, "/shop/catalog/1"));
+    }
+
+    #[test]
+    fn allow_wins_tie() {
+        let robots = Robots::parse("User-agent: *\nDisallow: /page\nAllow: /page\n", "pxshot");
+        assert!(allowed(&robots, "/page"));
+
+        let robots = Robots::parse("User-agent: *\nAllow: /page\nDisallow: /page\n", "pxshot");
+        assert!(allowed(&robots, "/page"));
+    }
+
+    #[test]
+    fn group_with_several_agents() {
+        let contents = "\
+User-agent: googlebot
+User-agent: pxshot
+Disallow: /private
+
+User-agent: *
+Disallow: /
+";
+        let robots = Robots::parse(contents, "pxshot/1.0");
+        assert!(allowed(&robots, "/public"));
+        assert!(!allowed(&robots, "/private"));
+
+        let robots = Robots::parse(contents, "googlebot");
+        assert!(!allowed(&robots, "/private"));
+
+        let robots = Robots::parse(contents, "otherbot");
+        assert!(!allowed(&robots, "/public"));
+    }
+
+    #[test]
+    fn specific_groups_replace_wildcard() {
+        let contents = "User-agent: *\nDisallow: /\n\nUser-agent: PXSHOT\nDisallow: /admin\n";
+        let robots = Robots::parse(contents, "pxshot/1.0 (+https://pxshot.com)");
+        assert!(allowed(&robots, "/"));
+        assert!(!allowed(&robots, "/admin"));
+    }
+
+    #[test]
+    fn comments_empty_rules_and_stray_lines() {
+        let contents = "\
+Disallow: /before-any-group
+# comment
+User-agent: * # everyone
+Disallow: # nothing
+Disallow: /tmp # temporary files
+Sitemap: https://example.com/sitemap.xml
+";
+        let robots = Robots::parse(contents, "pxshot");
+        assert!(allowed(&robots, "/before-any-group"));
+        assert!(allowed(&robots, "/page"));
+        assert!(!allowed(&robots, "/tmp/file"));
+    }
+
+    #[test]
+    fn query_is_matched() {
+        let robots = Robots::parse("User-agent: *\nDisallow: /*?sort=\n", "pxshot");
+        assert!(allowed(&robots, "/list"));
+        assert!(!allowed(&robots, "/list?sort=asc"));
+    }
+
+    #[test]
+    fn robots_txt_is_always_allowed() {
+        assert!(allowed(&Robots::disallow_all(), "/robots.txt"));
+        assert!(!allowed(&Robots::disallow_all(), "/"));
+        assert!(allowed(&Robots::allow_all(), "/anything"));
+    }
+}
@@ -0,0 +1,482 @@
+//! Perceptual similarity between screenshots.
+//!
+//! Requires the `image` feature. Unlike the exact pixel comparison in
+//! [`diff`](crate::diff), these measures tolerate small rendering
+//! differences such as font smoothing:
+//!
+//! - [`ssim`] scores the structural similarity of two images of the same
+//!   size, from 1.0 for identical images down to 0 or below.
+//! - [`hash`] computes a 64-bit perceptual hash. Similar images have hashes
+//!   that differ in few bits, so the [Hamming distance](ImageHash::distance)
+//!   between hashes finds near-duplicates without keeping the images around.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use pxshot::similarity::{self, HashKind};
+//!
+//! # fn main() -> pxshot::Result<()> {
+//! let a = std::fs::read("a.png")?;
+//! let b = std::fs::read("b.png")?;
+//!
+//! println!("SSIM: {:.4}", similarity::ssim(&a, &b)?);
+//!
+//! let hash_a = similarity::hash(&a, HashKind::Perceptual)?;
+//! let hash_b = similarity::hash(&b, HashKind::Perceptual)?;
+//! if hash_a.is_similar(&hash_b, 6) {
+//!     println!("near-duplicates ({} bits differ)", hash_a.distance(&hash_b));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::diff;
+use crate::error::{Error, Result};
+
+/// Side of the square windows SSIM is computed over.
+const SSIM_WINDOW: u32 = 8;
+/// Distance between neighbouring SSIM windows.
+const SSIM_STEP: u32 = 4;
+const SSIM_C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIM_C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+/// Side of the image the perceptual hash DCT is computed over.
+const DCT_SIZE: usize = 32;
+
+/// Structural similarity (SSIM) of two encoded screenshots.
+///
+/// Fails with [`Error::Image`] if either image cannot be decoded or their
+/// dimensions differ.
+pub fn ssim(a: &[u8], b: &[u8]) -> Result<f64> {
+    ssim_images(&diff::decode(a, "first")?, &diff::decode(b, "second")?)
+}
+
+/// Structural similarity (SSIM) of two decoded images.
+///
+/// Computed on luma over 8x8 windows spaced 4 pixels apart and averaged
+/// over the image. Scores of about 0.98 and above usually mean the images
+/// look the same.
+pub fn ssim_images(a: &DynamicImage, b: &DynamicImage) -> Result<f64> {
+    if (a.width(), a.height()) != (b.width(), b.height()) {
+        return Err(Error::Image(format!(
+            "cannot compute SSIM of images of different sizes ({}x{} and {}x{})",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height()
+        )));
+    }
+    let (a, b) = (a.to_luma8(), b.to_luma8());
+    let (width, height) = a.dimensions();
+    if width == 0 || height == 0 {
+        return Ok(1.0);
+    }
+
+    let window_width = SSIM_WINDOW.min(width);
+    let window_height = SSIM_WINDOW.min(height);
+    let mut total = 0.0;
+    let mut windows = 0u64;
+    for y in window_starts(height, window_height) {
+        for x in window_starts(width, window_width) {
+            total += window_ssim(&a, &b, x, y, window_width, window_height);
+            windows += 1;
+        }
+    }
+    Ok(total / windows as f64)
+}
+
+/// Window offsets along one axis, always including a window at the end.
+fn window_starts(length: u32, window: u32) -> impl Iterator<Item = u32> {
+    let last = length - window;
+    (0..=last)
+        .step_by(SSIM_STEP as usize)
+        .chain((!last.is_multiple_of(SSIM_STEP)).then_some(last))
+}
+
+fn window_ssim(a: &GrayImage, b: &GrayImage, x: u32, y: u32, width: u32, height: u32) -> f64 {
+    let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for py in y..y + height {
+        for px in x..x + width {
+            let va = f64::from(a.get_pixel(px, py)[0]);
+            let vb = f64::from(b.get_pixel(px, py)[0]);
+            sum_a += va;
+            sum_b += vb;
+            sum_aa += va * va;
+            sum_bb += vb * vb;
+            sum_ab += va * vb;
+        }
+    }
+
+    let n = f64::from(width * height);
+    let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+    let var_a = sum_aa / n - mean_a * mean_a;
+    let var_b = sum_bb / n - mean_b * mean_b;
+    let covariance = sum_ab / n - mean_a * mean_b;
+
+    ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+        / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1) * (var_a + var_b + SSIM_C2))
+}
+
+/// A perceptual hash algorithm.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashKind {
+    /// Average hash (aHash): which pixels of an 8x8 thumbnail are brighter
+    /// than its mean. Fastest, but sensitive to overall brightness changes.
+    Average,
+    /// Difference hash (dHash): which pixels of a 9x8 thumbnail are brighter
+    /// than their left neighbour. Fast and robust to brightness changes.
+    Difference,
+    /// Perceptual hash (pHash): the signs of the low frequencies of a 32x32
+    /// thumbnail's discrete cosine transform. Slowest and most robust.
+    #[default]
+    Perceptual,
+}
+
+/// A 64-bit perceptual hash of an image.
+///
+/// Hashes are only comparable if computed with the same [`HashKind`].
+/// They format and serialize as 16 hex digits, so they can be stored, e.g.
+/// alongside a crawl's results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageHash(u64);
+
+impl ImageHash {
+    /// Create a hash from its bits.
+    pub fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    /// The bits of the hash.
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Number of bits that differ between the hashes (0-64).
+    pub fn distance(&self, other: &ImageHash) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+
+    /// Fraction of bits the hashes share, from 0.0 to 1.0.
+    pub fn similarity(&self, other: &ImageHash) -> f64 {
+        1.0 - f64::from(self.distance(other)) / 64.0
+    }
+
+    /// Whether at most `max_distance` bits differ.
+    ///
+    /// A distance of up to about 5 usually means the images look alike;
+    /// 10 or more usually means they differ.
+    pub fn is_similar(&self, other: &ImageHash, max_distance: u32) -> bool {
+        self.distance(other) <= max_distance
+    }
+}
+
+impl fmt::Display for ImageHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for ImageHash {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // `from_str_radix` alone would also accept a leading `+`.
+        if s.len() != 16 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(Error::Image(format!(
+                "invalid image hash `{}`; expected 16 hex digits",
+                s
+            )));
+        }
+        u64::from_str_radix(s, 16).map(Self).map_err(|_| {
+            Error::Image(format!(
+                "invalid image hash `{}`; expected 16 hex digits",
+                s
+            ))
+        })
+    }
+}
+
+impl Serialize for ImageHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ImageHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Perceptual hash of an encoded screenshot.
+pub fn hash(bytes: &[u8], kind: HashKind) -> Result<ImageHash> {
+    Ok(hash_image(&diff::decode(bytes, "the")?, kind))
+}
+
+/// Perceptual hash of a decoded image.
+pub fn hash_image(image: &DynamicImage, kind: HashKind) -> ImageHash {
+    match kind {
+        HashKind::Average => average_hash(image),
+        HashKind::Difference => difference_hash(image),
+        HashKind::Perceptual => perceptual_hash(image),
+    }
+}
+
+fn shrink(image: &DynamicImage, width: u32, height: u32) -> GrayImage {
+    image
+        .grayscale()
+        .resize_exact(width, height, FilterType::Triangle)
+        .to_luma8()
+}
+
+fn bits(values: impl Iterator<Item = bool>) -> ImageHash {
+    ImageHash(values.fold(0, |bits, set| (bits << 1) | u64::from(set)))
+}
+
+fn average_hash(image: &DynamicImage) -> ImageHash {
+    let pixels = shrink(image, 8, 8).into_raw();
+    let mean = pixels.iter().map(|&p| u32::from(p)).sum::<u32>() as f64 / 64.0;
+    bits(pixels.iter().map(|&p| f64::from(p) > mean))
+}
+
+fn difference_hash(image: &DynamicImage) -> ImageHash {
+    let pixels = shrink(image, 9, 8);
+    bits((0..8).flat_map(|y| {
+        let pixels = &pixels;
+        (0..8).map(move |x| pixels.get_pixel(x + 1, y)[0] > pixels.get_pixel(x, y)[0])
+    }))
+}
+
+fn perceptual_hash(image: &DynamicImage) -> ImageHash {
+    let pixels = shrink(image, DCT_SIZE as u32, DCT_SIZE as u32);
+    let mut matrix: Vec<f64> = pixels.into_raw().into_iter().map(f64::from).collect();
+
+    // Separable 2D DCT-II: transform every row, then every column.
+    for row in matrix.chunks_mut(DCT_SIZE) {
+        let transformed = dct(row);
+        row.copy_from_slice(&transformed);
+    }
+    for x in 0..DCT_SIZE {
+        let column: Vec<f64> = (0..DCT_SIZE).map(|y| matrix[y * DCT_SIZE + x]).collect();
+        for (y, value) in dct(&column).into_iter().enumerate() {
+            matrix[y * DCT_SIZE + x] = value;
+        }
+    }
+
+    let low: Vec<f64> = (0..8)
+        .flat_map(|y| (0..8).map(move |x| (y, x)))
+        .map(|(y, x)| matrix[y * DCT_SIZE + x])
+        .collect();
+    let mut sorted = low.clone();
+    sorted.sort_by(f64::total_cmp);
+    let median = (sorted[31] + sorted[32]) / 2.0;
+    bits(low.into_iter().map(|value| value > median))
+}
+
+/// Unnormalized one-dimensional DCT-II.
+fn dct(input: &[f64]) -> Vec<f64> {
+    let n = input.len() as f64;
+    (0..input.len())
+        .map(|k| {
+            input
+                .iter()
+                .enumerate()
+                .map(|(i, value)| {
+                    value * (PI * k as f64 * (2.0 * i as f64 + 1.0) / (2.0 * n)).cos()
+                })
+                .sum()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{Luma, Rgb, RgbImage};
+
+    use crate::imaging;
+    use crate::types::ImageFormat;
+
+    const KINDS: [HashKind; 3] = [
+        HashKind::Average,
+        HashKind::Difference,
+        HashKind::Perceptual,
+    ];
+
+    /// A diagonal gradient with a dark block, so every hash has some bits set.
+    fn pattern(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            if x < width / 3 && y < height / 3 {
+                Rgb([20, 20, 20])
+            } else {
+                let value = ((x + y) * 255 / (width + height)) as u8;
+                Rgb([value, value / 2, 255 - value])
+            }
+        }))
+    }
+
+    fn checkerboard(width: u32, height: u32, cell: u32) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+            Luma([if (x / cell + y / cell).is_multiple_of(2) {
+                0
+            } else {
+                255
+            }])
+        }))
+    }
+
+    fn png(image: &DynamicImage) -> Vec<u8> {
+        imaging::encode(image, ImageFormat::Png, None).unwrap()
+    }
+
+    #[test]
+    fn ssim_of_identical_images_is_one() {
+        for (width, height) in [(64, 48), (13, 7), (3, 3), (1, 1)] {
+            let image = pattern(width, height);
+            let score = ssim_images(&image, &image).unwrap();
+            assert!(
+                (score - 1.0).abs() < 1e-9,
+                "{}x{}: {}",
+                width,
+                height,
+                score
+            );
+        }
+
+        let bytes = png(&pattern(32, 32));
+        assert!((ssim(&bytes, &bytes).unwrap() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ssim_of_mismatched_sizes_is_an_error() {
+        let error = ssim_images(&pattern(10, 10), &pattern(10, 11))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("10x10 and 10x11"), "{}", error);
+
+        assert!(ssim(&png(&pattern(10, 10)), b"not an image").is_err());
+    }
+
+    #[test]
+    fn ssim_drops_for_different_images() {
+        let a = checkerboard(64, 64, 8);
+        let b = checkerboard(64, 64, 4);
+        let score = ssim_images(&a, &b).unwrap();
+        assert!(score < 0.5, "score = {}", score);
+
+        let mut shifted = a.to_luma8();
+        shifted.put_pixel(0, 0, Luma([10]));
+        let score = ssim_images(&a, &DynamicImage::ImageLuma8(shifted)).unwrap();
+        assert!(score > 0.98 && score < 1.0, "score = {}", score);
+    }
+
+    #[test]
+    fn window_starts_cover_the_whole_axis() {
+        assert_eq!(window_starts(8, 8).collect::<Vec<_>>(), [0]);
+        assert_eq!(window_starts(16, 8).collect::<Vec<_>>(), [0, 4, 8]);
+        assert_eq!(window_starts(18, 8).collect::<Vec<_>>(), [0, 4, 8, 10]);
+    }
+
+    #[test]
+    fn hashes_are_stable() {
+        let image = pattern(200, 120);
+        let bytes = png(&image);
+        for kind in KINDS {
+            let first = hash_image(&image, kind);
+            assert_eq!(first, hash_image(&image, kind), "{:?}", kind);
+            assert_eq!(
+                first,
+                hash(&bytes, kind).unwrap(),
+                "{:?} of the encoded image",
+                kind
+            );
+            assert_ne!(first.bits(), 0, "{:?} hash should not be empty", kind);
+        }
+    }
+
+    #[test]
+    fn hashes_tolerate_resizing_but_not_new_content() {
+        let image = pattern(400, 240);
+        let resized = image.resize_exact(200, 120, FilterType::Triangle);
+        let other = checkerboard(400, 240, 40);
+        for kind in KINDS {
+            let (a, b, c) = (
+                hash_image(&image, kind),
+                hash_image(&resized, kind),
+                hash_image(&other, kind),
+            );
+            assert!(
+                a.is_similar(&b, 5),
+                "{:?}: resized distance {}",
+                kind,
+                a.distance(&b)
+            );
+            assert!(
+                !a.is_similar(&c, 10),
+                "{:?}: distinct distance {}",
+                kind,
+                a.distance(&c)
+            );
+        }
+    }
+
+    #[test]
+    fn distance_and_similarity() {
+        let a = ImageHash::from_bits(0);
+        let b = ImageHash::from_bits(0b1011);
+        assert_eq!(a.distance(&b), 3);
+        assert_eq!(a.distance(&ImageHash::from_bits(u64::MAX)), 64);
+        assert_eq!(a.similarity(&a), 1.0);
+        assert_eq!(a.similarity(&ImageHash::from_bits(u64::MAX)), 0.0);
+        assert!(a.is_similar(&b, 3));
+        assert!(!a.is_similar(&b, 2));
+    }
+
+    #[test]
+    fn hash_display_round_trips() {
+        for bits in [0, 1, 0x0123_4567_89ab_cdef, u64::MAX] {
+            let hash = ImageHash::from_bits(bits);
+            let text = hash.to_string();
+            assert_eq!(text.len(), 16, "{}", text);
+            assert_eq!(text.parse::<ImageHash>().unwrap(), hash);
+        }
+        assert_eq!(
+            "0123456789ABCDEF".parse::<ImageHash>().unwrap().bits(),
+            0x0123_4567_89ab_cdef
+        );
+    }
+
+    #[test]
+    fn hash_parse_rejects_malformed_input() {
+        for text in [
+            "",
+            "123",
+            "0123456789abcdef0",
+            "0123456789abcdeg",
+            "+123456789abcdef",
+            " 123456789abcdef",
+        ] {
+            assert!(text.parse::<ImageHash>().is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn hash_serializes_as_hex_string() {
+        let hash = ImageHash::from_bits(0xff);
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(json, "\"00000000000000ff\"");
+        assert_eq!(serde_json::from_str::<ImageHash>(&json).unwrap(), hash);
+        assert!(serde_json::from_str::<ImageHash>("\"ff\"").is_err());
+    }
+}
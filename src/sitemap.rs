@@ -0,0 +1,614 @@
+//! Capture every page listed in a sitemap.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+use url::Url;
+
+use crate::batch::{glob_match, unique_path, Manifest, ManifestItem};
+use crate::error::{Error, Result};
+use crate::output::url_path;
+use crate::types::{ScreenshotOptions, ScreenshotRequest};
+use crate::urls::IntoUrl;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Largest sitemap accepted, after decompression, per the sitemap protocol.
+const MAX_SITEMAP_BYTES: u64 = 50 * 1024 * 1024;
+/// How deeply sitemap index files may refer to further index files.
+const MAX_INDEX_DEPTH: usize = 3;
+
+/// A page listed in a sitemap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SitemapEntry {
+    /// URL of the page.
+    pub url: Url,
+    /// When the page was last modified, if the sitemap says.
+    pub lastmod: Option<DateTime<Utc>>,
+}
+
+/// A sitemap to capture, turned into a batch [`Manifest`].
+///
+/// Sitemaps are fetched over HTTP. Sitemap index files are followed to the
+/// sitemaps they list on the same host, and gzip-compressed sitemaps (such
+/// as `sitemap.xml.gz`) are decompressed. Sitemaps larger than 50 MB are
+/// rejected.
+/// Every page is captured with the options of a base request and written to
+/// a path derived from its URL: `https://example.com/blog/launch` becomes
+/// `blog/launch.png` and `https://example.com/blog/` becomes
+/// `blog/index.png`.
+///
+/// Include and exclude patterns use `*` for any run of characters and `?`
+/// for a single character. Patterns containing `://` are matched against
+/// the whole URL; others against its path and query.
+///
+/// # Example
+///
+/// ```no_run
+/// use pxshot::{Batch, ImageFormat, Pxshot, ScreenshotRequest, Sitemap};
+///
+/// #[tokio::main]
+/// async fn main() -> pxshot::Result<()> {
+///     let base = ScreenshotRequest::builder()
+///         .url("https://example.com")
+///         .format(ImageFormat::Jpeg)
+///         .width(1280)
+///         .build()?;
+///
+///     let sitemap = Sitemap::builder("https://example.com/sitemap.xml")
+///         .base_request(base)
+///         .output_dir("screenshots")
+///         .include("/blog/*")
+///         .exclude("*/drafts/*")
+///         .only_changed(true)
+///         .build()?;
+///
+///     let manifest = sitemap.manifest().await?;
+///     let client = Pxshot::new("px_your_api_key");
+///     let report = Batch::builder(manifest).concurrency(8).build()?.run(&client).await?;
+///     println!("{}", report);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Sitemap {
+    url: Url,
+    options: ScreenshotOptions,
+    output_dir: Option<PathBuf>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    modified_since: Option<DateTime<Utc>>,
+    only_changed: bool,
+    max_urls: Option<usize>,
+    timeout: Duration,
+    http: reqwest::Client,
+}
+
+impl Sitemap {
+    /// Create a new builder for the sitemap at `url`.
+    pub fn builder(url: impl IntoUrl) -> SitemapBuilder {
+        SitemapBuilder {
+            url: url.into_url(),
+            options: ScreenshotOptions::default(),
+            output_dir: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            modified_since: None,
+            only_changed: false,
+            max_urls: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// The URL of the sitemap.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Fetch the sitemap and list the pages that pass the filters.
+    ///
+    /// Pages listed more than once are returned once, in sitemap order.
+    pub async fn entries(&self) -> Result<Vec<SitemapEntry>> {
+        let mut entries = Vec::new();
+        let mut seen_sitemaps = HashSet::new();
+        let mut seen_pages = HashSet::new();
+        let mut queue = vec![(self.url.clone(), 0)];
+
+        // Depth-first, so pages keep the order of the sitemaps listing them.
+        while let Some((url, depth)) = queue.pop() {
+            if !seen_sitemaps.insert(url.clone()) {
+                continue;
+            }
+            match parse(&self.fetch(&url).await?)? {
+                Document::UrlSet(pages) => {
+                    for page in pages {
+                        if self.matches(&page) && seen_pages.insert(page.url.clone()) {
+                            entries.push(page);
+                        }
+                    }
+                }
+                Document::Index(sitemaps) => {
+                    if depth >= MAX_INDEX_DEPTH {
+                        return Err(Error::Manifest(format!(
+                            "sitemap indexes nested too deeply at {}",
+                            url
+                        )));
+                    }
+                    // As search engines do, an index may only refer to
+                    // sitemaps on its own host.
+                    queue.extend(
+                        sitemaps
+                            .into_iter()
+                            .rev()
+                            .filter(|entry| entry.url.host_str() == url.host_str())
+                            .map(|entry| (entry.url, depth + 1)),
+                    );
+                }
+            }
+            if self.max_urls.is_some_and(|max| entries.len() >= max) {
+                break;
+            }
+        }
+
+        if let Some(max) = self.max_urls {
+            entries.truncate(max);
+        }
+        Ok(entries)
+    }
+
+    /// Fetch the sitemap and build a manifest capturing its pages.
+    ///
+    /// With [`only_changed`](SitemapBuilder::only_changed), pages whose
+    /// output file is newer than their `lastmod` are left out.
+    pub async fn manifest(&self) -> Result<Manifest> {
+        let entries = self.entries().await?;
+        self.manifest_for(&entries)
+    }
+
+    /// Build a manifest capturing `entries`, e.g. from [`Sitemap::entries`].
+    pub fn manifest_for(&self, entries: &[SitemapEntry]) -> Result<Manifest> {
+        let extension = self.options.format.unwrap_or_default().as_str();
+        let stored = self.options.store == Some(true);
+        let output_dir = self.output_dir.clone().unwrap_or_default();
+
+        let mut outputs = HashSet::new();
+        let mut items = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let mut item = ManifestItem::new(entry.url.clone());
+            if !stored {
+                let output = unique_path(&mut outputs, url_path(&entry.url), extension);
+                if self.only_changed && is_current(&output_dir.join(&output), entry.lastmod)? {
+                    continue;
+                }
+                item.output = Some(output);
+            }
+            items.push(item);
+        }
+
+        Ok(Manifest {
+            output_dir: self.output_dir.clone(),
+            defaults: self.options.clone(),
+            items,
+            ..Manifest::default()
+        })
+    }
+
+    fn matches(&self, entry: &SitemapEntry) -> bool {
+        let url = &entry.url;
+        let matches_any = |patterns: &[String]| {
+            patterns.iter().any(|pattern| {
+                if pattern.contains("://") {
+                    glob_match(pattern, url.as_str())
+                } else {
+                    let path = match url.query() {
+                        Some(query) => format!("{}?{}", url.path(), query),
+                        None => url.path().to_string(),
+                    };
+                    glob_match(pattern, &path)
+                }
+            })
+        };
+
+        if !self.include.is_empty() && !matches_any(&self.include) {
+            return false;
+        }
+        if matches_any(&self.exclude) {
+            return false;
+        }
+        match (self.modified_since, entry.lastmod) {
+            (Some(since), Some(lastmod)) => lastmod >= since,
+            _ => true,
+        }
+    }
+
+    async fn fetch(&self, url: &Url) -> Result<Vec<u8>> {
+        let mut response = self
+            .http
+            .get(url.clone())
+            .timeout(self.timeout)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        // A gzipped sitemap is never larger than its contents, so the same
+        // cap applies before decompression.
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() as u64 > MAX_SITEMAP_BYTES {
+                return Err(too_large(url));
+            }
+        }
+
+        if !body.starts_with(&[0x1f, 0x8b]) {
+            return Ok(body);
+        }
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&body[..])
+            .take(MAX_SITEMAP_BYTES + 1)
+            .read_to_end(&mut decompressed)
+            .map_err(|e| Error::Manifest(format!("invalid gzipped sitemap {}: {}", url, e)))?;
+        if decompressed.len() as u64 > MAX_SITEMAP_BYTES {
+            return Err(too_large(url));
+        }
+        Ok(decompressed)
+    }
+}
+
+fn too_large(url: &Url) -> Error {
+    Error::Manifest(format!("sitemap {} is larger than 50 MB", url))
+}
+
+enum Document {
+    UrlSet(Vec<SitemapEntry>),
+    Index(Vec<SitemapEntry>),
+}
+
+fn parse(contents: &[u8]) -> Result<Document> {
+    let text = std::str::from_utf8(contents)
+        .map_err(|_| Error::Manifest("sitemap is not valid UTF-8".into()))?;
+    let document = roxmltree::Document::parse(text)
+        .map_err(|e| Error::Manifest(format!("invalid sitemap: {}", e)))?;
+
+    let root = document.root_element();
+    let (entry_tag, index) = match root.tag_name().name() {
+        "urlset" => ("url", false),
+        "sitemapindex" => ("sitemap", true),
+        other => {
+            return Err(Error::Manifest(format!(
+                "invalid sitemap: unexpected root element `{}`",
+                other
+            )))
+        }
+    };
+
+    // Entries with a missing or malformed location are skipped, as search
+    // engines do.
+    let entries = root
+        .children()
+        .filter(|node| node.tag_name().name() == entry_tag)
+        .filter_map(|node| {
+            let url = Url::parse(child_text(node, "loc")?).ok()?;
+            let lastmod = child_text(node, "lastmod").and_then(parse_lastmod);
+            Some(SitemapEntry { url, lastmod })
+        })
+        .collect();
+
+    Ok(if index {
+        Document::Index(entries)
+    } else {
+        Document::UrlSet(entries)
+    })
+}
+
+/// Text of the first child element of `node` named `name`.
+fn child_text<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| child.tag_name().name() == name)
+        .and_then(|child| child.text())
+        .map(str::trim)
+}
+
+/// Parse a W3C datetime as used by `lastmod`: a full timestamp or a date.
+fn parse_lastmod(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0).map(|midnight| midnight.and_utc());
+    }
+    let value = match value.strip_suffix('Z') {
+        Some(local) => format!("{}+00:00", local),
+        None => value.to_string(),
+    };
+    // Seconds are optional, e.g. `2024-05-01T10:30+02:00`.
+    DateTime::parse_from_rfc3339(&value)
+        .or_else(|_| DateTime::parse_from_str(&value, "%Y-%m-%dT%H:%M%:z"))
+        .ok()
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+/// Whether the file at `path` exists and is newer than `lastmod`.
+fn is_current(path: &Path, lastmod: Option<DateTime<Utc>>) -> Result<bool> {
+    let Some(lastmod) = lastmod else {
+        return Ok(false);
+    };
+    match fs::metadata(path) {
+        Ok(metadata) => Ok(DateTime::<Utc>::from(metadata.modified()?) >= lastmod),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Builder for [`Sitemap`].
+#[derive(Debug)]
+pub struct SitemapBuilder {
+    url: Result<Url>,
+    options: ScreenshotOptions,
+    output_dir: Option<PathBuf>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    modified_since: Option<DateTime<Utc>>,
+    only_changed: bool,
+    max_urls: Option<usize>,
+    timeout: Duration,
+}
+
+impl SitemapBuilder {
+    /// Capture every page with the options of `request`; its URL is ignored.
+    pub fn base_request(mut self, request: ScreenshotRequest) -> Self {
+        self.options = request.options();
+        self
+    }
+
+    /// Capture every page with `options`.
+    pub fn options(mut self, options: ScreenshotOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Directory the screenshots are written to (default: the current
+    /// directory).
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = Some(dir.into());
+        self
+    }
+
+    /// Only capture pages matching this pattern (or another added with this
+    /// method).
+    pub fn include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    /// Skip pages matching this pattern. Exclusions take precedence over
+    /// inclusions.
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Skip pages whose `lastmod` is before `since`. Pages without a
+    /// `lastmod` are kept.
+    pub fn modified_since(mut self, since: DateTime<Utc>) -> Self {
+        self.modified_since = Some(since);
+        self
+    }
+
+    /// Skip pages whose screenshot already exists and is newer than their
+    /// `lastmod` (default: false). Pages without a `lastmod` are always
+    /// captured.
+    pub fn only_changed(mut self, only_changed: bool) -> Self {
+        self.only_changed = only_changed;
+        self
+    }
+
+    /// Capture at most this many pages.
+    pub fn max_urls(mut self, max: usize) -> Self {
+        self.max_urls = Some(max);
+        self
+    }
+
+    /// Timeout for fetching each sitemap file (default: 30 seconds).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Build the sitemap.
+    pub fn build(self) -> Result<Sitemap> {
+        let url = self.url?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(Error::Config(format!(
+                "invalid sitemap URL `{}`; expected http or https",
+                url
+            )));
+        }
+        if let Some(pattern) = self
+            .include
+            .iter()
+            .chain(&self.exclude)
+            .find(|pattern| pattern.is_empty())
+        {
+            return Err(Error::Config(format!(
+                "invalid sitemap pattern `{}`",
+                pattern
+            )));
+        }
+        if self.max_urls == Some(0) {
+            return Err(Error::Config("sitemap max_urls must be at least 1".into()));
+        }
+
+        Ok(Sitemap {
+            url,
+            options: self.options,
+            output_dir: self.output_dir,
+            include: self.include,
+            exclude: self.exclude,
+            modified_since: self.modified_since,
+            only_changed: self.only_changed,
+            max_urls: self.max_urls,
+            timeout: self.timeout,
+            http: reqwest::Client::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    /// Serve `pages` by path over HTTP on 127.0.0.1 until the test ends.
+    ///
+    /// `{port}` in a page is replaced with the server's port.
+    fn serve(pages: HashMap<&'static str, String>) -> u16 {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let pages: HashMap<_, _> = pages
+            .into_iter()
+            .map(|(path, body)| (path, body.replace("{port}", &port.to_string())))
+            .collect();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut request = [0; 4096];
+                let len = stream.read(&mut request).unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..len]);
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let (status, body) = match pages.get(path) {
+                    Some(body) => ("200 OK", body.as_str()),
+                    None => ("404 Not Found", ""),
+                };
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(body.as_bytes());
+            }
+        });
+        port
+    }
+
+    fn urlset(urls: &[&str]) -> String {
+        let entries: String = urls
+            .iter()
+            .map(|url| format!("<url><loc>{}</loc></url>", url))
+            .collect();
+        format!(
+            r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">{}</urlset>"#,
+            entries
+        )
+    }
+
+    fn urls(entries: &[SitemapEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.url.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_urlset_and_skips_bad_locations() {
+        let xml = r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+            <url><loc> https://example.com/a </loc><lastmod>2024-05-01</lastmod></url>
+            <url><loc>not a url</loc></url>
+            <url><lastmod>2024-05-01</lastmod></url>
+            <url><loc>https://example.com/b</loc></url>
+        </urlset>"#;
+        let Document::UrlSet(entries) = parse(xml.as_bytes()).unwrap() else {
+            panic!("expected a urlset");
+        };
+        assert_eq!(
+            urls(&entries),
+            ["https://example.com/a", "https://example.com/b"]
+        );
+        assert_eq!(entries[0].lastmod, parse_lastmod("2024-05-01"));
+        assert_eq!(entries[1].lastmod, None);
+    }
+
+    #[test]
+    fn parses_index() {
+        let xml = r#"<sitemapindex><sitemap><loc>https://example.com/s1.xml</loc></sitemap></sitemapindex>"#;
+        let Document::Index(entries) = parse(xml.as_bytes()).unwrap() else {
+            panic!("expected an index");
+        };
+        assert_eq!(urls(&entries), ["https://example.com/s1.xml"]);
+    }
+
+    #[test]
+    fn rejects_other_documents() {
+        assert!(parse(b"<html></html>").is_err());
+        assert!(parse(b"<urlset>").is_err());
+        assert!(parse(&[0xff, 0xfe]).is_err());
+    }
+
+    #[test]
+    fn parses_lastmod_formats() {
+        let expected = "2024-05-01T08:30:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(parse_lastmod("2024-05-01T08:30:00Z"), Some(expected));
+        assert_eq!(parse_lastmod("2024-05-01T10:30:00+02:00"), Some(expected));
+        assert_eq!(parse_lastmod("2024-05-01T10:30+02:00"), Some(expected));
+        assert_eq!(parse_lastmod("2024-05-01T08:30Z"), Some(expected));
+        assert_eq!(
+            parse_lastmod("2024-05-01"),
+            "2024-05-01T00:00:00Z".parse::<DateTime<Utc>>().ok()
+        );
+        assert_eq!(parse_lastmod("yesterday"), None);
+    }
+
+    #[test]
+    fn filters_by_pattern_and_lastmod() {
+        let since = parse_lastmod("2024-01-01").unwrap();
+        let sitemap = Sitemap::builder("https://example.com/sitemap.xml")
+            .include("/blog/*")
+            .exclude("*/drafts/*")
+            .modified_since(since)
+            .build()
+            .unwrap();
+        let entry = |url: &str, lastmod: Option<&str>| SitemapEntry {
+            url: Url::parse(url).unwrap(),
+            lastmod: lastmod.and_then(parse_lastmod),
+        };
+
+        assert!(sitemap.matches(&entry("https://example.com/blog/a", None)));
+        assert!(sitemap.matches(&entry("https://example.com/blog/a", Some("2024-02-01"))));
+        assert!(!sitemap.matches(&entry("https://example.com/blog/a", Some("2023-12-31"))));
+        assert!(!sitemap.matches(&entry("https://example.com/blog/drafts/a", None)));
+        assert!(!sitemap.matches(&entry("https://example.com/about", None)));
+    }
+
+    #[tokio::test]
+    async fn follows_index_to_same_host_only() {
+        let index = r#"<sitemapindex>
+            <sitemap><loc>http://127.0.0.1:{port}/a.xml</loc></sitemap>
+            <sitemap><loc>http://localhost:{port}/b.xml</loc></sitemap>
+        </sitemapindex>"#;
+        let port = serve(HashMap::from([
+            ("/index.xml", index.to_string()),
+            ("/a.xml", urlset(&["https://example.com/a"])),
+            ("/b.xml", urlset(&["https://example.com/b"])),
+        ]));
+
+        let sitemap = Sitemap::builder(format!("http://127.0.0.1:{}/index.xml", port).as_str())
+            .build()
+            .unwrap();
+        let entries = sitemap.entries().await.unwrap();
+        assert_eq!(urls(&entries), ["https://example.com/a"]);
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_sitemap() {
+        let body = " ".repeat(MAX_SITEMAP_BYTES as usize + 1);
+        let port = serve(HashMap::from([("/sitemap.xml", body)]));
+
+        let sitemap = Sitemap::builder(format!("http://127.0.0.1:{}/sitemap.xml", port).as_str())
+            .build()
+            .unwrap();
+        let error = sitemap.entries().await.unwrap_err();
+        assert!(error.to_string().contains("larger than 50 MB"), "{}", error);
+    }
+}
@@ -0,0 +1,564 @@
+//! Visual regression testing against screenshots stored in the repository.
+//!
+//! Requires the `image` feature. [`assert_screenshot!`](crate::assert_screenshot)
+//! captures a page and compares it with a baseline image under
+//! `tests/snapshots` in the crate being tested:
+//!
+//! ```no_run
+//! use pxshot::{assert_screenshot, Pxshot, ScreenshotRequest};
+//!
+//! # async fn run() -> pxshot::Result<()> {
+//! // In an async test, e.g. with #[tokio::test]:
+//! let client = Pxshot::new(std::env::var("PXSHOT_API_KEY").unwrap());
+//! let request = ScreenshotRequest::builder()
+//!     .url("https://staging.example.com")
+//!     .width(1280)
+//!     .height(800)
+//!     .build()?;
+//!
+//! assert_screenshot!(&client, "home", request);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Workflow
+//!
+//! Baselines are stored as `<name>.png`. When a capture does not match, the
+//! assertion fails and writes `<name>.new.png` and a highlighted
+//! `<name>.diff.png` next to the baseline (see [`diff`](crate::diff)). A
+//! missing baseline also fails and writes `<name>.new.png`.
+//!
+//! To review, look at the pending `.new.png` and `.diff.png` files, then
+//! either accept them with [`accept_pending`] or run the tests again with the
+//! `PXSHOT_SNAPSHOT` environment variable set:
+//!
+//! - `compare` (default): fail on mismatches and missing baselines.
+//! - `new`: write missing baselines, but fail on mismatches.
+//! - `accept`: write every capture that differs as the new baseline.
+//!
+//! Commit the `.png` baselines and ignore `*.new.png` and `*.diff.png`.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use image::DynamicImage;
+
+use crate::client::Pxshot;
+use crate::diff::{self, DiffOptions, DiffResult};
+use crate::error::{Error, Result};
+use crate::imaging;
+use crate::similarity;
+use crate::traits::ScreenshotClient;
+use crate::types::{ImageFormat, ScreenshotRequest};
+
+/// Environment variable selecting the [`SnapshotMode`].
+pub const MODE_ENV: &str = "PXSHOT_SNAPSHOT";
+
+const DEFAULT_DIR: &str = "tests/snapshots";
+const NEW_SUFFIX: &str = ".new.png";
+const DIFF_SUFFIX: &str = ".diff.png";
+
+/// What to do when a capture does not match its baseline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SnapshotMode {
+    /// Fail on mismatches and missing baselines, writing the capture next to
+    /// the baseline for review.
+    #[default]
+    Compare,
+    /// Write missing baselines; fail on mismatches.
+    New,
+    /// Write every capture that differs as the new baseline.
+    Accept,
+}
+
+impl SnapshotMode {
+    /// Read the mode from the `PXSHOT_SNAPSHOT` environment variable,
+    /// defaulting to [`SnapshotMode::Compare`].
+    pub fn from_env() -> Result<Self> {
+        match std::env::var(MODE_ENV) {
+            Ok(value) => value.parse(),
+            Err(std::env::VarError::NotPresent) => Ok(Self::default()),
+            Err(e) => Err(Error::Config(format!("invalid {}: {}", MODE_ENV, e))),
+        }
+    }
+}
+
+impl std::str::FromStr for SnapshotMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "compare" => Ok(Self::Compare),
+            "new" => Ok(Self::New),
+            "accept" => Ok(Self::Accept),
+            other => Err(Error::Config(format!(
+                "invalid {} `{}`; expected compare, new or accept",
+                MODE_ENV, other
+            ))),
+        }
+    }
+}
+
+/// The result of checking a capture against its baseline.
+#[derive(Debug)]
+pub enum SnapshotOutcome {
+    /// The capture matches the baseline.
+    Matched(DiffResult),
+    /// There was no baseline; the capture was written as the baseline.
+    Created,
+    /// The capture differed and was written as the new baseline.
+    Updated(DiffResult),
+    /// The capture differs from the baseline.
+    Mismatch {
+        /// The comparison with the baseline.
+        diff: DiffResult,
+        /// Where the capture was written.
+        new_path: PathBuf,
+        /// Where the diff image was written.
+        diff_path: PathBuf,
+    },
+    /// There is no baseline.
+    Missing {
+        /// Where the capture was written.
+        new_path: PathBuf,
+    },
+}
+
+impl SnapshotOutcome {
+    /// Whether the check passed, i.e. the capture matched or the baseline
+    /// was written.
+    pub fn is_pass(&self) -> bool {
+        matches!(self, Self::Matched(_) | Self::Created | Self::Updated(_))
+    }
+}
+
+/// A directory of baseline screenshots.
+///
+/// Use [`Snapshots::check`] to compare without panicking, e.g. to collect
+/// every mismatch in a page before failing.
+///
+/// # Example
+///
+/// ```no_run
+/// use pxshot::diff::{DiffOptions, Region};
+/// use pxshot::snapshot::Snapshots;
+/// use pxshot::{Pxshot, ScreenshotRequest};
+///
+/// # async fn run() -> pxshot::Result<()> {
+/// let snapshots = Snapshots::builder("tests/visual")
+///     .diff_options(DiffOptions::new().tolerance(16).ignore(Region::new(0, 0, 1280, 64)))
+///     .threshold(0.1)
+///     .build()?;
+///
+/// let client = Pxshot::new("px_your_api_key");
+/// let request = ScreenshotRequest::builder().url("https://staging.example.com").build()?;
+/// snapshots.assert(&client, "home", request).await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Snapshots {
+    dir: PathBuf,
+    mode: SnapshotMode,
+    options: DiffOptions,
+    threshold: f64,
+    min_ssim: Option<f64>,
+}
+
+impl Snapshots {
+    /// Create a new builder storing baselines in `dir`.
+    pub fn builder(dir: impl Into<PathBuf>) -> SnapshotsBuilder {
+        SnapshotsBuilder {
+            dir: dir.into(),
+            mode: None,
+            options: DiffOptions::default(),
+            threshold: 0.0,
+            min_ssim: None,
+        }
+    }
+
+    /// Baselines in `tests/snapshots` under `manifest_dir`, with the mode
+    /// from the environment. Used by [`assert_screenshot!`](crate::assert_screenshot).
+    pub fn for_crate(manifest_dir: impl AsRef<Path>) -> Result<Self> {
+        Self::builder(manifest_dir.as_ref().join(DEFAULT_DIR)).build()
+    }
+
+    /// The directory holding the baselines.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The mode in use.
+    pub fn mode(&self) -> SnapshotMode {
+        self.mode
+    }
+
+    /// Path of the baseline for `name`.
+    pub fn baseline_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.png", name))
+    }
+
+    /// Capture `request` and check it against the baseline `name`.
+    ///
+    /// Names may contain `/` to group baselines in subdirectories. Fails if
+    /// the name is invalid, the capture fails, or `request` sets `store`.
+    pub async fn check<C>(
+        &self,
+        client: &C,
+        name: &str,
+        request: ScreenshotRequest,
+    ) -> Result<SnapshotOutcome>
+    where
+        C: ScreenshotClient + ?Sized,
+    {
+        check_name(name)?;
+        let response = client.screenshot(request).await?;
+        let bytes = response.bytes().ok_or_else(|| {
+            Error::Image("cannot snapshot a stored screenshot; capture without `store`".into())
+        })?;
+        self.check_bytes(name, bytes)
+    }
+
+    /// Check an already captured screenshot against the baseline `name`.
+    pub fn check_bytes(&self, name: &str, bytes: &[u8]) -> Result<SnapshotOutcome> {
+        check_name(name)?;
+        let baseline_path = self.baseline_path(name);
+        let new_path = self.dir.join(format!("{}{}", name, NEW_SUFFIX));
+        let diff_path = self.dir.join(format!("{}{}", name, DIFF_SUFFIX));
+
+        let baseline = match fs::read(&baseline_path) {
+            Ok(baseline) => Some(baseline),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        let Some(baseline) = baseline else {
+            let png = to_png(bytes)?;
+            if self.mode == SnapshotMode::Compare {
+                write(&new_path, &png)?;
+                return Ok(SnapshotOutcome::Missing { new_path });
+            }
+            write(&baseline_path, &png)?;
+            remove_pending(&new_path, &diff_path)?;
+            return Ok(SnapshotOutcome::Created);
+        };
+
+        let baseline = diff::decode(&baseline, "baseline")?;
+        let current = diff::decode(bytes, "captured")?;
+        let result = diff::compare_images(&baseline, &current, &self.options);
+        let matches = !result.dimensions_differ
+            && (result.within(self.threshold) || self.similar(&baseline, &current)?);
+        if matches {
+            remove_pending(&new_path, &diff_path)?;
+            return Ok(SnapshotOutcome::Matched(result));
+        }
+
+        let png = to_png(bytes)?;
+        if self.mode == SnapshotMode::Accept {
+            write(&baseline_path, &png)?;
+            remove_pending(&new_path, &diff_path)?;
+            return Ok(SnapshotOutcome::Updated(result));
+        }
+        write(&new_path, &png)?;
+        result.save_image(&diff_path)?;
+        Ok(SnapshotOutcome::Mismatch {
+            diff: result,
+            new_path,
+            diff_path,
+        })
+    }
+
+    fn similar(&self, baseline: &DynamicImage, current: &DynamicImage) -> Result<bool> {
+        match self.min_ssim {
+            Some(min_ssim) => Ok(similarity::ssim_images(baseline, current)? >= min_ssim),
+            None => Ok(false),
+        }
+    }
+
+    /// Capture `request` and assert that it matches the baseline `name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics with a description of the difference if the check does not
+    /// pass or fails with an error.
+    pub async fn assert<C>(&self, client: &C, name: &str, request: ScreenshotRequest)
+    where
+        C: ScreenshotClient + ?Sized,
+    {
+        match self.check(client, name, request).await {
+            Ok(outcome) if outcome.is_pass() => {}
+            Ok(outcome) => panic!("{}", Failure { name, outcome }),
+            Err(e) => panic!("screenshot `{}` could not be checked: {}", name, e),
+        }
+    }
+}
+
+struct Failure<'a> {
+    name: &'a str,
+    outcome: SnapshotOutcome,
+}
+
+impl fmt::Display for Failure<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.outcome {
+            SnapshotOutcome::Missing { new_path } => {
+                writeln!(f, "screenshot `{}` has no baseline", self.name)?;
+                writeln!(f, "  new: {}", new_path.display())?;
+            }
+            SnapshotOutcome::Mismatch {
+                diff,
+                new_path,
+                diff_path,
+            } => {
+                if diff.dimensions_differ {
+                    writeln!(
+                        f,
+                        "screenshot `{}` does not match its baseline: dimensions differ",
+                        self.name
+                    )?;
+                } else {
+                    writeln!(
+                        f,
+                        "screenshot `{}` does not match its baseline: {:.3}% of pixels changed in {} region{}",
+                        self.name,
+                        diff.percentage(),
+                        diff.regions.len(),
+                        if diff.regions.len() == 1 { "" } else { "s" }
+                    )?;
+                }
+                writeln!(f, "  new:  {}", new_path.display())?;
+                writeln!(f, "  diff: {}", diff_path.display())?;
+            }
+            _ => {}
+        }
+        write!(
+            f,
+            "review the new screenshot, then run with {}=accept or call \
+             pxshot::snapshot::accept_pending to accept it",
+            MODE_ENV
+        )
+    }
+}
+
+/// Builder for [`Snapshots`].
+#[derive(Debug)]
+pub struct SnapshotsBuilder {
+    dir: PathBuf,
+    mode: Option<SnapshotMode>,
+    options: DiffOptions,
+    threshold: f64,
+    min_ssim: Option<f64>,
+}
+
+impl SnapshotsBuilder {
+    /// Set the mode (default: read from `PXSHOT_SNAPSHOT`).
+    pub fn mode(mut self, mode: SnapshotMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Set the options for comparing captures with baselines.
+    pub fn diff_options(mut self, options: DiffOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Percentage of pixels (0-100) allowed to change before a capture no
+    /// longer matches (default: 0).
+    pub fn threshold(mut self, percentage: f64) -> Self {
+        self.threshold = percentage;
+        self
+    }
+
+    /// Also accept captures whose [SSIM](crate::similarity::ssim) with the
+    /// baseline is at least `score` (0-1), however many pixels changed.
+    ///
+    /// Useful when font rendering differs slightly between environments;
+    /// a score around 0.98 tolerates such noise but catches layout changes.
+    pub fn min_ssim(mut self, score: f64) -> Self {
+        self.min_ssim = Some(score);
+        self
+    }
+
+    /// Build the snapshot directory.
+    pub fn build(self) -> Result<Snapshots> {
+        if !(0.0..=100.0).contains(&self.threshold) {
+            return Err(Error::Config(format!(
+                "snapshot threshold must be between 0 and 100, got {}",
+                self.threshold
+            )));
+        }
+        if let Some(score) = self.min_ssim.filter(|score| !(0.0..=1.0).contains(score)) {
+            return Err(Error::Config(format!(
+                "snapshot min_ssim must be between 0 and 1, got {}",
+                score
+            )));
+        }
+        let mode = match self.mode {
+            Some(mode) => mode,
+            None => SnapshotMode::from_env()?,
+        };
+        Ok(Snapshots {
+            dir: self.dir,
+            mode,
+            options: self.options,
+            threshold: self.threshold,
+            min_ssim: self.min_ssim,
+        })
+    }
+}
+
+/// Pending captures (`*.new.png`) under `dir`, in sorted order.
+pub fn pending(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    collect_pending(dir.as_ref(), &mut found)?;
+    found.sort();
+    Ok(found)
+}
+
+/// Accept every pending capture under `dir` as its baseline, removing the
+/// diff images. Returns the baselines that were written.
+pub fn accept_pending(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let mut accepted = Vec::new();
+    for new_path in pending(dir)? {
+        let (baseline_path, diff_path) = pending_paths(&new_path);
+        fs::rename(&new_path, &baseline_path)?;
+        remove_if_exists(&diff_path)?;
+        accepted.push(baseline_path);
+    }
+    Ok(accepted)
+}
+
+/// Discard every pending capture and diff image under `dir`.
+pub fn reject_pending(dir: impl AsRef<Path>) -> Result<()> {
+    for new_path in pending(dir)? {
+        let (_, diff_path) = pending_paths(&new_path);
+        remove_pending(&new_path, &diff_path)?;
+    }
+    Ok(())
+}
+
+/// A client for [`assert_screenshot!`](crate::assert_screenshot) calls
+/// without one, using `PXSHOT_API_KEY` and, if set, `PXSHOT_BASE_URL`.
+///
+/// # Panics
+///
+/// Panics if `PXSHOT_API_KEY` is not set.
+pub fn client_from_env() -> Pxshot {
+    let api_key = std::env::var("PXSHOT_API_KEY")
+        .expect("PXSHOT_API_KEY must be set to capture screenshots in tests");
+    match std::env::var("PXSHOT_BASE_URL") {
+        Ok(base_url) => Pxshot::with_base_url(api_key, base_url),
+        Err(_) => Pxshot::new(api_key),
+    }
+}
+
+/// Capture a screenshot and assert that it matches a baseline in
+/// `tests/snapshots`.
+///
+/// Must be used in an async context. Takes an optional client, which
+/// defaults to [`snapshot::client_from_env`](crate::snapshot::client_from_env),
+/// the baseline name and the [`ScreenshotRequest`](crate::ScreenshotRequest).
+/// See the [`snapshot`](crate::snapshot) module for the review workflow.
+///
+/// ```no_run
+/// use pxshot::{assert_screenshot, ScreenshotRequest};
+///
+/// # async fn run(client: pxshot::testing::MockClient) -> pxshot::Result<()> {
+/// let request = ScreenshotRequest::builder().url("https://staging.example.com").build()?;
+/// assert_screenshot!(&client, "home", request.clone());
+///
+/// // With a client from PXSHOT_API_KEY
+/// assert_screenshot!("home", request);
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+#[cfg_attr(docsrs, doc(cfg(feature = "image")))]
+macro_rules! assert_screenshot {
+    ($name:expr, $request:expr $(,)?) => {
+        $crate::assert_screenshot!(&$crate::snapshot::client_from_env(), $name, $request)
+    };
+    ($client:expr, $name:expr, $request:expr $(,)?) => {
+        match $crate::snapshot::Snapshots::for_crate(env!("CARGO_MANIFEST_DIR")) {
+            Ok(snapshots) => snapshots.assert($client, $name, $request).await,
+            Err(e) => panic!("{}", e),
+        }
+    };
+}
+
+/// Reject names that would escape the snapshot directory.
+fn check_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.ends_with('/')
+        && Path::new(name)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Config(format!("invalid snapshot name `{}`", name)))
+    }
+}
+
+/// Baselines are stored as PNG so they are lossless and diffable.
+fn to_png(bytes: &[u8]) -> Result<Vec<u8>> {
+    match ImageFormat::detect(bytes) {
+        Some(ImageFormat::Png) => Ok(bytes.to_vec()),
+        Some(format) => imaging::encode(&imaging::decode(bytes, format)?, ImageFormat::Png, None),
+        None => Err(Error::Image("unrecognized image format".into())),
+    }
+}
+
+fn write(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+fn remove_pending(new_path: &Path, diff_path: &Path) -> Result<()> {
+    remove_if_exists(new_path)?;
+    remove_if_exists(diff_path)
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn collect_pending(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_pending(&path, found)?;
+        } else if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(NEW_SUFFIX))
+        {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The baseline and diff paths belonging to a pending capture.
+fn pending_paths(new_path: &Path) -> (PathBuf, PathBuf) {
+    let name = new_path.to_string_lossy();
+    let stem = name.strip_suffix(NEW_SUFFIX).unwrap_or(&name);
+    (
+        PathBuf::from(format!("{}.png", stem)),
+        PathBuf::from(format!("{}{}", stem, DIFF_SUFFIX)),
+    )
+}
@@ -0,0 +1,321 @@
+//! Instrumentation hooks for the request path.
+//!
+//! With the `tracing` feature enabled, requests are wrapped in `tracing`
+//! spans. With the `metrics` feature enabled, counters and histograms are
+//! recorded through the `metrics` facade. Without either, everything here
+//! compiles to no-ops.
+//!
+//! Only the target host is ever recorded; the API key, target paths, query
+//! strings and credentials embedded in target URLs are not.
+
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::types::{ScreenshotRequest, ScreenshotResponse};
+
+#[cfg(feature = "tracing")]
+pub(crate) use enabled::*;
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use disabled::*;
+
+/// Total screenshot requests, labelled by `outcome`, `status`, `format` and `host`.
+#[cfg(feature = "metrics")]
+const REQUESTS_TOTAL: &str = "pxshot_screenshot_requests_total";
+/// Screenshot request duration, labelled by `outcome` and `format`.
+#[cfg(feature = "metrics")]
+const REQUEST_DURATION: &str = "pxshot_screenshot_duration_seconds";
+/// Image bytes downloaded, labelled by `format` and `host`.
+#[cfg(feature = "metrics")]
+const DOWNLOADED_BYTES: &str = "pxshot_downloaded_bytes_total";
+/// Requests retried after a `429` response.
+#[cfg(feature = "metrics")]
+const RETRIES_TOTAL: &str = "pxshot_retries_total";
+/// Time spent queued in the rate limiter.
+#[cfg(feature = "metrics")]
+const RATE_LIMIT_WAIT: &str = "pxshot_rate_limiter_wait_seconds";
+/// Requests answered from a cassette instead of the API.
+#[cfg(feature = "metrics")]
+const CACHE_HITS: &str = "pxshot_cache_hits_total";
+
+/// Host of a capture target, without credentials, path or query.
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+pub(crate) fn target_host(url: &url::Url) -> Option<String> {
+    url.host_str().map(str::to_string)
+}
+
+/// Short label describing how a request ended.
+#[cfg(feature = "metrics")]
+fn outcome<T>(result: &Result<T>) -> &'static str {
+    match result {
+        Ok(_) => "success",
+        Err(crate::error::Error::Api { .. }) => "api_error",
+        Err(crate::error::Error::Request(_)) => "request_error",
+        Err(crate::error::Error::Parse(_)) => "parse_error",
+        Err(crate::error::Error::BudgetExceeded { .. }) => "budget_exceeded",
+        Err(_) => "error",
+    }
+}
+
+/// Record a completed screenshot request.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn screenshot_completed(
+    request: &ScreenshotRequest,
+    status: Option<u16>,
+    latency: Duration,
+    result: &Result<ScreenshotResponse>,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        describe_metrics();
+
+        let outcome = outcome(result);
+        let format = request.format.unwrap_or_default().as_str();
+        let host = target_host(&request.url).unwrap_or_default();
+        let status = status.map(|status| status.to_string()).unwrap_or_default();
+
+        metrics::counter!(
+            REQUESTS_TOTAL,
+            "outcome" => outcome,
+            "status" => status,
+            "format" => format,
+            "host" => host.clone(),
+        )
+        .increment(1);
+        metrics::histogram!(REQUEST_DURATION, "outcome" => outcome, "format" => format)
+            .record(latency.as_secs_f64());
+
+        if let Ok(ScreenshotResponse::Bytes(bytes)) = result {
+            metrics::counter!(DOWNLOADED_BYTES, "format" => format, "host" => host)
+                .increment(bytes.len() as u64);
+        }
+    }
+}
+
+/// Note that a throttled request will be retried after `delay`.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn retry(attempt: u32, delay: Duration) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        attempt,
+        delay_ms = delay.as_millis() as u64,
+        "request throttled, retrying"
+    );
+
+    #[cfg(feature = "metrics")]
+    {
+        describe_metrics();
+        metrics::counter!(RETRIES_TOTAL).increment(1);
+    }
+}
+
+/// Record time spent waiting for the rate limiter.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn rate_limit_wait(waited: Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        describe_metrics();
+        metrics::histogram!(RATE_LIMIT_WAIT).record(waited.as_secs_f64());
+    }
+}
+
+/// Record a request answered from a cassette.
+pub(crate) fn cache_hit() {
+    #[cfg(feature = "metrics")]
+    {
+        describe_metrics();
+        metrics::counter!(CACHE_HITS).increment(1);
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn describe_metrics() {
+    static DESCRIBED: std::sync::Once = std::sync::Once::new();
+
+    DESCRIBED.call_once(|| {
+        metrics::describe_counter!(REQUESTS_TOTAL, "Total Pxshot screenshot requests.");
+        metrics::describe_histogram!(
+            REQUEST_DURATION,
+            metrics::Unit::Seconds,
+            "Duration of Pxshot screenshot requests."
+        );
+        metrics::describe_counter!(
+            DOWNLOADED_BYTES,
+            metrics::Unit::Bytes,
+            "Image bytes downloaded from Pxshot."
+        );
+        metrics::describe_counter!(RETRIES_TOTAL, "Pxshot requests retried after a 429.");
+        metrics::describe_histogram!(
+            RATE_LIMIT_WAIT,
+            metrics::Unit::Seconds,
+            "Time spent queued in the Pxshot rate limiter."
+        );
+        metrics::describe_counter!(CACHE_HITS, "Pxshot requests answered from a cassette.");
+    });
+}
+
+#[cfg(feature = "tracing")]
+mod enabled {
+    use std::collections::hash_map::RandomState;
+    use std::future::Future;
+    use std::hash::{BuildHasher, Hasher};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+
+    use tracing::field::Empty;
+    use tracing::Instrument;
+
+    use crate::error::Result;
+    use crate::types::ScreenshotRequest;
+
+    /// A span covering one SDK call or one attempt of it.
+    #[derive(Debug, Clone)]
+    pub(crate) struct Span(tracing::Span);
+
+    impl Span {
+        pub(crate) fn screenshot(request: &ScreenshotRequest) -> Self {
+            let host = super::target_host(&request.url);
+            Self(tracing::info_span!(
+                "pxshot.screenshot",
+                host = host.as_deref().unwrap_or(""),
+                format = request.format.unwrap_or_default().as_str(),
+                full_page = request.full_page.unwrap_or(false),
+                store = request.store.unwrap_or(false),
+                status = Empty,
+                bytes = Empty,
+                latency_ms = Empty,
+                trace_id = Empty,
+                error = Empty,
+            ))
+        }
+
+        pub(crate) fn usage(endpoint: &'static str) -> Self {
+            Self(tracing::info_span!(
+                "pxshot.usage",
+                endpoint,
+                status = Empty,
+                latency_ms = Empty,
+                trace_id = Empty,
+                error = Empty,
+            ))
+        }
+
+        pub(crate) fn attempt(attempt: u32) -> Self {
+            Self(tracing::debug_span!(
+                "pxshot.attempt",
+                attempt,
+                status = Empty,
+                latency_ms = Empty,
+            ))
+        }
+
+        pub(crate) fn record_status(&self, status: u16) {
+            self.0.record("status", status);
+        }
+
+        pub(crate) fn record_bytes(&self, bytes: u64) {
+            self.0.record("bytes", bytes);
+        }
+
+        pub(crate) fn record_latency(&self, latency: Duration) {
+            self.0.record("latency_ms", latency.as_millis() as u64);
+        }
+
+        pub(crate) fn record_trace_id(&self, trace_id: &str) {
+            self.0.record("trace_id", trace_id);
+        }
+
+        pub(crate) fn record_result<T>(&self, result: &Result<T>, latency: Duration) {
+            self.record_latency(latency);
+            if let Err(e) = result {
+                self.0.record("error", tracing::field::display(e));
+            }
+        }
+
+        pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+            future.instrument(self.0.clone())
+        }
+
+        #[cfg(feature = "blocking")]
+        pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+            self.0.in_scope(f)
+        }
+    }
+
+    /// W3C trace context for the attempts of one SDK call.
+    #[derive(Debug)]
+    pub(crate) struct TraceContext {
+        trace_id: String,
+    }
+
+    impl TraceContext {
+        pub(crate) fn new() -> Self {
+            Self {
+                trace_id: format!("{:016x}{:016x}", random_u64(), random_u64()),
+            }
+        }
+
+        pub(crate) fn trace_id(&self) -> &str {
+            &self.trace_id
+        }
+
+        /// A `traceparent` header value with a fresh parent span ID.
+        pub(crate) fn traceparent(&self) -> String {
+            format!("00-{}-{:016x}-01", self.trace_id, random_u64())
+        }
+    }
+
+    fn random_u64() -> u64 {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        match hasher.finish() {
+            0 => 1,
+            value => value,
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+mod disabled {
+    use std::future::Future;
+    use std::time::Duration;
+
+    use crate::error::Result;
+    use crate::types::ScreenshotRequest;
+
+    #[derive(Debug, Clone)]
+    pub(crate) struct Span;
+
+    impl Span {
+        pub(crate) fn screenshot(_request: &ScreenshotRequest) -> Self {
+            Self
+        }
+
+        pub(crate) fn usage(_endpoint: &'static str) -> Self {
+            Self
+        }
+
+        pub(crate) fn attempt(_attempt: u32) -> Self {
+            Self
+        }
+
+        pub(crate) fn record_status(&self, _status: u16) {}
+
+        pub(crate) fn record_bytes(&self, _bytes: u64) {}
+
+        pub(crate) fn record_latency(&self, _latency: Duration) {}
+
+        pub(crate) fn record_result<T>(&self, _result: &Result<T>, _latency: Duration) {}
+
+        pub(crate) fn instrument<F: Future>(&self, future: F) -> F {
+            future
+        }
+
+        #[cfg(feature = "blocking")]
+        pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+            f()
+        }
+    }
+}
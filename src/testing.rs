@@ -0,0 +1,173 @@
+//! Test doubles for code that depends on Pxshot.
+//!
+//! [`MockClient`] scripts responses for unit tests. With the `test-server`
+//! feature, [`FakeServer`] runs a local fake of the API for integration
+//! tests.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+
+use crate::error::Result;
+use crate::traits::ScreenshotClient;
+use crate::types::{ScreenshotRequest, ScreenshotResponse, Usage};
+
+#[cfg(feature = "test-server")]
+mod fake_server;
+
+#[cfg(feature = "test-server")]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-server")))]
+pub use fake_server::{FakeServer, Fault};
+
+type Responder = Box<dyn Fn(&ScreenshotRequest) -> Result<ScreenshotResponse> + Send + Sync>;
+
+/// A scripted [`ScreenshotClient`] for unit tests.
+///
+/// Responses and errors pushed onto the mock are returned in order. Once the
+/// script runs out, the responder set with [`MockClient::respond_with`] is
+/// used; without one, the call panics. Every screenshot request received is
+/// recorded for later assertions.
+///
+/// Cloning a mock is cheap and every clone shares the same script and
+/// recorded requests, so a clone can be handed to the code under test.
+///
+/// # Example
+///
+/// ```
+/// use pxshot::testing::MockClient;
+/// use pxshot::{Error, ScreenshotClient, ScreenshotRequest, ScreenshotResponse};
+///
+/// # #[tokio::main]
+/// # async fn main() -> pxshot::Result<()> {
+/// let mock = MockClient::new();
+/// mock.push_screenshot(ScreenshotResponse::Bytes(vec![0x89, b'P', b'N', b'G']));
+/// mock.push_screenshot_error(Error::Api {
+///     status: 500,
+///     message: "internal error".into(),
+/// });
+///
+/// let request = ScreenshotRequest::builder().url("https://example.com").build()?;
+/// assert!(mock.screenshot(request.clone()).await.is_ok());
+/// assert!(mock.screenshot(request).await.is_err());
+///
+/// assert_eq!(mock.requests().len(), 2);
+/// assert_eq!(mock.last_request().unwrap().url.as_str(), "https://example.com/");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct MockClient {
+    inner: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    screenshots: VecDeque<Result<ScreenshotResponse>>,
+    usage: VecDeque<Result<Usage>>,
+    responder: Option<Responder>,
+    requests: Vec<ScreenshotRequest>,
+    usage_calls: usize,
+}
+
+impl MockClient {
+    /// Create a mock with an empty script.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a successful screenshot response.
+    pub fn push_screenshot(&self, response: ScreenshotResponse) -> &Self {
+        self.lock().screenshots.push_back(Ok(response));
+        self
+    }
+
+    /// Queue a screenshot error.
+    pub fn push_screenshot_error(&self, error: crate::Error) -> &Self {
+        self.lock().screenshots.push_back(Err(error));
+        self
+    }
+
+    /// Queue a successful usage response.
+    pub fn push_usage(&self, usage: Usage) -> &Self {
+        self.lock().usage.push_back(Ok(usage));
+        self
+    }
+
+    /// Queue a usage error.
+    pub fn push_usage_error(&self, error: crate::Error) -> &Self {
+        self.lock().usage.push_back(Err(error));
+        self
+    }
+
+    /// Answer screenshot requests with `responder` once the script runs out.
+    pub fn respond_with(
+        &self,
+        responder: impl Fn(&ScreenshotRequest) -> Result<ScreenshotResponse> + Send + Sync + 'static,
+    ) -> &Self {
+        self.lock().responder = Some(Box::new(responder));
+        self
+    }
+
+    /// All screenshot requests received, in order.
+    pub fn requests(&self) -> Vec<ScreenshotRequest> {
+        self.lock().requests.clone()
+    }
+
+    /// The most recent screenshot request received.
+    pub fn last_request(&self) -> Option<ScreenshotRequest> {
+        self.lock().requests.last().cloned()
+    }
+
+    /// Number of usage calls received.
+    pub fn usage_calls(&self) -> usize {
+        self.lock().usage_calls
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for MockClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("MockClient")
+            .field("scripted_screenshots", &state.screenshots.len())
+            .field("scripted_usage", &state.usage.len())
+            .field("has_responder", &state.responder.is_some())
+            .field("requests", &state.requests)
+            .field("usage_calls", &state.usage_calls)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl ScreenshotClient for MockClient {
+    async fn screenshot(&self, request: ScreenshotRequest) -> Result<ScreenshotResponse> {
+        let mut state = self.lock();
+        state.requests.push(request.clone());
+
+        if let Some(result) = state.screenshots.pop_front() {
+            return result;
+        }
+        match &state.responder {
+            Some(responder) => responder(&request),
+            None => panic!(
+                "MockClient received an unexpected screenshot request for {}",
+                request.url
+            ),
+        }
+    }
+
+    async fn usage(&self) -> Result<Usage> {
+        let mut state = self.lock();
+        state.usage_calls += 1;
+
+        match state.usage.pop_front() {
+            Some(result) => result,
+            None => panic!("MockClient received an unexpected usage request"),
+        }
+    }
+}
@@ -0,0 +1,488 @@
+//! Local fake of the Pxshot API.
+
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{ExtendedColorType, ImageEncoder, Rgb, RgbImage};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use crate::client::Pxshot;
+use crate::error::Result;
+use crate::types::{ImageFormat, Usage};
+
+const DEFAULT_WIDTH: u32 = 1280;
+const DEFAULT_HEIGHT: u32 = 720;
+const DEFAULT_JPEG_QUALITY: u8 = 80;
+const MAX_DIMENSION: u32 = 16_383;
+const STORED_TTL_HOURS: i64 = 24;
+const PERIOD_DAYS: i64 = 30;
+
+/// A scripted failure returned by [`FakeServer`] instead of a normal response.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// Respond with this status code and error message.
+    Status {
+        /// HTTP status code.
+        status: u16,
+        /// Error message in the response body.
+        message: String,
+    },
+
+    /// Respond with `429 Too Many Requests` and a `Retry-After` header.
+    RateLimited {
+        /// Delay advertised in `Retry-After` (rounded up to whole seconds).
+        retry_after: Duration,
+    },
+}
+
+/// A local HTTP server implementing the Pxshot API for integration tests.
+///
+/// The server binds to an ephemeral port on localhost and implements
+/// `/v1/screenshot` and `/v1/usage` with the real wire format:
+///
+/// - Screenshots are placeholder PNG, JPEG or WebP images of the requested
+///   viewport size (multiplied by `device_scale_factor`). The pattern is
+///   derived from the target URL, so different URLs give different images.
+/// - With `store: true`, a stored-screenshot JSON body is returned, and the
+///   image is served from its `url` until the server is dropped.
+/// - Usage counts every successful capture.
+///
+/// Latency, `429` responses and other errors can be scripted to test error
+/// handling. Requests without a bearer token are rejected with `401`.
+///
+/// The server shuts down when dropped. Requires the `test-server` feature.
+///
+/// # Example
+///
+/// ```
+/// use pxshot::testing::FakeServer;
+/// use pxshot::{ImageFormat, ScreenshotRequest};
+///
+/// # #[tokio::main]
+/// # async fn main() -> pxshot::Result<()> {
+/// let server = FakeServer::start().await?;
+/// let client = server.client();
+///
+/// let response = client
+///     .screenshot(
+///         ScreenshotRequest::builder()
+///             .url("https://example.com")
+///             .format(ImageFormat::Png)
+///             .width(320)
+///             .height(240)
+///             .build()?,
+///     )
+///     .await?;
+/// assert!(response.bytes().unwrap().starts_with(b"\x89PNG"));
+///
+/// server.fail_next(503);
+/// assert!(client.usage().await.is_err());
+/// assert_eq!(client.usage().await?.screenshots, 1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FakeServer {
+    addr: SocketAddr,
+    state: Arc<Shared>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    base_url: String,
+    next_id: AtomicU64,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    latency: Duration,
+    faults: VecDeque<Fault>,
+    requests: Vec<serde_json::Value>,
+    stored: HashMap<String, (ImageFormat, Bytes)>,
+    screenshots: u64,
+    bytes: u64,
+    period_start: DateTime<Utc>,
+}
+
+/// Subset of the screenshot request the fake needs to honour.
+#[derive(Debug, Deserialize)]
+struct WireRequest {
+    url: String,
+    #[serde(default)]
+    format: Option<ImageFormat>,
+    #[serde(default)]
+    quality: Option<u8>,
+    #[serde(default)]
+    width: Option<u32>,
+    #[serde(default)]
+    height: Option<u32>,
+    #[serde(default)]
+    device_scale_factor: Option<f32>,
+    #[serde(default)]
+    store: Option<bool>,
+}
+
+type HttpResponse = Response<Full<Bytes>>;
+
+impl FakeServer {
+    /// Start a server on an ephemeral localhost port.
+    pub async fn start() -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+
+        let state = Arc::new(Shared {
+            base_url: format!("http://{}", addr),
+            next_id: AtomicU64::new(1),
+            state: Mutex::new(State {
+                latency: Duration::ZERO,
+                faults: VecDeque::new(),
+                requests: Vec::new(),
+                stored: HashMap::new(),
+                screenshots: 0,
+                bytes: 0,
+                period_start: Utc::now(),
+            }),
+        });
+
+        let task = tokio::spawn(serve(listener, state.clone()));
+
+        Ok(Self { addr, state, task })
+    }
+
+    /// Base URL of the server, for [`Pxshot::with_base_url`].
+    pub fn url(&self) -> String {
+        self.state.base_url.clone()
+    }
+
+    /// Address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// A client pointed at this server.
+    pub fn client(&self) -> Pxshot {
+        Pxshot::with_base_url("px_test_key", self.url())
+    }
+
+    /// Delay every response by `latency`.
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.lock().latency = latency;
+    }
+
+    /// Answer the next API request with `fault`.
+    ///
+    /// Faults are consumed in the order they were pushed.
+    pub fn push_fault(&self, fault: Fault) {
+        self.state.lock().faults.push_back(fault);
+    }
+
+    /// Answer the next API request with an error of the given status.
+    pub fn fail_next(&self, status: u16) {
+        let message = StatusCode::from_u16(status)
+            .ok()
+            .and_then(|status| status.canonical_reason())
+            .unwrap_or("error")
+            .to_string();
+        self.push_fault(Fault::Status { status, message });
+    }
+
+    /// Answer the next API request with `429 Too Many Requests`.
+    pub fn rate_limit_next(&self, retry_after: Duration) {
+        self.push_fault(Fault::RateLimited { retry_after });
+    }
+
+    /// JSON bodies of all screenshot requests received, in order.
+    pub fn requests(&self) -> Vec<serde_json::Value> {
+        self.state.lock().requests.clone()
+    }
+
+    /// Usage recorded so far, as returned by `/v1/usage`.
+    pub fn usage(&self) -> Usage {
+        self.state.lock().usage()
+    }
+
+    /// Reset usage counters and start a new billing period.
+    pub fn reset_usage(&self) {
+        let mut state = self.state.lock();
+        state.screenshots = 0;
+        state.bytes = 0;
+        state.period_start = Utc::now();
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl State {
+    fn usage(&self) -> Usage {
+        Usage {
+            screenshots: self.screenshots,
+            bytes: self.bytes,
+            period_start: self.period_start,
+            period_end: self.period_start + chrono::Duration::days(PERIOD_DAYS),
+        }
+    }
+}
+
+async fn serve(listener: TcpListener, state: Arc<Shared>) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+
+        let state = state.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let state = state.clone();
+                async move { Ok::<_, Infallible>(handle(&state, request).await) }
+            });
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
+
+async fn handle(state: &Shared, request: Request<Incoming>) -> HttpResponse {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    if method == Method::GET {
+        if let Some(id) = path.strip_prefix("/stored/") {
+            return stored(state, id);
+        }
+    }
+
+    let authorized = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|key| !key.trim().is_empty());
+
+    let (latency, fault) = {
+        let mut state = state.lock();
+        (state.latency, state.faults.pop_front())
+    };
+    if !latency.is_zero() {
+        tokio::time::sleep(latency).await;
+    }
+
+    if !authorized {
+        return error(StatusCode::UNAUTHORIZED, "missing or invalid API key");
+    }
+    if let Some(fault) = fault {
+        return fault_response(fault);
+    }
+
+    match (method, path.as_str()) {
+        (Method::POST, "/v1/screenshot") => {
+            let body = match request.into_body().collect().await {
+                Ok(body) => body.to_bytes(),
+                Err(_) => return error(StatusCode::BAD_REQUEST, "failed to read request body"),
+            };
+            screenshot(state, &body)
+        }
+        (Method::GET, "/v1/usage") => json_response(StatusCode::OK, &state.lock().usage()),
+        _ => error(StatusCode::NOT_FOUND, "not found"),
+    }
+}
+
+fn screenshot(state: &Shared, body: &[u8]) -> HttpResponse {
+    let value: serde_json::Value = match serde_json::from_slice(body) {
+        Ok(value) => value,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &format!("invalid JSON: {}", e)),
+    };
+    state.lock().requests.push(value.clone());
+
+    let request: WireRequest = match serde_json::from_value(value) {
+        Ok(request) => request,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &format!("invalid request: {}", e)),
+    };
+
+    match reqwest::Url::parse(&request.url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.host().is_some() => {}
+        _ => return error(StatusCode::BAD_REQUEST, "url must be an absolute http(s) URL"),
+    }
+    if request.quality.is_some_and(|q| !(1..=100).contains(&q)) {
+        return error(StatusCode::BAD_REQUEST, "quality must be between 1 and 100");
+    }
+
+    let scale = request.device_scale_factor.unwrap_or(1.0);
+    if !(1.0..=3.0).contains(&scale) {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "device_scale_factor must be between 1 and 3",
+        );
+    }
+    let width = (request.width.unwrap_or(DEFAULT_WIDTH) as f32 * scale).round() as u32;
+    let height = (request.height.unwrap_or(DEFAULT_HEIGHT) as f32 * scale).round() as u32;
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return error(
+            StatusCode::BAD_REQUEST,
+            &format!("dimensions must be between 1 and {} pixels", MAX_DIMENSION),
+        );
+    }
+
+    let format = request.format.unwrap_or_default();
+    let image = match encode(&render(&request.url, width, height), format, request.quality) {
+        Ok(image) => Bytes::from(image),
+        Err(e) => {
+            return error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("failed to encode image: {}", e),
+            )
+        }
+    };
+
+    let mut locked = state.lock();
+    locked.screenshots += 1;
+    locked.bytes += image.len() as u64;
+
+    if !request.store.unwrap_or(false) {
+        return Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, format.mime_type())
+            .body(Full::new(image))
+            .expect("valid response");
+    }
+
+    let id = format!(
+        "{}.{}",
+        state.next_id.fetch_add(1, Ordering::Relaxed),
+        format.as_str()
+    );
+    let size_bytes = image.len() as u64;
+    locked.stored.insert(id.clone(), (format, image));
+    drop(locked);
+
+    let expires_at = Utc::now() + chrono::Duration::hours(STORED_TTL_HOURS);
+    json_response(
+        StatusCode::OK,
+        &json!({
+            "url": format!("{}/stored/{}", state.base_url, id),
+            "expires_at": expires_at,
+            "width": width,
+            "height": height,
+            "size_bytes": size_bytes,
+        }),
+    )
+}
+
+fn stored(state: &Shared, id: &str) -> HttpResponse {
+    match state.lock().stored.get(id) {
+        Some((format, image)) => Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, format.mime_type())
+            .body(Full::new(image.clone()))
+            .expect("valid response"),
+        None => error(StatusCode::NOT_FOUND, "stored screenshot not found"),
+    }
+}
+
+fn fault_response(fault: Fault) -> HttpResponse {
+    match fault {
+        Fault::Status { status, message } => error(
+            StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            &message,
+        ),
+        Fault::RateLimited { retry_after } => {
+            let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            let mut response = error(StatusCode::TOO_MANY_REQUESTS, "rate limit exceeded");
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds));
+            response
+        }
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> HttpResponse {
+    json_response(status, &json!({ "error": message }))
+}
+
+fn json_response(status: StatusCode, body: &impl serde::Serialize) -> HttpResponse {
+    let body = serde_json::to_vec(body).expect("serializable response");
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body)))
+        .expect("valid response")
+}
+
+/// Render a placeholder image whose colours are derived from `url`.
+fn render(url: &str, width: u32, height: u32) -> RgbImage {
+    const GRID: u32 = 64;
+
+    // FNV-1a, so the same URL always renders the same image.
+    let seed = url.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    let [r, g, b, ..] = seed.to_le_bytes();
+
+    RgbImage::from_fn(width, height, |x, y| {
+        if x % GRID == 0 || y % GRID == 0 {
+            return Rgb([255, 255, 255]);
+        }
+        let dx = (u64::from(x) * 255 / u64::from(width)) as u8;
+        let dy = (u64::from(y) * 255 / u64::from(height)) as u8;
+        Rgb([r.wrapping_add(dx), g.wrapping_add(dy), b])
+    })
+}
+
+fn encode(image: &RgbImage, format: ImageFormat, quality: Option<u8>) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let (width, height) = image.dimensions();
+
+    let result = match format {
+        ImageFormat::Png => PngEncoder::new(&mut out).write_image(
+            image.as_raw(),
+            width,
+            height,
+            ExtendedColorType::Rgb8,
+        ),
+        ImageFormat::Jpeg => {
+            JpegEncoder::new_with_quality(&mut out, quality.unwrap_or(DEFAULT_JPEG_QUALITY))
+                .write_image(image.as_raw(), width, height, ExtendedColorType::Rgb8)
+        }
+        ImageFormat::Webp => WebPEncoder::new_lossless(&mut out).write_image(
+            image.as_raw(),
+            width,
+            height,
+            ExtendedColorType::Rgb8,
+        ),
+    };
+
+    result.map_err(io::Error::other)?;
+    Ok(out)
+}
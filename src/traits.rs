@@ -0,0 +1,96 @@
+//! Abstraction over screenshot clients.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::client::Pxshot;
+use crate::error::Result;
+use crate::types::{ScreenshotRequest, ScreenshotResponse, Usage};
+
+/// An async client capable of capturing screenshots.
+///
+/// [`Pxshot`] implements this trait. Depend on it instead of the concrete
+/// client to unit-test code without network access, using
+/// [`MockClient`](crate::testing::MockClient) in tests.
+///
+/// # Example
+///
+/// ```
+/// use pxshot::{ScreenshotClient, ScreenshotRequest};
+///
+/// async fn capture_homepage(client: &impl ScreenshotClient) -> pxshot::Result<Vec<u8>> {
+///     let response = client
+///         .screenshot(
+///             ScreenshotRequest::builder()
+///                 .url("https://example.com")
+///                 .build()?,
+///         )
+///         .await?;
+///
+///     Ok(response.into_bytes().unwrap_or_default())
+/// }
+/// ```
+#[async_trait]
+pub trait ScreenshotClient: Send + Sync {
+    /// Capture a screenshot.
+    async fn screenshot(&self, request: ScreenshotRequest) -> Result<ScreenshotResponse>;
+
+    /// Get API usage statistics.
+    async fn usage(&self) -> Result<Usage>;
+
+    /// The request that [`screenshot`](Self::screenshot) sends for
+    /// `request`, e.g. with client defaults applied.
+    ///
+    /// Code that names or describes captures before they are taken uses
+    /// this to see the options actually sent, such as the image format.
+    /// The default implementation returns the request unchanged.
+    fn effective_request(&self, request: &ScreenshotRequest) -> ScreenshotRequest {
+        request.clone()
+    }
+}
+
+#[async_trait]
+impl ScreenshotClient for Pxshot {
+    async fn screenshot(&self, request: ScreenshotRequest) -> Result<ScreenshotResponse> {
+        Pxshot::screenshot(self, request).await
+    }
+
+    async fn usage(&self) -> Result<Usage> {
+        Pxshot::usage(self).await
+    }
+
+    fn effective_request(&self, request: &ScreenshotRequest) -> ScreenshotRequest {
+        Pxshot::effective_request(self, request)
+    }
+}
+
+#[async_trait]
+impl<T: ScreenshotClient + ?Sized> ScreenshotClient for &T {
+    async fn screenshot(&self, request: ScreenshotRequest) -> Result<ScreenshotResponse> {
+        (**self).screenshot(request).await
+    }
+
+    async fn usage(&self) -> Result<Usage> {
+        (**self).usage().await
+    }
+
+    fn effective_request(&self, request: &ScreenshotRequest) -> ScreenshotRequest {
+        (**self).effective_request(request)
+    }
+}
+
+#[async_trait]
+impl<T: ScreenshotClient + ?Sized> ScreenshotClient for Arc<T> {
+    async fn screenshot(&self, request: ScreenshotRequest) -> Result<ScreenshotResponse> {
+        (**self).screenshot(request).await
+    }
+
+    async fn usage(&self) -> Result<Usage> {
+        (**self).usage().await
+    }
+
+    fn effective_request(&self, request: &ScreenshotRequest) -> ScreenshotRequest {
+        (**self).effective_request(request)
+    }
+}
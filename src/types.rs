@@ -2,8 +2,10 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use url::Url;
 
 use crate::error::{Error, Result};
+use crate::urls::{self, IntoUrl};
 
 /// Image format for screenshots.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
@@ -18,6 +20,47 @@ pub enum ImageFormat {
     Webp,
 }
 
+impl ImageFormat {
+    /// Name of the format as used by the API (`"png"`, `"jpeg"` or `"webp"`).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpeg",
+            Self::Webp => "webp",
+        }
+    }
+
+    /// Detect the format of image data from its leading bytes.
+    ///
+    /// ```
+    /// use pxshot::ImageFormat;
+    ///
+    /// let png = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    /// assert_eq!(ImageFormat::detect(&png), Some(ImageFormat::Png));
+    /// assert_eq!(ImageFormat::detect(b"GIF89a"), None);
+    /// ```
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(Self::Jpeg)
+        } else if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+            Some(Self::Webp)
+        } else {
+            None
+        }
+    }
+
+    /// MIME type of the format (e.g. `"image/png"`).
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+        }
+    }
+}
+
 /// When to consider the page loaded.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "snake_case")]
@@ -32,10 +75,13 @@ pub enum WaitUntil {
 }
 
 /// Request to capture a screenshot.
-#[derive(Debug, Clone, Serialize)]
+///
+/// Requests serialize to and deserialize from the API's JSON format, so they
+/// can be loaded from configuration files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScreenshotRequest {
     /// URL to capture.
-    pub url: String,
+    pub url: Url,
 
     /// Image format.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -83,16 +129,139 @@ pub struct ScreenshotRequest {
 }
 
 impl ScreenshotRequest {
+    /// Create a request for `url` with the API defaults for every option.
+    ///
+    /// ```
+    /// use pxshot::{ScreenshotRequest, Url};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let request = ScreenshotRequest::new(Url::parse("https://example.com")?);
+    /// assert_eq!(request.width, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(url: Url) -> Self {
+        ScreenshotOptions::default().to_request(url)
+    }
+
     /// Create a new builder for a screenshot request.
     pub fn builder() -> ScreenshotRequestBuilder {
         ScreenshotRequestBuilder::default()
     }
+
+    /// Check that every option is within the range accepted by the API.
+    ///
+    /// Called by [`ScreenshotRequestBuilder::build`] and by the client before
+    /// a request is sent, so requests assembled field by field are checked
+    /// too.
+    pub fn validate(&self) -> Result<()> {
+        fn invalid(field: &'static str, message: impl Into<String>) -> Result<()> {
+            Err(Error::InvalidField {
+                field,
+                message: message.into(),
+            })
+        }
+
+        urls::check_target(&self.url)?;
+        if let Some(quality) = self.quality {
+            if !(1..=100).contains(&quality) {
+                return invalid(
+                    "quality",
+                    format!("must be between 1 and 100, got {}", quality),
+                );
+            }
+        }
+        if self.width == Some(0) {
+            return invalid("width", "must be at least 1");
+        }
+        if self.height == Some(0) {
+            return invalid("height", "must be at least 1");
+        }
+        if let Some(factor) = self.device_scale_factor {
+            if !(1.0..=3.0).contains(&factor) {
+                return invalid(
+                    "device_scale_factor",
+                    format!("must be between 1 and 3, got {}", factor),
+                );
+            }
+        }
+        if self
+            .wait_for_selector
+            .as_ref()
+            .is_some_and(|selector| selector.trim().is_empty())
+        {
+            return invalid("wait_for_selector", "must not be empty");
+        }
+        Ok(())
+    }
+
+    /// The options of this request, without the URL.
+    pub fn options(&self) -> ScreenshotOptions {
+        ScreenshotOptions {
+            format: self.format,
+            quality: self.quality,
+            width: self.width,
+            height: self.height,
+            full_page: self.full_page,
+            wait_until: self.wait_until,
+            wait_for_selector: self.wait_for_selector.clone(),
+            wait_for_timeout: self.wait_for_timeout,
+            device_scale_factor: self.device_scale_factor,
+            store: self.store,
+            block_ads: self.block_ads,
+        }
+    }
+
+    /// Layer `overrides` on top of this request.
+    ///
+    /// Fields set in `overrides` win; unset fields keep their value from
+    /// `self`. The URL is kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pxshot::{ScreenshotOptions, ScreenshotRequest};
+    ///
+    /// # fn main() -> pxshot::Result<()> {
+    /// let base = ScreenshotRequest::builder()
+    ///     .url("https://example.com")
+    ///     .width(1280)
+    ///     .full_page(true)
+    ///     .build()?;
+    ///
+    /// let request = base.merge(&ScreenshotOptions {
+    ///     width: Some(390),
+    ///     ..Default::default()
+    /// });
+    /// assert_eq!(request.width, Some(390));
+    /// assert_eq!(request.full_page, Some(true));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn merge(&self, overrides: &ScreenshotOptions) -> ScreenshotRequest {
+        self.options().merge(overrides).to_request(self.url.clone())
+    }
 }
 
+/// Type-state marker for a [`ScreenshotRequestBuilder`] without a URL.
+#[derive(Debug, Clone, Default)]
+pub struct NoUrl;
+
+/// Type-state marker for a [`ScreenshotRequestBuilder`] with a URL set.
+#[derive(Debug, Clone)]
+pub struct HasUrl(std::result::Result<Url, String>);
+
 /// Builder for [`ScreenshotRequest`].
-#[derive(Debug, Default)]
-pub struct ScreenshotRequestBuilder {
-    url: Option<String>,
+///
+/// `build` is only available once a URL has been set with
+/// [`url`](Self::url), so a request without a URL fails to compile:
+///
+/// ```compile_fail
+/// let request = pxshot::ScreenshotRequest::builder().width(1280).build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ScreenshotRequestBuilder<U = NoUrl> {
+    url: U,
     format: Option<ImageFormat>,
     quality: Option<u8>,
     width: Option<u32>,
@@ -106,11 +275,29 @@ pub struct ScreenshotRequestBuilder {
     block_ads: Option<bool>,
 }
 
-impl ScreenshotRequestBuilder {
+impl<U> ScreenshotRequestBuilder<U> {
     /// Set the URL to capture.
-    pub fn url(mut self, url: impl Into<String>) -> Self {
-        self.url = Some(url.into());
-        self
+    ///
+    /// Strings are parsed when the request is built.
+    pub fn url(self, url: impl IntoUrl) -> ScreenshotRequestBuilder<HasUrl> {
+        let url = url.into_url().map_err(|e| match e {
+            Error::InvalidField { message, .. } => message,
+            e => e.to_string(),
+        });
+        ScreenshotRequestBuilder {
+            url: HasUrl(url),
+            format: self.format,
+            quality: self.quality,
+            width: self.width,
+            height: self.height,
+            full_page: self.full_page,
+            wait_until: self.wait_until,
+            wait_for_selector: self.wait_for_selector,
+            wait_for_timeout: self.wait_for_timeout,
+            device_scale_factor: self.device_scale_factor,
+            store: self.store,
+            block_ads: self.block_ads,
+        }
     }
 
     /// Set the image format.
@@ -178,12 +365,126 @@ impl ScreenshotRequestBuilder {
         self.block_ads = Some(block_ads);
         self
     }
+}
 
+impl ScreenshotRequestBuilder<HasUrl> {
     /// Build the screenshot request.
+    ///
+    /// Fails with [`Error::InvalidField`] if the URL is malformed or uses a
+    /// scheme other than `http` or `https`, or if an option is out of range.
     pub fn build(self) -> Result<ScreenshotRequest> {
-        let url = self.url.ok_or(Error::MissingField("url"))?;
+        let request = ScreenshotRequest {
+            url: self.url.0.map_err(urls::invalid_url)?,
+            format: self.format,
+            quality: self.quality,
+            width: self.width,
+            height: self.height,
+            full_page: self.full_page,
+            wait_until: self.wait_until,
+            wait_for_selector: self.wait_for_selector,
+            wait_for_timeout: self.wait_for_timeout,
+            device_scale_factor: self.device_scale_factor,
+            store: self.store,
+            block_ads: self.block_ads,
+        };
+        request.validate()?;
+        Ok(request)
+    }
+}
+
+/// Screenshot options without a URL.
+///
+/// Used for defaults and per-item overrides, e.g. in batch manifests. Every
+/// field is optional; unset fields fall back to the API defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScreenshotOptions {
+    /// Image format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ImageFormat>,
+
+    /// Image quality (1-100, only for JPEG/WebP).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+
+    /// Viewport width in pixels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+
+    /// Viewport height in pixels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+
+    /// Capture the full scrollable page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_page: Option<bool>,
+
+    /// When to consider navigation succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_until: Option<WaitUntil>,
+
+    /// Wait for a specific CSS selector before capturing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_for_selector: Option<String>,
+
+    /// Additional wait time in milliseconds after page load.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_for_timeout: Option<u32>,
+
+    /// Device scale factor (1-3).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_scale_factor: Option<f32>,
+
+    /// Store the screenshot and return a URL instead of bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store: Option<bool>,
 
-        Ok(ScreenshotRequest {
+    /// Block ads and trackers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_ads: Option<bool>,
+}
+
+impl ScreenshotOptions {
+    /// Names of the fields, as used in serialized options.
+    pub(crate) const FIELDS: &'static [&'static str] = &[
+        "format",
+        "quality",
+        "width",
+        "height",
+        "full_page",
+        "wait_until",
+        "wait_for_selector",
+        "wait_for_timeout",
+        "device_scale_factor",
+        "store",
+        "block_ads",
+    ];
+
+    /// Layer `overrides` on top of these options.
+    ///
+    /// Fields set in `overrides` win; unset fields keep their value from
+    /// `self`.
+    pub fn merge(&self, overrides: &ScreenshotOptions) -> ScreenshotOptions {
+        ScreenshotOptions {
+            format: overrides.format.or(self.format),
+            quality: overrides.quality.or(self.quality),
+            width: overrides.width.or(self.width),
+            height: overrides.height.or(self.height),
+            full_page: overrides.full_page.or(self.full_page),
+            wait_until: overrides.wait_until.or(self.wait_until),
+            wait_for_selector: overrides
+                .wait_for_selector
+                .clone()
+                .or_else(|| self.wait_for_selector.clone()),
+            wait_for_timeout: overrides.wait_for_timeout.or(self.wait_for_timeout),
+            device_scale_factor: overrides.device_scale_factor.or(self.device_scale_factor),
+            store: overrides.store.or(self.store),
+            block_ads: overrides.block_ads.or(self.block_ads),
+        }
+    }
+
+    /// Create a request for `url` with these options.
+    pub fn to_request(&self, url: Url) -> ScreenshotRequest {
+        ScreenshotRequest {
             url,
             format: self.format,
             quality: self.quality,
@@ -191,17 +492,17 @@ impl ScreenshotRequestBuilder {
             height: self.height,
             full_page: self.full_page,
             wait_until: self.wait_until,
-            wait_for_selector: self.wait_for_selector,
+            wait_for_selector: self.wait_for_selector.clone(),
             wait_for_timeout: self.wait_for_timeout,
             device_scale_factor: self.device_scale_factor,
             store: self.store,
             block_ads: self.block_ads,
-        })
+        }
     }
 }
 
 /// Response when storing a screenshot (store=true).
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoredScreenshot {
     /// URL where the screenshot is stored.
     pub url: String,
@@ -220,7 +521,7 @@ pub struct StoredScreenshot {
 }
 
 /// Result of a screenshot request.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ScreenshotResponse {
     /// Raw image bytes (when store=false).
     Bytes(Vec<u8>),
@@ -246,6 +547,14 @@ impl ScreenshotResponse {
         }
     }
 
+    /// Size of the screenshot in bytes.
+    pub fn size_bytes(&self) -> u64 {
+        match self {
+            Self::Bytes(bytes) => bytes.len() as u64,
+            Self::Stored(info) => info.size_bytes,
+        }
+    }
+
     /// Convert into bytes, returning None if stored.
     pub fn into_bytes(self) -> Option<Vec<u8>> {
         match self {
@@ -264,7 +573,7 @@ impl ScreenshotResponse {
 }
 
 /// API usage statistics.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Usage {
     /// Number of screenshots taken this billing period.
     pub screenshots: u64,
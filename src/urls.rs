@@ -0,0 +1,350 @@
+//! URL parsing, validation and normalization for capture targets.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use url::{Host, Url};
+
+use crate::error::{Error, Result};
+
+/// Maximum length of a DNS name, in bytes.
+const MAX_HOST_LEN: usize = 253;
+/// Maximum length of a single DNS label, in bytes.
+const MAX_LABEL_LEN: usize = 63;
+
+/// Conversion into a [`Url`] to capture.
+///
+/// Implemented for [`Url`] and for strings, which are parsed. Parse errors
+/// are reported as [`Error::InvalidField`] for the `url` field.
+pub trait IntoUrl {
+    /// Convert into a [`Url`].
+    fn into_url(self) -> Result<Url>;
+}
+
+impl IntoUrl for Url {
+    fn into_url(self) -> Result<Url> {
+        Ok(self)
+    }
+}
+
+impl IntoUrl for &Url {
+    fn into_url(self) -> Result<Url> {
+        Ok(self.clone())
+    }
+}
+
+impl IntoUrl for &str {
+    fn into_url(self) -> Result<Url> {
+        Url::parse(self.trim()).map_err(|e| invalid_url(format!("`{}`: {}", self, e)))
+    }
+}
+
+impl IntoUrl for String {
+    fn into_url(self) -> Result<Url> {
+        self.as_str().into_url()
+    }
+}
+
+impl IntoUrl for &String {
+    fn into_url(self) -> Result<Url> {
+        self.as_str().into_url()
+    }
+}
+
+pub(crate) fn invalid_url(message: impl Into<String>) -> Error {
+    Error::InvalidField {
+        field: "url",
+        message: message.into(),
+    }
+}
+
+/// Check that `url` is something the API can capture: an `http` or `https`
+/// URL with a well-formed host.
+pub(crate) fn check_target(url: &Url) -> Result<()> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(invalid_url(format!(
+            "unsupported scheme `{}`; expected http or https",
+            url.scheme()
+        )));
+    }
+
+    match url.host() {
+        None => Err(invalid_url("missing host")),
+        Some(Host::Domain(domain)) => check_domain(domain),
+        Some(Host::Ipv4(_) | Host::Ipv6(_)) => Ok(()),
+    }
+}
+
+fn check_domain(domain: &str) -> Result<()> {
+    let name = domain.strip_suffix('.').unwrap_or(domain);
+    if name.is_empty() || name.len() > MAX_HOST_LEN {
+        return Err(invalid_url(format!("malformed host `{}`", domain)));
+    }
+
+    for label in name.split('.') {
+        let valid = !label.is_empty()
+            && label.len() <= MAX_LABEL_LEN
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_');
+        if !valid {
+            return Err(invalid_url(format!("malformed host `{}`", domain)));
+        }
+    }
+    Ok(())
+}
+
+/// Normalize a URL so equivalent URLs compare equal, e.g. for cache keys.
+///
+/// The fragment is removed and query parameters are sorted by name; the
+/// relative order of repeated parameters and their encoding are kept. An
+/// empty query (a trailing `?`) is removed. Scheme and host case, default
+/// ports and empty paths are already normalized when the URL is parsed.
+///
+/// # Example
+///
+/// ```
+/// use pxshot::{normalize_url, Url};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let url = Url::parse("HTTPS://Example.com:443/search?q=rust&page=2#results")?;
+/// assert_eq!(
+///     normalize_url(&url).as_str(),
+///     "https://example.com/search?page=2&q=rust"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn normalize_url(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_fragment(None);
+
+    let query = url.query().map(|query| {
+        let mut pairs: Vec<&str> = query.split('&').filter(|pair| !pair.is_empty()).collect();
+        pairs.sort_by_key(|pair| pair.split('=').next().unwrap_or(""));
+        pairs.join("&")
+    });
+    match query {
+        Some(query) if !query.is_empty() => url.set_query(Some(&query)),
+        _ => url.set_query(None),
+    }
+    url
+}
+
+/// Whether `url` points at a private, loopback or otherwise internal address.
+///
+/// Use this in multi-tenant applications to refuse capture targets supplied
+/// by end users that would reach internal services. IP addresses are checked
+/// against loopback, private, link-local, shared, documentation and other
+/// reserved ranges (including IPv4 addresses embedded in IPv6). Host names
+/// are treated as internal if they are `localhost` or end in `.localhost`,
+/// `.local` or `.internal`, or have a single label such as `intranet`.
+///
+/// Host names are not resolved, so a public name whose DNS record points at
+/// a private address is not detected; resolve the name and check the
+/// addresses with [`is_private_ip`] for that.
+///
+/// # Example
+///
+/// ```
+/// use pxshot::{is_private_address, Url};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// assert!(is_private_address(&Url::parse("http://127.0.0.1:8080/admin")?));
+/// assert!(is_private_address(&Url::parse("http://[::ffff:10.0.0.1]/")?));
+/// assert!(is_private_address(&Url::parse("http://metadata.internal/")?));
+/// assert!(!is_private_address(&Url::parse("https://example.com/")?));
+/// # Ok(())
+/// # }
+/// ```
+pub fn is_private_address(url: &Url) -> bool {
+    match url.host() {
+        None => false,
+        Some(Host::Ipv4(ip)) => is_private_ip(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => is_private_ip(IpAddr::V6(ip)),
+        Some(Host::Domain(domain)) => {
+            let name = domain
+                .strip_suffix('.')
+                .unwrap_or(domain)
+                .to_ascii_lowercase();
+            name == "localhost"
+                || [".localhost", ".local", ".internal"]
+                    .iter()
+                    .any(|suffix| name.ends_with(suffix))
+                || !name.contains('.')
+        }
+    }
+}
+
+/// Whether `ip` is a loopback, private, link-local or otherwise
+/// non-public address.
+pub fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_ipv4(ip),
+        IpAddr::V6(ip) => is_private_ipv6(ip),
+    }
+}
+
+fn is_private_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // "This network" (0.0.0.0/8)
+        || a == 0
+        // Shared address space for carrier-grade NAT (100.64.0.0/10)
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments (192.0.0.0/24)
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking (198.18.0.0/15)
+        || (a == 198 && (18..20).contains(&b))
+        // Reserved (240.0.0.0/4)
+        || a >= 240
+}
+
+fn is_private_ipv6(ip: Ipv6Addr) -> bool {
+    if let Some(ipv4) = embedded_ipv4(ip) {
+        return is_private_ipv4(ipv4);
+    }
+
+    let segments = ip.segments();
+    ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local (fc00::/7)
+        || (segments[0] & 0xfe00) == 0xfc00
+        // Link-local (fe80::/10)
+        || (segments[0] & 0xffc0) == 0xfe80
+        // Site-local, deprecated (fec0::/10)
+        || (segments[0] & 0xffc0) == 0xfec0
+        // Documentation (2001:db8::/32)
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+}
+
+/// The IPv4 address embedded in an IPv4-mapped (`::ffff:a.b.c.d`),
+/// IPv4-compatible (`::a.b.c.d`) or NAT64 (`64:ff9b::a.b.c.d`) address.
+pub(crate) fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let ipv4 = || {
+        let [.., a, b] = segments;
+        Ipv4Addr::new((a >> 8) as u8, a as u8, (b >> 8) as u8, b as u8)
+    };
+
+    match segments {
+        [0, 0, 0, 0, 0, 0xffff, _, _] => Some(ipv4()),
+        [0, 0, 0, 0, 0, 0, hi, _] if hi != 0 => Some(ipv4()),
+        [0x64, 0xff9b, 0, 0, 0, 0, _, _] => Some(ipv4()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn private(ip: &str) -> bool {
+        is_private_ip(ip.parse().unwrap())
+    }
+
+    fn private_url(url: &str) -> bool {
+        is_private_address(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn private_ipv4_ranges() {
+        for ip in [
+            "0.0.0.0",
+            "0.1.2.3",
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "172.31.255.255",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "100.127.255.255",
+            "192.0.0.8",
+            "192.0.2.1",
+            "198.18.0.1",
+            "198.19.255.255",
+            "224.0.0.1",
+            "240.0.0.1",
+            "255.255.255.255",
+        ] {
+            assert!(private(ip), "{} should be private", ip);
+        }
+    }
+
+    #[test]
+    fn public_ipv4_addresses() {
+        for ip in [
+            "8.8.8.8",
+            "93.184.216.34",
+            "100.63.255.255",
+            "100.128.0.0",
+            "172.32.0.1",
+            "198.20.0.1",
+            "1.1.1.1",
+        ] {
+            assert!(!private(ip), "{} should be public", ip);
+        }
+    }
+
+    #[test]
+    fn private_ipv6_ranges() {
+        for ip in [
+            "::",
+            "::1",
+            "fc00::1",
+            "fd12:3456::1",
+            "fe80::1",
+            "fec0::1",
+            "ff02::1",
+            "2001:db8::1",
+        ] {
+            assert!(private(ip), "{} should be private", ip);
+        }
+        assert!(!private("2606:4700::1111"));
+    }
+
+    #[test]
+    fn embedded_ipv4_addresses() {
+        // IPv4-mapped
+        assert!(private("::ffff:127.0.0.1"));
+        assert!(private("::ffff:10.0.0.1"));
+        assert!(private("::ffff:100.64.0.1"));
+        assert!(!private("::ffff:8.8.8.8"));
+        // IPv4-compatible
+        assert!(private("::192.168.0.1"));
+        // NAT64
+        assert!(private("64:ff9b::169.254.169.254"));
+        assert!(private("64:ff9b::a00:1"));
+        assert!(!private("64:ff9b::8.8.8.8"));
+
+        assert_eq!(
+            embedded_ipv4("64:ff9b::c0a8:101".parse().unwrap()),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+        assert_eq!(embedded_ipv4("2001:db8::1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn private_host_names() {
+        assert!(private_url("http://localhost/"));
+        assert!(private_url("http://LOCALHOST./"));
+        assert!(private_url("http://app.localhost/"));
+        assert!(private_url("http://printer.local/"));
+        assert!(private_url("http://metadata.google.internal/"));
+        assert!(private_url("http://intranet/"));
+        assert!(private_url("http://intranet./"));
+        assert!(private_url("http://[::ffff:7f00:1]/"));
+        assert!(!private_url("https://example.com/"));
+        assert!(!private_url("https://example.com./"));
+        assert!(!private_url("https://local.example.com/"));
+    }
+}
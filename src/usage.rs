@@ -0,0 +1,423 @@
+//! Usage history types and consumption projections.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::types::{ImageFormat, Usage};
+
+/// Bucket size for a usage history series.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    /// One data point per day (default).
+    #[default]
+    Day,
+    /// One data point per billing period.
+    Period,
+}
+
+/// Time range to fetch usage history for.
+///
+/// # Example
+///
+/// ```
+/// use pxshot::{Granularity, UsageRange};
+///
+/// let last_month = UsageRange::last_days(30);
+/// let by_period = UsageRange::last_days(365).granularity(Granularity::Period);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct UsageRange {
+    /// Start of the range (inclusive).
+    pub start: DateTime<Utc>,
+
+    /// End of the range (exclusive).
+    pub end: DateTime<Utc>,
+
+    /// Bucket size for the series.
+    pub granularity: Granularity,
+}
+
+impl UsageRange {
+    /// Create a range with daily granularity.
+    pub fn new(start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            end,
+            granularity: Granularity::default(),
+        }
+    }
+
+    /// The last `days` days up to now, with daily granularity.
+    pub fn last_days(days: u32) -> Self {
+        let end = Utc::now();
+        Self::new(end - Duration::days(i64::from(days)), end)
+    }
+
+    /// Set the bucket size for the series.
+    pub fn granularity(mut self, granularity: Granularity) -> Self {
+        self.granularity = granularity;
+        self
+    }
+}
+
+/// Screenshot and byte totals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub struct UsageTotals {
+    /// Number of screenshots taken.
+    pub screenshots: u64,
+
+    /// Total bytes of screenshots taken.
+    pub bytes: u64,
+}
+
+/// Usage for one bucket of a history series.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UsagePoint {
+    /// Start of the bucket.
+    pub start: DateTime<Utc>,
+
+    /// End of the bucket.
+    pub end: DateTime<Utc>,
+
+    /// Number of screenshots taken.
+    pub screenshots: u64,
+
+    /// Total bytes of screenshots taken.
+    pub bytes: u64,
+}
+
+/// Usage for one image format.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FormatUsage {
+    /// Image format.
+    pub format: ImageFormat,
+
+    /// Number of screenshots taken.
+    pub screenshots: u64,
+
+    /// Total bytes of screenshots taken.
+    pub bytes: u64,
+}
+
+/// Usage split by how screenshots were delivered.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct DeliveryUsage {
+    /// Screenshots stored and returned as a URL (`store: true`).
+    pub stored: UsageTotals,
+
+    /// Screenshots returned as raw bytes.
+    pub bytes: UsageTotals,
+}
+
+/// Usage for one API key.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKeyUsage {
+    /// API key identifier (never the secret key itself).
+    pub key_id: String,
+
+    /// Display name of the key, if set.
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Number of screenshots taken.
+    pub screenshots: u64,
+
+    /// Total bytes of screenshots taken.
+    pub bytes: u64,
+}
+
+/// Usage history with breakdowns.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UsageHistory {
+    /// Start of the range.
+    pub start: DateTime<Utc>,
+
+    /// End of the range.
+    pub end: DateTime<Utc>,
+
+    /// Bucket size of the series.
+    pub granularity: Granularity,
+
+    /// Usage per bucket, in chronological order.
+    pub series: Vec<UsagePoint>,
+
+    /// Usage by image format.
+    #[serde(default)]
+    pub by_format: Vec<FormatUsage>,
+
+    /// Usage by delivery mode.
+    #[serde(default)]
+    pub by_delivery: DeliveryUsage,
+
+    /// Usage by API key.
+    #[serde(default)]
+    pub by_api_key: Vec<ApiKeyUsage>,
+}
+
+/// Average consumption rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UsageRate {
+    /// Screenshots per day.
+    pub screenshots_per_day: f64,
+
+    /// Bytes per day.
+    pub bytes_per_day: f64,
+}
+
+/// Projected consumption at the end of a billing period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsageProjection {
+    /// Projected number of screenshots.
+    pub screenshots: u64,
+
+    /// Projected bytes of screenshots.
+    pub bytes: u64,
+
+    /// End of the period the projection is for.
+    pub period_end: DateTime<Utc>,
+}
+
+impl UsageHistory {
+    /// Sum of the series.
+    pub fn totals(&self) -> UsageTotals {
+        self.series
+            .iter()
+            .fold(UsageTotals::default(), |acc, point| UsageTotals {
+                screenshots: acc.screenshots + point.screenshots,
+                bytes: acc.bytes + point.bytes,
+            })
+    }
+
+    /// Average daily consumption over the series.
+    ///
+    /// Returns `None` if the series is empty.
+    pub fn rate(&self) -> Option<UsageRate> {
+        let first = self.series.first()?;
+        let last = self.series.last()?;
+        let days = days_between(first.start, last.end.min(Utc::now()));
+        if days <= 0.0 {
+            return None;
+        }
+
+        let totals = self.totals();
+        Some(UsageRate {
+            screenshots_per_day: totals.screenshots as f64 / days,
+            bytes_per_day: totals.bytes as f64 / days,
+        })
+    }
+
+    /// Project end-of-period consumption from the trend in this history.
+    ///
+    /// Adds the average daily rate of the series to the current period's
+    /// usage for the rest of the period. Falls back to
+    /// [`Usage::project`] if the series is empty.
+    pub fn project(&self, usage: &Usage) -> UsageProjection {
+        match self.rate() {
+            Some(rate) => project(usage, rate, Utc::now()),
+            None => usage.project(),
+        }
+    }
+}
+
+impl Usage {
+    /// Project end-of-period consumption from the period-to-date rate.
+    pub fn project(&self) -> UsageProjection {
+        self.project_at(Utc::now())
+    }
+
+    /// Project end-of-period consumption as of `now`.
+    pub fn project_at(&self, now: DateTime<Utc>) -> UsageProjection {
+        let elapsed = days_between(self.period_start, now.min(self.period_end));
+        if elapsed <= 0.0 {
+            return UsageProjection {
+                screenshots: self.screenshots,
+                bytes: self.bytes,
+                period_end: self.period_end,
+            };
+        }
+
+        let rate = UsageRate {
+            screenshots_per_day: self.screenshots as f64 / elapsed,
+            bytes_per_day: self.bytes as f64 / elapsed,
+        };
+        project(self, rate, now)
+    }
+}
+
+fn project(usage: &Usage, rate: UsageRate, now: DateTime<Utc>) -> UsageProjection {
+    let remaining = days_between(now.max(usage.period_start), usage.period_end).max(0.0);
+    UsageProjection {
+        screenshots: usage.screenshots + (rate.screenshots_per_day * remaining).round() as u64,
+        bytes: usage.bytes + (rate.bytes_per_day * remaining).round() as u64,
+        period_end: usage.period_end,
+    }
+}
+
+fn days_between(start: DateTime<Utc>, end: DateTime<Utc>) -> f64 {
+    (end - start).num_seconds() as f64 / 86_400.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    fn date(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 4, day, 0, 0, 0).unwrap()
+    }
+
+    /// April 2024: a 30-day period.
+    fn april(screenshots: u64, bytes: u64) -> Usage {
+        Usage {
+            screenshots,
+            bytes,
+            period_start: date(1),
+            period_end: Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap(),
+        }
+    }
+
+    fn history(points: &[(DateTime<Utc>, u64)]) -> UsageHistory {
+        UsageHistory {
+            start: points.first().map_or(date(1), |point| point.0),
+            end: points
+                .last()
+                .map_or(date(1), |point| point.0 + Duration::days(1)),
+            granularity: Granularity::Day,
+            series: points
+                .iter()
+                .map(|&(start, screenshots)| UsagePoint {
+                    start,
+                    end: start + Duration::days(1),
+                    screenshots,
+                    bytes: screenshots * 1_000,
+                })
+                .collect(),
+            by_format: Vec::new(),
+            by_delivery: DeliveryUsage::default(),
+            by_api_key: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn projection_at_period_start_is_current_usage() {
+        let usage = april(5, 500);
+        for now in [date(1), date(1) - Duration::days(3)] {
+            let projection = usage.project_at(now);
+            assert_eq!(
+                (projection.screenshots, projection.bytes),
+                (5, 500),
+                "at {}",
+                now
+            );
+            assert_eq!(projection.period_end, usage.period_end);
+        }
+    }
+
+    #[test]
+    fn projection_extrapolates_period_to_date_rate() {
+        let projection = april(100, 1_000).project_at(date(16));
+        assert_eq!((projection.screenshots, projection.bytes), (200, 2_000));
+
+        let projection = april(10, 0).project_at(date(4));
+        assert_eq!(projection.screenshots, 100, "10 in 3 days over 30 days");
+    }
+
+    #[test]
+    fn projection_at_and_after_period_end_is_final_usage() {
+        let usage = april(123, 4_567);
+        for now in [usage.period_end, usage.period_end + Duration::days(10)] {
+            let projection = usage.project_at(now);
+            assert_eq!(
+                (projection.screenshots, projection.bytes),
+                (123, 4_567),
+                "at {}",
+                now
+            );
+        }
+    }
+
+    #[test]
+    fn history_totals_and_rate() {
+        let history = history(&[(date(1), 10), (date(2), 20), (date(3), 30)]);
+        assert_eq!(
+            history.totals(),
+            UsageTotals {
+                screenshots: 60,
+                bytes: 60_000
+            }
+        );
+
+        let rate = history.rate().unwrap();
+        assert_eq!(rate.screenshots_per_day, 20.0);
+        assert_eq!(rate.bytes_per_day, 20_000.0);
+    }
+
+    #[test]
+    fn empty_history_has_no_rate() {
+        assert_eq!(history(&[]).rate(), None);
+        assert_eq!(history(&[]).totals(), UsageTotals::default());
+    }
+
+    #[test]
+    fn history_projection_uses_series_rate() {
+        let today = Utc::now();
+        let history = history(&[
+            (today - Duration::days(10), 40),
+            (today - Duration::days(9), 60),
+        ]);
+        let usage = Usage {
+            screenshots: 7,
+            bytes: 0,
+            period_start: today - Duration::days(1),
+            period_end: today + Duration::days(10),
+        };
+
+        // 100 screenshots over the two days of the series, for ten more days.
+        let projection = history.project(&usage);
+        assert!(
+            (506..=508).contains(&projection.screenshots),
+            "screenshots = {}",
+            projection.screenshots
+        );
+    }
+
+    #[test]
+    fn history_projection_falls_back_to_period_rate() {
+        let usage = april(9, 9);
+        let projection = history(&[]).project(&usage);
+        assert_eq!(
+            projection.screenshots, 9,
+            "a finished period projects its usage"
+        );
+    }
+
+    #[test]
+    fn history_deserializes_with_optional_breakdowns() {
+        let json = r#"{
+            "start": "2024-04-01T00:00:00Z",
+            "end": "2024-04-03T00:00:00Z",
+            "granularity": "period",
+            "series": [
+                {"start": "2024-04-01T00:00:00Z", "end": "2024-04-02T00:00:00Z", "screenshots": 3, "bytes": 30}
+            ]
+        }"#;
+        let history: UsageHistory = serde_json::from_str(json).unwrap();
+        assert_eq!(history.granularity, Granularity::Period);
+        assert_eq!(history.totals().screenshots, 3);
+        assert!(history.by_format.is_empty());
+        assert_eq!(history.by_delivery.stored, UsageTotals::default());
+    }
+
+    #[test]
+    fn ranges_default_to_daily_granularity() {
+        let range = UsageRange::last_days(7);
+        assert_eq!(range.granularity, Granularity::Day);
+        assert_eq!(range.end - range.start, Duration::days(7));
+        assert_eq!(
+            range.granularity(Granularity::Period).granularity,
+            Granularity::Period
+        );
+    }
+}